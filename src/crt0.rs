@@ -20,6 +20,7 @@ pub type ExitFn = fn(ResultCode) -> !;
 
 static mut G_EXIT_FN: sync::Locked<Option<ExitFn>> = sync::Locked::new(false, None);
 static mut G_MAIN_THREAD: thread::Thread = thread::Thread::empty();
+static mut G_BASE_ADDRESS: sync::Locked<*const u8> = sync::Locked::new(false, ptr::null());
 
 #[no_mangle]
 #[linkage = "weak"]
@@ -39,6 +40,7 @@ unsafe fn __nx_crt0_entry(
 
     // Relocate ourselves
     dynamic::relocate(aslr_base_address).unwrap();
+    G_BASE_ADDRESS.set(aslr_base_address);
 
     let mut heap = util::PointerAndSize::new(ptr::null_mut(), 0);
     let mut main_thread_handle = raw_main_thread_handle as svc::Handle;
@@ -59,6 +61,9 @@ unsafe fn __nx_crt0_entry(
                 hbl::AbiConfigEntryKey::MainThreadHandle => {
                     main_thread_handle = (*abi_entry).value[0] as svc::Handle;
                 }
+                hbl::AbiConfigEntryKey::Argv => {
+                    hbl::set_argv((*abi_entry).value[1] as *const u8);
+                }
                 hbl::AbiConfigEntryKey::HosVersion => {
                     let hos_version_v = (*abi_entry).value[0] as u32;
                     hos_version = hbl::Version::new(hos_version_v);
@@ -125,6 +130,10 @@ unsafe fn __nx_crt0_exception_entry(_error_desc: u32, _stack_top: *mut u8) {
     svc::return_from_exception(results::os::ResultUnhandledException::make());
 }
 
+pub fn get_base_address() -> *const u8 {
+    unsafe { *G_BASE_ADDRESS.get() }
+}
+
 pub fn exit(rc: ResultCode) -> ! {
     unsafe {
         match G_EXIT_FN.get() {
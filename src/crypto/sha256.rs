@@ -0,0 +1,204 @@
+use core::cmp;
+
+// Software SHA-256, for verifying hashes stored in container formats
+
+pub const HASH_SIZE: usize = 0x20;
+pub const BLOCK_SIZE: usize = 0x40;
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19,
+];
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428A2F98, 0x71374491, 0xB5C0FBCF, 0xE9B5DBA5, 0x3956C25B, 0x59F111F1, 0x923F82A4, 0xAB1C5ED5,
+    0xD807AA98, 0x12835B01, 0x243185BE, 0x550C7DC3, 0x72BE5D74, 0x80DEB1FE, 0x9BDC06A7, 0xC19BF174,
+    0xE49B69C1, 0xEFBE4786, 0x0FC19DC6, 0x240CA1CC, 0x2DE92C6F, 0x4A7484AA, 0x5CB0A9DC, 0x76F988DA,
+    0x983E5152, 0xA831C66D, 0xB00327C8, 0xBF597FC7, 0xC6E00BF3, 0xD5A79147, 0x06CA6351, 0x14292967,
+    0x27B70A85, 0x2E1B2138, 0x4D2C6DFC, 0x53380D13, 0x650A7354, 0x766A0ABB, 0x81C2C92E, 0x92722C85,
+    0xA2BFE8A1, 0xA81A664B, 0xC24B8B70, 0xC76C51A3, 0xD192E819, 0xD6990624, 0xF40E3585, 0x106AA070,
+    0x19A4C116, 0x1E376C08, 0x2748774C, 0x34B0BCB5, 0x391C0CB3, 0x4ED8AA4A, 0x5B9CCA4F, 0x682E6FF3,
+    0x748F82EE, 0x78A5636F, 0x84C87814, 0x8CC70208, 0x90BEFFFA, 0xA4506CEB, 0xBEF9A3F7, 0xC67178F2,
+];
+
+pub struct Context {
+    state: [u32; 8],
+    block: [u8; BLOCK_SIZE],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Context {
+    pub const fn new() -> Self {
+        Self {
+            state: INITIAL_STATE,
+            block: [0; BLOCK_SIZE],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn process_block(&mut self) {
+        let mut w = [0u32; 64];
+        for (word, bytes) in w.iter_mut().zip(self.block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (&round_constant, &word) in ROUND_CONSTANTS.iter().zip(w.iter()) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(round_constant)
+                .wrapping_add(word);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        for (state_val, new_val) in self.state.iter_mut().zip(&[a, b, c, d, e, f, g, h]) {
+            *state_val = state_val.wrapping_add(*new_val);
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.total_len += data.len() as u64;
+        let mut data_offset = 0;
+        while data_offset < data.len() {
+            let copy_size = cmp::min(BLOCK_SIZE - self.block_len, data.len() - data_offset);
+            self.block[self.block_len..self.block_len + copy_size]
+                .copy_from_slice(&data[data_offset..data_offset + copy_size]);
+            self.block_len += copy_size;
+            data_offset += copy_size;
+
+            if self.block_len == BLOCK_SIZE {
+                self.process_block();
+                self.block_len = 0;
+            }
+        }
+    }
+
+    pub fn finalize(mut self) -> [u8; HASH_SIZE] {
+        let bit_len = self.total_len.wrapping_mul(8);
+
+        // Padding: a single 1 bit, zeroes and the message length in bits
+        self.block[self.block_len] = 0x80;
+        self.block_len += 1;
+        if self.block_len > (BLOCK_SIZE - 8) {
+            for byte in self.block[self.block_len..].iter_mut() {
+                *byte = 0;
+            }
+            self.process_block();
+            self.block_len = 0;
+        }
+        for byte in self.block[self.block_len..BLOCK_SIZE - 8].iter_mut() {
+            *byte = 0;
+        }
+        self.block[BLOCK_SIZE - 8..].copy_from_slice(&bit_len.to_be_bytes());
+        self.process_block();
+
+        let mut hash = [0u8; HASH_SIZE];
+        for (i, state_val) in self.state.iter().enumerate() {
+            hash[i * 4..i * 4 + 4].copy_from_slice(&state_val.to_be_bytes());
+        }
+        hash
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn hash(data: &[u8]) -> [u8; HASH_SIZE] {
+    let mut ctx = Context::new();
+    ctx.update(data);
+    ctx.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn parse_hash(hex: &str) -> [u8; HASH_SIZE] {
+        let mut hash = [0u8; HASH_SIZE];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        hash
+    }
+
+    // Test vectors from FIPS 180-2 / NIST's SHA examples
+    #[test]
+    fn hash_empty() {
+        assert_eq!(
+            hash(b""),
+            parse_hash("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+    }
+
+    #[test]
+    fn hash_abc() {
+        assert_eq!(
+            hash(b"abc"),
+            parse_hash("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[test]
+    fn hash_448_bits() {
+        assert_eq!(
+            hash(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            parse_hash("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")
+        );
+    }
+
+    #[test]
+    fn hash_million_a_streamed() {
+        // Uneven chunks, so that updates keep straddling block boundaries
+        let data = [b'a'; 997];
+        let mut ctx = Context::default();
+        let mut remaining = 1_000_000;
+        while remaining > 0 {
+            let chunk_size = cmp::min(remaining, data.len());
+            ctx.update(&data[..chunk_size]);
+            remaining -= chunk_size;
+        }
+        assert_eq!(
+            ctx.finalize(),
+            parse_hash("cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0")
+        );
+    }
+
+    #[test]
+    fn streamed_update_matches_hash() {
+        let data: Vec<u8> = (0..300u32).map(|i| (i * 7) as u8).collect();
+        for &chunk_size in &[1, 3, BLOCK_SIZE - 1, BLOCK_SIZE, BLOCK_SIZE + 1] {
+            let mut ctx = Context::new();
+            for chunk in data.chunks(chunk_size) {
+                ctx.update(chunk);
+            }
+            assert_eq!(ctx.finalize(), hash(&data));
+        }
+    }
+}
@@ -9,12 +9,14 @@ pub enum Tag {
     Invalid = 0,
     Needed = 1,
     PltRelSize = 2,
+    PltGot = 3,
     Hash = 4,
     StrTab = 5,
     SymTab = 6,
     RelaOffset = 7,
     RelaSize = 8,
     RelaEntrySize = 9,
+    StrSize = 10,
     SymEnt = 11,
     RelOffset = 17,
     RelSize = 18,
@@ -25,22 +27,29 @@ pub enum Tag {
     FiniArray = 26,
     InitArraySize = 27,
     FiniArraySize = 28,
+    RelrSize = 35,
+    Relr = 36,
+    RelrEntrySize = 37,
+    GnuHash = 0x6FFFFEF5,
     RelaCount = 0x6FFFFFF9,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u32)]
 pub enum RelocationType {
+    None = 0,
     AArch64Abs64 = 257,
     AArch64GlobDat = 1025,
     AArch64JumpSlot = 1026,
     AArch64Relative = 1027,
 }
 
+// Tags are kept as raw values, since tables may have tags we don't know about
+// (which can't be represented by Tag)
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
 #[repr(C)]
 pub struct Dyn {
-    pub tag: Tag,
+    pub tag: i64,
     pub val_ptr: u64,
 }
 
@@ -50,8 +59,13 @@ impl Dyn {
             let mut found: *const u64 = ptr::null();
             let mut self_ptr = self as *const Self;
 
-            while (*self_ptr).tag != Tag::Invalid {
-                if (*self_ptr).tag == tag {
+            let tag_value = tag as i64;
+            loop {
+                let cur_tag_value = (*self_ptr).tag;
+                if cur_tag_value == Tag::Invalid as i64 {
+                    break;
+                }
+                if cur_tag_value == tag_value {
                     result_return_unless!(
                         found.is_null(),
                         results::lib::elf::ResultDuplicatedDtEntry
@@ -70,7 +84,7 @@ impl Dyn {
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub struct InfoSymbol {
-    pub relocation_type: RelocationType,
+    pub relocation_type: u32,
     pub symbol: u32,
 }
 
@@ -81,6 +95,19 @@ pub union Info {
     pub symbol: InfoSymbol,
 }
 
+impl RelocationType {
+    pub fn from_raw(raw_type: u32) -> Option<Self> {
+        match raw_type {
+            0 => Some(Self::None),
+            257 => Some(Self::AArch64Abs64),
+            1025 => Some(Self::AArch64GlobDat),
+            1026 => Some(Self::AArch64JumpSlot),
+            1027 => Some(Self::AArch64Relative),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub struct Rela {
@@ -88,3 +115,103 @@ pub struct Rela {
     pub info: Info,
     pub addend: i64,
 }
+
+impl Rela {
+    // Unknown types can't be represented by RelocationType, hence the raw value
+    pub fn get_relocation_type(&self) -> Option<RelocationType> {
+        unsafe { RelocationType::from_raw(self.info.symbol.relocation_type) }
+    }
+
+    pub fn get_symbol_index(&self) -> u32 {
+        unsafe { self.info.symbol.symbol }
+    }
+}
+
+// Plain RELA relocations (DT_RELA) and PLT ones (DT_JMPREL) use the same format
+pub const PLT_RELOCATION_FORMAT_RELA: u64 = Tag::RelaOffset as u64;
+
+pub type Relr = u64;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum SymbolType {
+    NoType = 0,
+    Object = 1,
+    Function = 2,
+    Section = 3,
+    File = 4,
+    Common = 5,
+    Tls = 6,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum SymbolBinding {
+    Local = 0,
+    Global = 1,
+    Weak = 2,
+}
+
+pub const SECTION_INDEX_UNDEFINED: u16 = 0;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct Sym {
+    pub name: u32,
+    pub info: u8,
+    pub other: u8,
+    pub section_index: u16,
+    pub value: u64,
+    pub size: u64,
+}
+
+impl Sym {
+    pub fn get_type(&self) -> Option<SymbolType> {
+        match self.info & 0xF {
+            0 => Some(SymbolType::NoType),
+            1 => Some(SymbolType::Object),
+            2 => Some(SymbolType::Function),
+            3 => Some(SymbolType::Section),
+            4 => Some(SymbolType::File),
+            5 => Some(SymbolType::Common),
+            6 => Some(SymbolType::Tls),
+            _ => None,
+        }
+    }
+
+    pub fn get_binding(&self) -> Option<SymbolBinding> {
+        match self.info >> 4 {
+            0 => Some(SymbolBinding::Local),
+            1 => Some(SymbolBinding::Global),
+            2 => Some(SymbolBinding::Weak),
+            _ => None,
+        }
+    }
+
+    pub fn is_defined(&self) -> bool {
+        self.section_index != SECTION_INDEX_UNDEFINED
+    }
+}
+
+// Hash function used by DT_HASH tables
+pub fn hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 0;
+    for &c in name {
+        h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xF0000000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+    }
+    h
+}
+
+// Hash function used by DT_GNU_HASH tables
+pub fn gnu_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 5381;
+    for &c in name {
+        h = h.wrapping_mul(33).wrapping_add(c as u32);
+    }
+    h
+}
@@ -0,0 +1,302 @@
+use crate::{
+    crypto::sha256,
+    dynamic,
+    dynamic::elf,
+    fs, io,
+    io::{Read, Seek},
+    ipc::cmif::sf,
+    mem, nro,
+    result::*,
+    results, service,
+    service::cmif::{ro, ro::IRoInterface},
+    svc, sync,
+};
+use alloc::{
+    alloc::{alloc_zeroed, dealloc, Layout},
+    string::String,
+    vec::Vec,
+};
+use core::{mem as cmem, ptr, slice};
+
+// Runtime loading of NRO modules through ldr:ro: the NRO is read into
+// page-aligned memory, registered through a generated NRR holding its hash,
+// mapped by ro and then relocated and initialized by us
+
+pub const NRR_MAGIC: u32 = u32::from_le_bytes(*b"NRR0");
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct NrrCertification {
+    pub program_id_mask: u64,
+    pub program_id_pattern: u64,
+    pub reserved: [u8; 0x10],
+    pub signed_key_modulus: [u8; 0x100],
+    pub signed_key_signature: [u8; 0x100],
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct NrrHeader {
+    pub magic: u32,
+    pub key_generation: u32,
+    pub reserved: [u8; 0x8],
+    pub certification: NrrCertification,
+    pub signature: [u8; 0x100],
+    pub program_id: u64,
+    pub size: u32,
+    pub nrr_type: u8,
+    pub reserved_2: [u8; 0x3],
+    pub hashes_offset: u32,
+    pub hash_count: u32,
+    pub reserved_3: [u8; 0x8],
+}
+
+struct PageBuffer {
+    address: *mut u8,
+    layout: Layout,
+}
+
+impl PageBuffer {
+    fn new(size: usize) -> Result<Self> {
+        let layout = match Layout::from_size_align(
+            mem::align_up(size, mem::PAGE_ALIGNMENT),
+            mem::PAGE_ALIGNMENT,
+        ) {
+            Ok(layout) => layout,
+            Err(_) => return Err(results::lib::dynamic::ResultOutOfMemory::make()),
+        };
+
+        let address = unsafe { alloc_zeroed(layout) };
+        result_return_if!(address.is_null(), results::lib::dynamic::ResultOutOfMemory);
+        Ok(Self { address, layout })
+    }
+
+    fn get_size(&self) -> usize {
+        self.layout.size()
+    }
+}
+
+impl Drop for PageBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            dealloc(self.address, self.layout);
+        }
+    }
+}
+
+static mut G_RO_SESSION: sync::Locked<mem::Shared<ro::RoInterface>> =
+    sync::Locked::new(false, mem::Shared::empty());
+static mut G_LOADED_MODULES: sync::Locked<Vec<dynamic::Module>> =
+    sync::Locked::new(false, Vec::new());
+
+fn get_ro_session() -> Result<mem::Shared<ro::RoInterface>> {
+    unsafe {
+        if G_RO_SESSION.get().is_null() {
+            G_RO_SESSION.set(service::cmif::new_service_object()?);
+        }
+        Ok(G_RO_SESSION.get().clone())
+    }
+}
+
+// Imports are resolved against the main module first, then against every
+// module loaded so far (in load order)
+fn resolve_import(name: &str) -> Option<*const u8> {
+    if let Ok(main_module) = dynamic::Module::get_current() {
+        if let Ok(address) = main_module.find_symbol(name) {
+            return Some(address);
+        }
+    }
+
+    unsafe {
+        for module in G_LOADED_MODULES.get().iter() {
+            if let Ok(address) = module.find_symbol(name) {
+                return Some(address);
+            }
+        }
+    }
+    None
+}
+
+fn create_nrr(nro_hash: &[u8; sha256::HASH_SIZE]) -> Result<PageBuffer> {
+    let hashes_offset = cmem::size_of::<NrrHeader>();
+    let nrr = PageBuffer::new(hashes_offset + sha256::HASH_SIZE)?;
+    let program_id = svc::get_info(
+        svc::InfoId::ProgramId,
+        svc::CURRENT_PROCESS_PSEUDO_HANDLE,
+        0,
+    )?;
+
+    unsafe {
+        let mut header: NrrHeader = cmem::zeroed();
+        header.magic = NRR_MAGIC;
+        header.program_id = program_id;
+        header.size = nrr.get_size() as u32;
+        header.hashes_offset = hashes_offset as u32;
+        header.hash_count = 1;
+        ptr::write(nrr.address as *mut NrrHeader, header);
+        ptr::copy(
+            nro_hash.as_ptr(),
+            nrr.address.add(hashes_offset),
+            sha256::HASH_SIZE,
+        );
+    }
+    Ok(nrr)
+}
+
+unsafe fn run_init_array(base_address: *const u8, dynamic: *const elf::Dyn) -> Result<()> {
+    let init_array = dynamic::find_optional_value(dynamic, elf::Tag::InitArray)?;
+    let init_array_size = dynamic::find_optional_value(dynamic, elf::Tag::InitArraySize)?;
+    if let (Some(init_array), Some(init_array_size)) = (init_array, init_array_size) {
+        let init_fns = base_address.add(init_array as usize) as *const extern "C" fn();
+        for i in 0..(init_array_size as usize / cmem::size_of::<usize>()) {
+            (*init_fns.add(i))();
+        }
+    }
+    Ok(())
+}
+
+unsafe fn run_fini_array(base_address: *const u8, dynamic: *const elf::Dyn) -> Result<()> {
+    let fini_array = dynamic::find_optional_value(dynamic, elf::Tag::FiniArray)?;
+    let fini_array_size = dynamic::find_optional_value(dynamic, elf::Tag::FiniArraySize)?;
+    if let (Some(fini_array), Some(fini_array_size)) = (fini_array, fini_array_size) {
+        let fini_fns = base_address.add(fini_array as usize) as *const extern "C" fn();
+        for i in (0..(fini_array_size as usize / cmem::size_of::<usize>())).rev() {
+            (*fini_fns.add(i))();
+        }
+    }
+    Ok(())
+}
+
+// The NRO and .bss buffers are owned by ro while mapped, they're only kept
+// here so they get freed after unmapping
+pub struct LoadedModule {
+    module: dynamic::Module,
+    _nro: PageBuffer,
+    _bss: Option<PageBuffer>,
+    nrr: PageBuffer,
+}
+
+impl LoadedModule {
+    pub fn get_module(&self) -> &dynamic::Module {
+        &self.module
+    }
+
+    pub fn get_base_address(&self) -> *const u8 {
+        self.module.get_base_address()
+    }
+
+    pub fn find_symbol(&self, name: &str) -> Result<*const u8> {
+        self.module.find_symbol(name)
+    }
+}
+
+impl Drop for LoadedModule {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = run_fini_array(self.module.get_base_address(), self.module.get_dynamic());
+
+            let base_address = self.module.get_base_address();
+            G_LOADED_MODULES
+                .get()
+                .retain(|module| module.get_base_address() != base_address);
+
+            if let Ok(ro_srv) = get_ro_session() {
+                let _ = ro_srv
+                    .get()
+                    .unmap_manual_load_module_memory(sf::ProcessId::new(), base_address as u64);
+                let _ = ro_srv
+                    .get()
+                    .unregister_module_info(sf::ProcessId::new(), self.nrr.address as u64);
+            }
+        }
+    }
+}
+
+pub fn load_module(path: String) -> Result<LoadedModule> {
+    let mut nro_file = fs::open_file(path, fs::FileOpenOption::Read())?;
+
+    nro_file.seek(io::SeekFrom::Start(nro::HEADER_OFFSET as u64))?;
+    let header: nro::Header = nro_file.read_val()?;
+    result_return_unless!(
+        header.magic == nro::MAGIC,
+        results::lib::nro::ResultInvalidHeader
+    );
+
+    // Only the NRO itself gets mapped, any asset section after it is ignored
+    let nro_size = header.size as usize;
+    result_return_unless!(
+        mem::align_up(nro_size, mem::PAGE_ALIGNMENT) == nro_size,
+        results::lib::nro::ResultInvalidHeader
+    );
+    let nro = PageBuffer::new(nro_size)?;
+    let nro_data = unsafe { slice::from_raw_parts_mut(nro.address, nro_size) };
+    nro_file.seek(io::SeekFrom::Start(0))?;
+    nro_file.read_exact(nro_data)?;
+    drop(nro_file);
+
+    let bss = match header.bss_size {
+        0 => None,
+        bss_size => Some(PageBuffer::new(bss_size as usize)?),
+    };
+    let (bss_address, bss_size) = match bss {
+        Some(ref bss) => (bss.address as u64, bss.get_size() as u64),
+        None => (0, 0),
+    };
+
+    let nrr = create_nrr(&sha256::hash(nro_data))?;
+    let ro_srv = get_ro_session()?;
+    ro_srv.get().register_module_info(
+        sf::ProcessId::new(),
+        nrr.address as u64,
+        nrr.get_size() as u64,
+    )?;
+
+    let base_address = match ro_srv.get().map_manual_load_module_memory(
+        sf::ProcessId::new(),
+        nro.address as u64,
+        nro_size as u64,
+        bss_address,
+        bss_size,
+    ) {
+        Ok(base_address) => base_address as *const u8,
+        Err(rc) => {
+            let _ = ro_srv
+                .get()
+                .unregister_module_info(sf::ProcessId::new(), nrr.address as u64);
+            return Err(rc);
+        }
+    };
+
+    let module_load = || -> Result<dynamic::Module> {
+        let dynamic = dynamic::find_dynamic(base_address)?;
+        dynamic::relocate_with_dyn_and_resolver(base_address, dynamic, Some(resolve_import))?;
+        let module = dynamic::Module::new(base_address, dynamic)?;
+        unsafe {
+            run_init_array(base_address, dynamic)?;
+        }
+        Ok(module)
+    };
+    let module = match module_load() {
+        Ok(module) => module,
+        Err(rc) => {
+            let _ = ro_srv
+                .get()
+                .unmap_manual_load_module_memory(sf::ProcessId::new(), base_address as u64);
+            let _ = ro_srv
+                .get()
+                .unregister_module_info(sf::ProcessId::new(), nrr.address as u64);
+            return Err(rc);
+        }
+    };
+
+    // Only fully initialized modules are made visible to later imports
+    unsafe {
+        G_LOADED_MODULES.get().push(module);
+    }
+    Ok(LoadedModule {
+        module,
+        _nro: nro,
+        _bss: bss,
+        nrr,
+    })
+}
@@ -1,4 +1,5 @@
-use crate::{result::*, results};
+use crate::{crt0, result::*, results};
+use core::{mem as cmem, ptr, slice, str};
 
 #[derive(Copy, Clone)]
 #[repr(C)]
@@ -7,36 +8,171 @@ pub struct ModuleStart {
     pub magic_offset: u32,
 }
 
-pub fn relocate_with_dyn(base_address: *const u8, dynamic: *const elf::Dyn) -> Result<()> {
+// Resolves symbols the module imports (undefined in its own symbol table)
+pub type ImportResolverFn = fn(&str) -> Option<*const u8>;
+
+fn find_optional_value(dynamic: *const elf::Dyn, tag: elf::Tag) -> Result<Option<u64>> {
     unsafe {
-        let rela_offset = (*dynamic).find_value(elf::Tag::RelaOffset)?;
-        let rela_size = (*dynamic).find_value(elf::Tag::RelaSize)?;
-        let rela_entry_size = (*dynamic).find_value(elf::Tag::RelaEntrySize)?;
-        let rela_count = (*dynamic).find_value(elf::Tag::RelaCount)?;
-        result_return_unless!(
-            rela_size == rela_entry_size * rela_count,
-            results::lib::dynamic::ResultRelaSizeMismatch
-        );
+        match (*dynamic).find_value(tag) {
+            Ok(value) => Ok(Some(value)),
+            Err(rc) if results::lib::elf::ResultMissingDtEntry::matches(rc) => Ok(None),
+            Err(rc) => Err(rc),
+        }
+    }
+}
+
+fn resolve_symbol(
+    module: Option<&Module>,
+    symbol_index: u32,
+    import_resolver: Option<ImportResolverFn>,
+) -> Result<*const u8> {
+    let module = match module {
+        Some(module) => module,
+        None => return Err(results::lib::dynamic::ResultUnresolvedSymbol::make()),
+    };
+
+    let sym = module.get_symbol(symbol_index as usize);
+    if sym.is_defined() {
+        return unsafe { Ok(module.get_base_address().add(sym.value as usize)) };
+    }
+
+    if let Some(import_resolver) = import_resolver {
+        if let Some(address) = module
+            .get_symbol_name(&sym)
+            .and_then(|name| import_resolver(name))
+        {
+            return Ok(address);
+        }
+    }
+
+    // Unresolved weak imports are allowed to stay null
+    result_return_unless!(
+        sym.get_binding() == Some(elf::SymbolBinding::Weak),
+        results::lib::dynamic::ResultUnresolvedSymbol
+    );
+    Ok(ptr::null())
+}
 
-        let rela_base = base_address.offset(rela_offset as isize) as *const elf::Rela;
+fn apply_rela_relocations(
+    base_address: *const u8,
+    module: Option<&Module>,
+    import_resolver: Option<ImportResolverFn>,
+    rela_offset: u64,
+    rela_size: u64,
+    rela_entry_size: u64,
+) -> Result<()> {
+    result_return_unless!(
+        (rela_entry_size != 0) && ((rela_size % rela_entry_size) == 0),
+        results::lib::dynamic::ResultRelaSizeMismatch
+    );
+
+    unsafe {
+        let rela_count = rela_size / rela_entry_size;
         for i in 0..rela_count {
-            let rela = rela_base.offset(i as isize);
-            match (*rela).info.symbol.relocation_type {
-                elf::RelocationType::AArch64Relative => {
-                    if (*rela).info.symbol.symbol == 0 {
-                        let relocation_offset =
-                            base_address.offset((*rela).offset as isize) as *mut *const u8;
-                        *relocation_offset = base_address.offset((*rela).addend as isize);
+            let rela = &*(base_address.add((rela_offset + i * rela_entry_size) as usize)
+                as *const elf::Rela);
+            let relocation_target = base_address.add(rela.offset as usize) as *mut *const u8;
+            match rela.get_relocation_type() {
+                Some(elf::RelocationType::None) => {}
+                Some(elf::RelocationType::AArch64Relative) => {
+                    *relocation_target = base_address.offset(rela.addend as isize);
+                }
+                Some(elf::RelocationType::AArch64Abs64)
+                | Some(elf::RelocationType::AArch64GlobDat)
+                | Some(elf::RelocationType::AArch64JumpSlot) => {
+                    let symbol_address =
+                        resolve_symbol(module, rela.get_symbol_index(), import_resolver)?;
+                    *relocation_target = symbol_address.wrapping_offset(rela.addend as isize);
+                }
+                None => return Err(results::lib::dynamic::ResultUnknownRelocationType::make()),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_relr_relocations(base_address: *const u8, relr_offset: u64, relr_size: u64) {
+    // Each entry is either an address to relocate, or (odd values) a bitmap of
+    // the next 63 words to relocate after the last address
+    unsafe {
+        let relr_count = relr_size as usize / cmem::size_of::<elf::Relr>();
+        let relr_base = base_address.add(relr_offset as usize) as *const elf::Relr;
+        let mut relocation_target: *mut usize = ptr::null_mut();
+        for i in 0..relr_count {
+            let entry = *relr_base.add(i);
+            if (entry & 1) == 0 {
+                relocation_target = base_address.add(entry as usize) as *mut usize;
+                *relocation_target += base_address as usize;
+                relocation_target = relocation_target.add(1);
+            } else {
+                let mut bitmap = entry >> 1;
+                let mut bitmap_target = relocation_target;
+                while bitmap != 0 {
+                    if (bitmap & 1) != 0 {
+                        *bitmap_target += base_address as usize;
                     }
+                    bitmap >>= 1;
+                    bitmap_target = bitmap_target.add(1);
                 }
-                _ => {}
+                relocation_target = relocation_target.add(63);
             }
         }
     }
+}
+
+pub fn relocate_with_dyn_and_resolver(
+    base_address: *const u8,
+    dynamic: *const elf::Dyn,
+    import_resolver: Option<ImportResolverFn>,
+) -> Result<()> {
+    // Modules without symbol tables can still have relative relocations
+    let module = Module::new(base_address, dynamic).ok();
+
+    if let Some(rela_offset) = find_optional_value(dynamic, elf::Tag::RelaOffset)? {
+        let rela_size = find_optional_value(dynamic, elf::Tag::RelaSize)?.unwrap_or(0);
+        let rela_entry_size = find_optional_value(dynamic, elf::Tag::RelaEntrySize)?
+            .unwrap_or(cmem::size_of::<elf::Rela>() as u64);
+        apply_rela_relocations(
+            base_address,
+            module.as_ref(),
+            import_resolver,
+            rela_offset,
+            rela_size,
+            rela_entry_size,
+        )?;
+    }
+
+    if let Some(jmp_rel_offset) = find_optional_value(dynamic, elf::Tag::JmpRel)? {
+        let plt_rel_size = find_optional_value(dynamic, elf::Tag::PltRelSize)?.unwrap_or(0);
+        let plt_rel_format = find_optional_value(dynamic, elf::Tag::PltRel)?
+            .unwrap_or(elf::PLT_RELOCATION_FORMAT_RELA);
+        result_return_unless!(
+            plt_rel_format == elf::PLT_RELOCATION_FORMAT_RELA,
+            results::lib::dynamic::ResultUnsupportedRelocationFormat
+        );
+        apply_rela_relocations(
+            base_address,
+            module.as_ref(),
+            import_resolver,
+            jmp_rel_offset,
+            plt_rel_size,
+            cmem::size_of::<elf::Rela>() as u64,
+        )?;
+    }
+
+    if let Some(relr_offset) = find_optional_value(dynamic, elf::Tag::Relr)? {
+        let relr_size = find_optional_value(dynamic, elf::Tag::RelrSize)?.unwrap_or(0);
+        apply_relr_relocations(base_address, relr_offset, relr_size);
+    }
+
     Ok(())
 }
 
-pub fn relocate(base_address: *const u8) -> Result<()> {
+pub fn relocate_with_dyn(base_address: *const u8, dynamic: *const elf::Dyn) -> Result<()> {
+    relocate_with_dyn_and_resolver(base_address, dynamic, None)
+}
+
+pub fn find_dynamic(base_address: *const u8) -> Result<*const elf::Dyn> {
     unsafe {
         let module_start = base_address as *const ModuleStart;
         let mod_offset = (*module_start).magic_offset as isize;
@@ -47,11 +183,243 @@ pub fn relocate(base_address: *const u8) -> Result<()> {
         );
 
         let dyn_offset = mod_offset + (*module).dynamic as isize;
-        let dynamic = base_address.offset(dyn_offset) as *const elf::Dyn;
-        relocate_with_dyn(base_address, dynamic)
+        Ok(base_address.offset(dyn_offset) as *const elf::Dyn)
+    }
+}
+
+pub fn relocate(base_address: *const u8) -> Result<()> {
+    let dynamic = find_dynamic(base_address)?;
+    relocate_with_dyn(base_address, dynamic)
+}
+
+pub fn relocate_with_resolver(
+    base_address: *const u8,
+    import_resolver: ImportResolverFn,
+) -> Result<()> {
+    let dynamic = find_dynamic(base_address)?;
+    relocate_with_dyn_and_resolver(base_address, dynamic, Some(import_resolver))
+}
+
+fn find_optional_address<T>(
+    base_address: *const u8,
+    dynamic: *const elf::Dyn,
+    tag: elf::Tag,
+) -> Result<*const T> {
+    unsafe {
+        match (*dynamic).find_value(tag) {
+            Ok(offset) => Ok(base_address.offset(offset as isize) as *const T),
+            Err(rc) if results::lib::elf::ResultMissingDtEntry::matches(rc) => Ok(ptr::null()),
+            Err(rc) => Err(rc),
+        }
+    }
+}
+
+// Loaded module (the running one or any other one mapped in our address
+// space), giving access to its dynamic symbols
+#[derive(Copy, Clone)]
+pub struct Module {
+    base_address: *const u8,
+    dynamic: *const elf::Dyn,
+    symtab: *const elf::Sym,
+    strtab: *const u8,
+    hash: *const u32,
+    gnu_hash: *const u32,
+}
+
+impl Module {
+    pub fn new(base_address: *const u8, dynamic: *const elf::Dyn) -> Result<Self> {
+        let symtab = find_optional_address(base_address, dynamic, elf::Tag::SymTab)?;
+        let strtab = find_optional_address(base_address, dynamic, elf::Tag::StrTab)?;
+        result_return_if!(
+            symtab.is_null() || strtab.is_null(),
+            results::lib::elf::ResultMissingDtEntry
+        );
+
+        Ok(Self {
+            base_address,
+            dynamic,
+            symtab,
+            strtab,
+            hash: find_optional_address(base_address, dynamic, elf::Tag::Hash)?,
+            gnu_hash: find_optional_address(base_address, dynamic, elf::Tag::GnuHash)?,
+        })
+    }
+
+    pub fn from_base_address(base_address: *const u8) -> Result<Self> {
+        Self::new(base_address, find_dynamic(base_address)?)
+    }
+
+    pub fn get_current() -> Result<Self> {
+        Self::from_base_address(crt0::get_base_address())
+    }
+
+    pub fn get_base_address(&self) -> *const u8 {
+        self.base_address
+    }
+
+    pub fn get_dynamic(&self) -> *const elf::Dyn {
+        self.dynamic
+    }
+
+    pub fn get_symbol(&self, index: usize) -> elf::Sym {
+        unsafe { *self.symtab.add(index) }
+    }
+
+    pub fn get_symbol_name(&self, sym: &elf::Sym) -> Option<&'static str> {
+        unsafe {
+            let name_ptr = self.strtab.add(sym.name as usize);
+            let mut name_len = 0;
+            while *name_ptr.add(name_len) != 0 {
+                name_len += 1;
+            }
+            str::from_utf8(slice::from_raw_parts(name_ptr, name_len)).ok()
+        }
+    }
+
+    fn get_gnu_hash_layout(&self) -> (u32, u32, *const u32, *const u32) {
+        // The table starts with nbuckets, symoffset, the bloom filter size (in
+        // 64-bit words) and the bloom shift, followed by the bloom filter, the
+        // buckets and the chains, only nbuckets, symoffset, buckets and chains
+        // are returned since the bloom filter isn't used
+        unsafe {
+            let bucket_count = *self.gnu_hash;
+            let sym_offset = *self.gnu_hash.add(1);
+            let bloom_size = *self.gnu_hash.add(2) as usize;
+            let buckets = self.gnu_hash.add(4 + bloom_size * 2);
+            let chains = buckets.add(bucket_count as usize);
+            (bucket_count, sym_offset, buckets, chains)
+        }
+    }
+
+    pub fn get_symbol_count(&self) -> usize {
+        unsafe {
+            if !self.hash.is_null() {
+                // The chain count matches the symbol count
+                return *self.hash.add(1) as usize;
+            }
+
+            if !self.gnu_hash.is_null() {
+                // Find the highest symbol in any bucket and walk its chain to the end
+                let (bucket_count, sym_offset, buckets, chains) = self.get_gnu_hash_layout();
+                let buckets = slice::from_raw_parts(buckets, bucket_count as usize);
+                let mut last_sym = match buckets.iter().max() {
+                    Some(&last_sym) if last_sym >= sym_offset => last_sym,
+                    _ => return sym_offset as usize,
+                };
+                while (*chains.add((last_sym - sym_offset) as usize) & 1) == 0 {
+                    last_sym += 1;
+                }
+                return last_sym as usize + 1;
+            }
+
+            0
+        }
+    }
+
+    fn is_matching_symbol(&self, sym: &elf::Sym, name: &str) -> bool {
+        sym.is_defined() && (self.get_symbol_name(sym) == Some(name))
+    }
+
+    fn find_symbol_gnu_hash(&self, name: &str) -> Option<elf::Sym> {
+        unsafe {
+            let (bucket_count, sym_offset, buckets, chains) = self.get_gnu_hash_layout();
+            if bucket_count == 0 {
+                return None;
+            }
+
+            let hash = elf::gnu_hash(name.as_bytes());
+            let mut sym_index = *buckets.add((hash % bucket_count) as usize);
+            if sym_index < sym_offset {
+                return None;
+            }
+            loop {
+                let chain_hash = *chains.add((sym_index - sym_offset) as usize);
+                if (hash | 1) == (chain_hash | 1) {
+                    let sym = self.get_symbol(sym_index as usize);
+                    if self.is_matching_symbol(&sym, name) {
+                        return Some(sym);
+                    }
+                }
+                if (chain_hash & 1) != 0 {
+                    return None;
+                }
+                sym_index += 1;
+            }
+        }
+    }
+
+    fn find_symbol_hash(&self, name: &str) -> Option<elf::Sym> {
+        unsafe {
+            let bucket_count = *self.hash;
+            if bucket_count == 0 {
+                return None;
+            }
+            let buckets = self.hash.add(2);
+            let chains = buckets.add(bucket_count as usize);
+
+            let hash = elf::hash(name.as_bytes());
+            let mut sym_index = *buckets.add((hash % bucket_count) as usize);
+            while sym_index != 0 {
+                let sym = self.get_symbol(sym_index as usize);
+                if self.is_matching_symbol(&sym, name) {
+                    return Some(sym);
+                }
+                sym_index = *chains.add(sym_index as usize);
+            }
+            None
+        }
+    }
+
+    pub fn find_symbol_entry(&self, name: &str) -> Option<elf::Sym> {
+        if !self.gnu_hash.is_null() {
+            self.find_symbol_gnu_hash(name)
+        } else if !self.hash.is_null() {
+            self.find_symbol_hash(name)
+        } else {
+            None
+        }
+    }
+
+    pub fn find_symbol(&self, name: &str) -> Result<*const u8> {
+        match self.find_symbol_entry(name) {
+            Some(sym) => unsafe { Ok(self.base_address.add(sym.value as usize)) },
+            None => Err(results::lib::dynamic::ResultSymbolNotFound::make()),
+        }
+    }
+
+    // Finds the symbol containing the address (or the closest one before it,
+    // for symbols without size), returning its name and the offset inside it
+    pub fn symbolize(&self, address: *const u8) -> Option<(&'static str, usize)> {
+        let address_offset = (address as usize).checked_sub(self.base_address as usize)? as u64;
+
+        let mut best_sym: Option<elf::Sym> = None;
+        for i in 0..self.get_symbol_count() {
+            let sym = self.get_symbol(i);
+            if !sym.is_defined() || (sym.value > address_offset) {
+                continue;
+            }
+            match sym.get_type() {
+                Some(elf::SymbolType::Function) | Some(elf::SymbolType::Object) => {}
+                _ => continue,
+            };
+
+            if (sym.size != 0) && (address_offset < (sym.value + sym.size)) {
+                best_sym = Some(sym);
+                break;
+            }
+            if (sym.size == 0) && best_sym.map_or(true, |best_sym| sym.value > best_sym.value) {
+                best_sym = Some(sym);
+            }
+        }
+
+        let sym = best_sym?;
+        let name = self.get_symbol_name(&sym)?;
+        Some((name, (address_offset - sym.value) as usize))
     }
 }
 
 pub mod elf;
 
 pub mod mod0;
+
+pub mod loader;
@@ -0,0 +1,444 @@
+use crate::{
+    fs,
+    ipc::cmif::sf,
+    mem,
+    result::*,
+    service::cmif::{
+        fspsrv,
+        fspsrv::{IDirectory, IFile, IFileSystem, IStorage},
+    },
+};
+
+// Adapters exposing fsp-srv IPC objects as fs backends
+
+pub struct File {
+    file: mem::Shared<fspsrv::File>,
+}
+
+impl File {
+    pub fn new(file: mem::Shared<fspsrv::File>) -> Self {
+        Self { file }
+    }
+}
+
+impl fs::FileHandle for File {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.file.get().read(
+            fspsrv::FileReadOption::None(),
+            offset,
+            buf.len(),
+            sf::Buffer::from_mut(buf.as_mut_ptr(), buf.len()),
+        )
+    }
+
+    fn write(&mut self, offset: usize, buf: &[u8], option: fs::FileWriteOption) -> Result<()> {
+        self.file
+            .get()
+            .write(option, offset, buf.len(), sf::Buffer::from_array(buf))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.get().flush()
+    }
+
+    fn set_size(&mut self, size: usize) -> Result<()> {
+        self.file.get().set_size(size)
+    }
+
+    fn get_size(&mut self) -> Result<usize> {
+        self.file.get().get_size()
+    }
+}
+
+pub struct Directory {
+    dir: mem::Shared<fspsrv::Directory>,
+}
+
+impl Directory {
+    pub fn new(dir: mem::Shared<fspsrv::Directory>) -> Self {
+        Self { dir }
+    }
+}
+
+impl fs::DirectoryHandle for Directory {
+    fn read(&mut self, entries: &mut [fs::DirectoryEntry]) -> Result<usize> {
+        let read_count = self.dir.get().read(sf::Buffer::from_array(entries))?;
+        Ok(read_count as usize)
+    }
+
+    fn get_entry_count(&mut self) -> Result<u64> {
+        self.dir.get().get_entry_count()
+    }
+}
+
+pub struct Storage {
+    storage: mem::Shared<fspsrv::Storage>,
+}
+
+impl Storage {
+    pub fn new(storage: mem::Shared<fspsrv::Storage>) -> Self {
+        Self { storage }
+    }
+}
+
+impl fs::StorageHandle for Storage {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        self.storage.get().read(
+            offset,
+            buf.len(),
+            sf::Buffer::from_mut(buf.as_mut_ptr(), buf.len()),
+        )
+    }
+
+    fn write(&mut self, offset: usize, buf: &[u8]) -> Result<()> {
+        self.storage
+            .get()
+            .write(offset, buf.len(), sf::Buffer::from_array(buf))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.storage.get().flush()
+    }
+
+    fn set_size(&mut self, size: usize) -> Result<()> {
+        self.storage.get().set_size(size)
+    }
+
+    fn get_size(&mut self) -> Result<usize> {
+        self.storage.get().get_size()
+    }
+}
+
+pub struct FileSystem {
+    fs: mem::Shared<fspsrv::FileSystem>,
+}
+
+impl FileSystem {
+    pub fn new(fs: mem::Shared<fspsrv::FileSystem>) -> Self {
+        Self { fs }
+    }
+}
+
+impl fs::FileSystem for FileSystem {
+    fn create_file(&mut self, path: &str, attribute: fs::FileAttribute, size: usize) -> Result<()> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs
+            .get()
+            .create_file(attribute, size, sf::Buffer::from_var(&path_buf))
+    }
+
+    fn delete_file(&mut self, path: &str) -> Result<()> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs.get().delete_file(sf::Buffer::from_var(&path_buf))
+    }
+
+    fn create_directory(&mut self, path: &str) -> Result<()> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs
+            .get()
+            .create_directory(sf::Buffer::from_var(&path_buf))
+    }
+
+    fn delete_directory(&mut self, path: &str) -> Result<()> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs
+            .get()
+            .delete_directory(sf::Buffer::from_var(&path_buf))
+    }
+
+    fn delete_directory_recursively(&mut self, path: &str) -> Result<()> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs
+            .get()
+            .delete_directory_recursively(sf::Buffer::from_var(&path_buf))
+    }
+
+    fn get_entry_type(&mut self, path: &str) -> Result<fs::DirectoryEntryType> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs
+            .get()
+            .get_entry_type(sf::Buffer::from_var(&path_buf))
+    }
+
+    fn open_file(
+        &mut self,
+        path: &str,
+        mode: fs::FileOpenMode,
+    ) -> Result<mem::Shared<dyn fs::FileHandle>> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        let file = self
+            .fs
+            .get()
+            .open_file(mode, sf::Buffer::from_var(&path_buf))?
+            .to::<fspsrv::File>();
+        Ok(mem::Shared::new(File::new(file)))
+    }
+
+    fn open_directory(
+        &mut self,
+        path: &str,
+        mode: fs::DirectoryOpenMode,
+    ) -> Result<mem::Shared<dyn fs::DirectoryHandle>> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        let dir = self
+            .fs
+            .get()
+            .open_directory(mode, sf::Buffer::from_var(&path_buf))?
+            .to::<fspsrv::Directory>();
+        Ok(mem::Shared::new(Directory::new(dir)))
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.fs.get().commit()
+    }
+
+    fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<()> {
+        let old_path_buf = fspsrv::Path::from_str(old_path)?;
+        let new_path_buf = fspsrv::Path::from_str(new_path)?;
+        self.fs.get().rename_file(
+            sf::Buffer::from_var(&old_path_buf),
+            sf::Buffer::from_var(&new_path_buf),
+        )
+    }
+
+    fn rename_directory(&mut self, old_path: &str, new_path: &str) -> Result<()> {
+        let old_path_buf = fspsrv::Path::from_str(old_path)?;
+        let new_path_buf = fspsrv::Path::from_str(new_path)?;
+        self.fs.get().rename_directory(
+            sf::Buffer::from_var(&old_path_buf),
+            sf::Buffer::from_var(&new_path_buf),
+        )
+    }
+
+    fn get_free_space_size(&mut self, path: &str) -> Result<usize> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs
+            .get()
+            .get_free_space_size(sf::Buffer::from_var(&path_buf))
+    }
+
+    fn get_total_space_size(&mut self, path: &str) -> Result<usize> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs
+            .get()
+            .get_total_space_size(sf::Buffer::from_var(&path_buf))
+    }
+
+    fn clean_directory_recursively(&mut self, path: &str) -> Result<()> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs
+            .get()
+            .clean_directory_recursively(sf::Buffer::from_var(&path_buf))
+    }
+
+    fn get_file_time_stamp_raw(&mut self, path: &str) -> Result<fs::FileTimeStampRaw> {
+        let path_buf = fspsrv::Path::from_str(path)?;
+        self.fs
+            .get()
+            .get_file_time_stamp_raw(sf::Buffer::from_var(&path_buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ipc,
+        ipc::{cmif, transport},
+        results,
+        service::cmif::IClientObject,
+        svc,
+    };
+    use alloc::vec::Vec;
+    use core::{mem as cmem, ptr};
+
+    // Answers IDirectory requests with hand-written responses, writing the
+    // given entries to the request's receive buffer
+    struct CannedDirectoryTransport {
+        message_buffer: [u8; ipc::MESSAGE_BUFFER_SIZE],
+        entries: Vec<fs::DirectoryEntry>,
+        entry_index: usize,
+        read_result: ResultCode,
+        transport_id: transport::TransportId,
+    }
+
+    impl CannedDirectoryTransport {
+        fn new(entries: Vec<fs::DirectoryEntry>, read_result: ResultCode) -> mem::Shared<Self> {
+            let mut canned = mem::Shared::new(Self {
+                message_buffer: [0; ipc::MESSAGE_BUFFER_SIZE],
+                entries,
+                entry_index: 0,
+                read_result,
+                transport_id: transport::KERNEL_TRANSPORT_ID,
+            });
+
+            let transport_ptr: *mut dyn transport::Transport = canned.get();
+            canned.get().transport_id = transport::register_transport(transport_ptr);
+            canned
+        }
+
+        fn open_directory(&self) -> fs::ReadDir {
+            let session = sf::Session::from(cmif::ObjectInfo::from_transport_handle(
+                1,
+                self.transport_id,
+            ));
+            let dir = mem::Shared::new(fspsrv::Directory::new(session));
+            fs::ReadDir::new(fs::Directory::new(mem::Shared::new(Directory::new(dir))))
+        }
+
+        // Command header, padding up to the aligned data, data header and a
+        // single u64 as raw data
+        fn write_response(&mut self, result: ResultCode, value: u64) {
+            let data_offset = ipc::DATA_PADDING as usize;
+            let data_size = ipc::DATA_PADDING as usize
+                + cmem::size_of::<cmif::DataHeader>()
+                + cmem::size_of::<u64>();
+            let message_buffer = self.message_buffer.as_mut_ptr();
+            unsafe {
+                ptr::write_unaligned(
+                    message_buffer as *mut ipc::CommandHeader,
+                    ipc::CommandHeader::new(0, 0, 0, 0, 0, (data_size / 4) as u32, 0, false),
+                );
+                ptr::write_unaligned(
+                    message_buffer.add(data_offset) as *mut cmif::DataHeader,
+                    cmif::DataHeader::new(cmif::OUT_DATA_HEADER_MAGIC, 0, result.get_value(), 0),
+                );
+                ptr::write_unaligned(
+                    message_buffer.add(data_offset + cmem::size_of::<cmif::DataHeader>())
+                        as *mut u64,
+                    value,
+                );
+            }
+        }
+    }
+
+    impl Drop for CannedDirectoryTransport {
+        fn drop(&mut self) {
+            transport::unregister_transport(self.transport_id);
+        }
+    }
+
+    impl transport::Transport for CannedDirectoryTransport {
+        fn get_message_buffer(&mut self) -> *mut u8 {
+            self.message_buffer.as_mut_ptr()
+        }
+
+        fn send_sync_request(&mut self, _handle: svc::Handle) -> Result<()> {
+            let request = ipc::debug::decode(&self.message_buffer)?;
+            let request_id = match request.data_header {
+                Some(data_header) => data_header.value,
+                None => return Err(results::hipc::ResultUnsupportedOperation::make()),
+            };
+
+            match request_id {
+                // Read
+                0 => {
+                    if self.read_result.is_failure() {
+                        self.write_response(self.read_result, 0);
+                        return Ok(());
+                    }
+
+                    let out_entries = request.receive_buffers[0];
+                    let max_count = out_entries.get_size() / cmem::size_of::<fs::DirectoryEntry>();
+                    let remaining_entries = &self.entries[self.entry_index..];
+                    let read_count = core::cmp::min(max_count, remaining_entries.len());
+                    unsafe {
+                        ptr::copy_nonoverlapping(
+                            remaining_entries.as_ptr(),
+                            out_entries.get_address() as *mut fs::DirectoryEntry,
+                            read_count,
+                        );
+                    }
+                    self.entry_index += read_count;
+                    self.write_response(ResultSuccess::make(), read_count as u64);
+                }
+                // GetEntryCount
+                1 => {
+                    let entry_count = self.entries.len() as u64;
+                    self.write_response(ResultSuccess::make(), entry_count);
+                }
+                _ => return Err(results::hipc::ResultUnsupportedOperation::make()),
+            }
+            Ok(())
+        }
+    }
+
+    fn make_entry(
+        name: &str,
+        entry_type: fs::DirectoryEntryType,
+        file_size: usize,
+    ) -> fs::DirectoryEntry {
+        let mut entry: fs::DirectoryEntry = Default::default();
+        entry.name.set_str(name).unwrap();
+        entry.entry_type = entry_type;
+        entry.file_size = file_size;
+        entry
+    }
+
+    // More entries than a single ReadDir batch
+    fn make_entries() -> Vec<fs::DirectoryEntry> {
+        let mut entries = vec![
+            make_entry("Nintendo", fs::DirectoryEntryType::Directory, 0),
+            make_entry("switch", fs::DirectoryEntryType::Directory, 0),
+            make_entry("hbmenu.nro", fs::DirectoryEntryType::File, 0x1234),
+        ];
+        for i in 0..8 {
+            entries.push(make_entry(
+                &format!("file_{}.bin", i),
+                fs::DirectoryEntryType::File,
+                i * 0x100,
+            ));
+        }
+        entries
+    }
+
+    #[test]
+    fn read_directory_entries() {
+        let entries = make_entries();
+        let canned = CannedDirectoryTransport::new(entries.clone(), ResultSuccess::make());
+        let mut read_dir = canned.get().open_directory();
+
+        let mut read_entries: Vec<fs::DirectoryEntry> = Vec::new();
+        for entry in &mut read_dir {
+            read_entries.push(entry.unwrap());
+        }
+        assert_eq!(read_entries.len(), entries.len());
+        for (read_entry, entry) in read_entries.iter().zip(&entries) {
+            assert_eq!(
+                read_entry.name.get_str().unwrap(),
+                entry.name.get_str().unwrap()
+            );
+            assert_eq!(read_entry.entry_type, entry.entry_type);
+            assert_eq!(read_entry.file_size, entry.file_size);
+        }
+        assert!(read_dir.next().is_none());
+    }
+
+    #[test]
+    fn get_directory_entry_count() {
+        let canned = CannedDirectoryTransport::new(make_entries(), ResultSuccess::make());
+        let session = sf::Session::from(cmif::ObjectInfo::from_transport_handle(
+            1,
+            canned.get().transport_id,
+        ));
+        let mut dir = Directory::new(mem::Shared::new(fspsrv::Directory::new(session)));
+        assert_eq!(
+            fs::DirectoryHandle::get_entry_count(&mut dir).unwrap(),
+            make_entries().len() as u64
+        );
+    }
+
+    #[test]
+    fn read_directory_failure() {
+        let canned = CannedDirectoryTransport::new(
+            make_entries(),
+            results::lib::fs::ResultDeviceRemoved::make(),
+        );
+        let mut read_dir = canned.get().open_directory();
+
+        assert!(results::lib::fs::ResultDeviceRemoved::matches(
+            read_dir.next().unwrap().unwrap_err()
+        ));
+        assert!(read_dir.next().is_none());
+    }
+}
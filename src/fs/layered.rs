@@ -0,0 +1,185 @@
+use crate::{
+    fs,
+    fs::{path, ram},
+    mem,
+    result::*,
+    results,
+};
+use alloc::{string::String, vec::Vec};
+
+// Read-only overlay of a directory (usually on the SD card) on top of another
+// filesystem: files present in the overlay replace the base ones, everything
+// else (including any write operation) goes to the base filesystem
+
+pub struct FileSystem {
+    base_fs: mem::Shared<dyn fs::FileSystem>,
+    overlay_fs: mem::Shared<dyn fs::FileSystem>,
+    overlay_root: String,
+}
+
+const READ_ENTRY_BATCH_COUNT: usize = 8;
+
+fn read_all_entries(dir: mem::Shared<dyn fs::DirectoryHandle>) -> Result<Vec<fs::DirectoryEntry>> {
+    let mut entries: Vec<fs::DirectoryEntry> = Vec::new();
+    let mut batch: Vec<fs::DirectoryEntry> = vec![Default::default(); READ_ENTRY_BATCH_COUNT];
+    loop {
+        let read_count = dir.get().read(&mut batch)?;
+        if read_count == 0 {
+            break;
+        }
+        entries.extend_from_slice(&batch[..read_count]);
+    }
+    Ok(entries)
+}
+
+impl FileSystem {
+    pub fn new(
+        base_fs: mem::Shared<dyn fs::FileSystem>,
+        overlay_fs: mem::Shared<dyn fs::FileSystem>,
+        overlay_root: String,
+    ) -> Self {
+        Self {
+            base_fs,
+            overlay_fs,
+            overlay_root,
+        }
+    }
+
+    // Paths are normalized first, so that nothing above the overlay root (like
+    // "/../..") can be reached through it
+    fn get_overlay_path(&self, path: &str) -> Result<String> {
+        let normalized_path = path::normalize(path)?;
+        let relative_path = normalized_path.trim_start_matches(path::SEPARATOR);
+        if relative_path.is_empty() {
+            Ok(self.overlay_root.clone())
+        } else {
+            Ok(path::join(&self.overlay_root, relative_path))
+        }
+    }
+
+    fn get_overlay_entry_type(&self, path: &str) -> Option<fs::DirectoryEntryType> {
+        self.overlay_fs
+            .get()
+            .get_entry_type(&self.get_overlay_path(path).ok()?)
+            .ok()
+    }
+}
+
+impl fs::FileSystem for FileSystem {
+    fn create_file(&mut self, path: &str, attribute: fs::FileAttribute, size: usize) -> Result<()> {
+        self.base_fs.get().create_file(path, attribute, size)
+    }
+
+    fn delete_file(&mut self, path: &str) -> Result<()> {
+        self.base_fs.get().delete_file(path)
+    }
+
+    fn create_directory(&mut self, path: &str) -> Result<()> {
+        self.base_fs.get().create_directory(path)
+    }
+
+    fn delete_directory(&mut self, path: &str) -> Result<()> {
+        self.base_fs.get().delete_directory(path)
+    }
+
+    fn delete_directory_recursively(&mut self, path: &str) -> Result<()> {
+        self.base_fs.get().delete_directory_recursively(path)
+    }
+
+    fn get_entry_type(&mut self, path: &str) -> Result<fs::DirectoryEntryType> {
+        match self.get_overlay_entry_type(path) {
+            Some(entry_type) => Ok(entry_type),
+            None => self.base_fs.get().get_entry_type(path),
+        }
+    }
+
+    fn open_file(
+        &mut self,
+        path: &str,
+        mode: fs::FileOpenMode,
+    ) -> Result<mem::Shared<dyn fs::FileHandle>> {
+        let is_read_only =
+            !mode.contains(fs::FileOpenMode::Write()) && !mode.contains(fs::FileOpenMode::Append());
+        if is_read_only && (self.get_overlay_entry_type(path) == Some(fs::DirectoryEntryType::File))
+        {
+            return self
+                .overlay_fs
+                .get()
+                .open_file(&self.get_overlay_path(path)?, mode);
+        }
+
+        self.base_fs.get().open_file(path, mode)
+    }
+
+    fn open_directory(
+        &mut self,
+        path: &str,
+        mode: fs::DirectoryOpenMode,
+    ) -> Result<mem::Shared<dyn fs::DirectoryHandle>> {
+        let base_entries = match self.base_fs.get().open_directory(path, mode) {
+            Ok(base_dir) => Some(read_all_entries(base_dir)?),
+            Err(_) => None,
+        };
+        let overlay_entries = match self
+            .overlay_fs
+            .get()
+            .open_directory(&self.get_overlay_path(path)?, mode)
+        {
+            Ok(overlay_dir) => Some(read_all_entries(overlay_dir)?),
+            Err(_) => None,
+        };
+
+        let entries = match (base_entries, overlay_entries) {
+            (Some(mut entries), Some(overlay_entries)) => {
+                // Overlay entries replace the base ones with the same name
+                for overlay_entry in overlay_entries {
+                    match entries
+                        .iter_mut()
+                        .find(|entry| entry.name == overlay_entry.name)
+                    {
+                        Some(entry) => *entry = overlay_entry,
+                        None => entries.push(overlay_entry),
+                    };
+                }
+                entries
+            }
+            (Some(entries), None) | (None, Some(entries)) => entries,
+            (None, None) => return Err(results::fs::ResultPathNotFound::make()),
+        };
+        Ok(mem::Shared::new(ram::Directory::new(entries)))
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.base_fs.get().commit()
+    }
+
+    fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<()> {
+        self.base_fs.get().rename_file(old_path, new_path)
+    }
+
+    fn rename_directory(&mut self, old_path: &str, new_path: &str) -> Result<()> {
+        self.base_fs.get().rename_directory(old_path, new_path)
+    }
+
+    fn get_free_space_size(&mut self, path: &str) -> Result<usize> {
+        self.base_fs.get().get_free_space_size(path)
+    }
+
+    fn get_total_space_size(&mut self, path: &str) -> Result<usize> {
+        self.base_fs.get().get_total_space_size(path)
+    }
+
+    fn clean_directory_recursively(&mut self, path: &str) -> Result<()> {
+        self.base_fs.get().clean_directory_recursively(path)
+    }
+
+    fn get_file_time_stamp_raw(&mut self, path: &str) -> Result<fs::FileTimeStampRaw> {
+        match self.get_overlay_entry_type(path) {
+            Some(_) => self
+                .overlay_fs
+                .get()
+                .get_file_time_stamp_raw(&self.get_overlay_path(path)?),
+            None => self.base_fs.get().get_file_time_stamp_raw(path),
+        }
+    }
+}
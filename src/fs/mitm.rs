@@ -0,0 +1,207 @@
+use crate::{
+    fs,
+    ipc::cmif::{server, sf, ObjectInfo},
+    mem,
+    result::*,
+    results,
+    service::{
+        cmif::{fspsrv, fspsrv::IFileSystemProxy, IClientObject},
+        tipc::sm,
+    },
+    svc,
+};
+
+// fsp-srv MITM implementing layered filesystems: content filesystems opened via
+// OpenFileSystemWithId get the contents of
+// "sdmc:/atmosphere/contents/<program-id>/<type>" overlaid on top of them, any
+// other command (or programs without such directory) is forwarded untouched
+
+pub const OVERLAY_BASE_PATH: &str = "/atmosphere/contents";
+
+const APPLICATION_PROGRAM_ID_MIN: u64 = 0x0100000000010000;
+const APPLICATION_PROGRAM_ID_MAX: u64 = 0x01FFFFFFFFFFFFFF;
+
+pub fn get_overlay_type_name(fs_type: fspsrv::FileSystemProxyType) -> &'static str {
+    match fs_type {
+        fspsrv::FileSystemProxyType::Code => "exefs",
+        fspsrv::FileSystemProxyType::Rom | fspsrv::FileSystemProxyType::Data => "romfs",
+        fspsrv::FileSystemProxyType::Logo => "logo",
+        fspsrv::FileSystemProxyType::Control => "control",
+        fspsrv::FileSystemProxyType::Manual => "manual",
+        fspsrv::FileSystemProxyType::Meta => "meta",
+        fspsrv::FileSystemProxyType::Package => "package",
+        fspsrv::FileSystemProxyType::RegisteredUpdate => "update",
+    }
+}
+
+fn should_forward<T>() -> Result<T> {
+    Err(results::sm::mitm::ResultShouldForwardToSession::make())
+}
+
+pub struct FileSystemProxyMitm {
+    session: sf::Session,
+    forward_handle: svc::Handle,
+}
+
+impl FileSystemProxyMitm {
+    // Only the overlay is opened through our own fsp-srv session (fs must be
+    // initialized by the MITM process itself)
+    fn open_sd_card_filesystem_impl(&self) -> Result<mem::Shared<dyn fs::FileSystem>> {
+        result_return_unless!(fs::is_initialized(), results::lib::fs::ResultNotInitialized);
+        let sd_fs = unsafe {
+            fs::G_FSPSRV_SESSION
+                .get()
+                .get()
+                .open_sd_card_filesystem()?
+                .to::<fspsrv::FileSystem>()
+        };
+        Ok(mem::Shared::new(fs::fsp::FileSystem::new(sd_fs)))
+    }
+
+    // Anything opened on behalf of the client must go through its own forward
+    // session, so that fs applies the client's permissions. A clone of it keeps
+    // the client's identity, and isn't affected by the original being a domain
+    fn open_forward_filesystem_proxy(&self) -> Result<fspsrv::FileSystemProxy> {
+        let cloned_handle = ObjectInfo::from_handle(self.forward_handle).clone_current_object()?;
+        Ok(fspsrv::FileSystemProxy::new(sf::Session::from_handle(
+            cloned_handle.handle,
+        )))
+    }
+}
+
+impl IFileSystemProxy for FileSystemProxyMitm {
+    fn set_current_process(&mut self, _process_id: sf::ProcessId) -> Result<()> {
+        should_forward()
+    }
+
+    fn open_filesystem_with_patch(
+        &mut self,
+        _fs_type: fspsrv::FileSystemProxyType,
+        _program_id: u64,
+    ) -> Result<mem::Shared<dyn sf::IObject>> {
+        should_forward()
+    }
+
+    fn open_filesystem_with_id(
+        &mut self,
+        fs_type: fspsrv::FileSystemProxyType,
+        program_id: u64,
+        path_buf: sf::InPointerBuffer,
+    ) -> Result<mem::Shared<dyn sf::IObject>> {
+        let sd_fs = self.open_sd_card_filesystem_impl()?;
+        let overlay_root = format!(
+            "{}/{:016X}/{}",
+            OVERLAY_BASE_PATH,
+            program_id,
+            get_overlay_type_name(fs_type)
+        );
+        match sd_fs.get().get_entry_type(&overlay_root) {
+            Ok(fs::DirectoryEntryType::Directory) => {}
+            _ => return should_forward(),
+        };
+
+        let base_fs = self
+            .open_forward_filesystem_proxy()?
+            .open_filesystem_with_id(fs_type, program_id, path_buf)?
+            .to::<fspsrv::FileSystem>();
+        let layered_fs = fs::layered::FileSystem::new(
+            mem::Shared::new(fs::fsp::FileSystem::new(base_fs)),
+            sd_fs,
+            overlay_root,
+        );
+        Ok(mem::Shared::new(fs::server::FileSystem::new(
+            mem::Shared::new(layered_fs),
+        )))
+    }
+
+    fn open_bis_storage(
+        &mut self,
+        _partition_id: fspsrv::BisPartitionId,
+    ) -> Result<mem::Shared<dyn sf::IObject>> {
+        should_forward()
+    }
+
+    fn open_sd_card_filesystem(&mut self) -> Result<mem::Shared<dyn sf::IObject>> {
+        should_forward()
+    }
+
+    fn open_game_card_storage(
+        &mut self,
+        _handle: fspsrv::GameCardHandle,
+        _partition: fspsrv::GameCardPartitionRaw,
+    ) -> Result<mem::Shared<dyn sf::IObject>> {
+        should_forward()
+    }
+
+    fn open_save_data_filesystem(
+        &mut self,
+        _save_data_space_id: fspsrv::SaveDataSpaceId,
+        _attribute: fspsrv::SaveDataAttribute,
+    ) -> Result<mem::Shared<dyn sf::IObject>> {
+        should_forward()
+    }
+
+    fn open_save_data_filesystem_by_system_save_data_id(
+        &mut self,
+        _save_data_space_id: fspsrv::SaveDataSpaceId,
+        _attribute: fspsrv::SaveDataAttribute,
+    ) -> Result<mem::Shared<dyn sf::IObject>> {
+        should_forward()
+    }
+
+    fn open_data_storage_by_data_id(
+        &mut self,
+        _storage_id: fspsrv::StorageId,
+        _data_id: u64,
+    ) -> Result<mem::Shared<dyn sf::IObject>> {
+        should_forward()
+    }
+
+    fn open_device_operator(&mut self) -> Result<mem::Shared<dyn sf::IObject>> {
+        should_forward()
+    }
+
+    fn open_sd_card_detection_event_notifier(&mut self) -> Result<mem::Shared<dyn sf::IObject>> {
+        should_forward()
+    }
+
+    fn output_access_log_to_sd_card(&mut self, _access_log: sf::InMapAliasBuffer) -> Result<()> {
+        should_forward()
+    }
+}
+
+impl sf::IObject for FileSystemProxyMitm {
+    fn get_session(&mut self) -> &mut sf::Session {
+        &mut self.session
+    }
+
+    // Only intercepted commands are listed, the rest are forwarded automatically
+    fn get_command_table(&self) -> sf::CommandMetadataTable {
+        vec![ipc_cmif_interface_make_command_meta!(open_filesystem_with_id: 8, [(2, 0, 0) =>])]
+    }
+}
+
+impl server::IMitmServerObject for FileSystemProxyMitm {
+    fn new(_info: sm::MitmProcessInfo) -> Self {
+        Self {
+            session: sf::Session::new(),
+            forward_handle: 0,
+        }
+    }
+
+    fn set_forward_handle(&mut self, forward_handle: svc::Handle) {
+        self.forward_handle = forward_handle;
+    }
+}
+
+impl server::IMitmService for FileSystemProxyMitm {
+    fn get_name() -> &'static str {
+        nul!("fsp-srv")
+    }
+
+    fn should_mitm(info: sm::MitmProcessInfo) -> bool {
+        // Only applications get their content layered
+        (info.program_id >= APPLICATION_PROGRAM_ID_MIN)
+            && (info.program_id <= APPLICATION_PROGRAM_ID_MAX)
+    }
+}
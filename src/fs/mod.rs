@@ -0,0 +1,1300 @@
+use crate::{
+    io,
+    io::{Read, Write},
+    ipc::cmif::sf,
+    mem,
+    result::*,
+    results, service,
+    service::cmif::{
+        fspsrv,
+        fspsrv::{IDeviceOperator, IEventNotifier, IFileSystemProxy},
+        lr,
+        lr::{ILocationResolver, ILocationResolverManager},
+    },
+    sync, wait,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{mem as cmem, slice};
+
+pub use fspsrv::{
+    BisPartitionId, DirectoryEntry, DirectoryEntryType, DirectoryOpenMode, FileAttribute,
+    FileOpenMode, FileSystemProxyType, FileTimeStampRaw, FileWriteOption, GameCardHandle,
+    GameCardPartitionRaw, SaveDataAttribute, SaveDataRank, SaveDataSpaceId, SaveDataType,
+    StorageId, UserId,
+};
+
+// Backend traits any kind of filesystem (IPC or not) must implement in order to
+// be mounted as a device. Paths received by backends are always relative to the
+// device root (for instance, "sdmc:/dir/file" is received as "/dir/file")
+
+pub trait FileHandle {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<usize>;
+    fn write(&mut self, offset: usize, buf: &[u8], option: FileWriteOption) -> Result<()>;
+    fn flush(&mut self) -> Result<()>;
+    fn set_size(&mut self, size: usize) -> Result<()>;
+    fn get_size(&mut self) -> Result<usize>;
+}
+
+pub trait DirectoryHandle {
+    fn read(&mut self, entries: &mut [DirectoryEntry]) -> Result<usize>;
+    fn get_entry_count(&mut self) -> Result<u64>;
+}
+
+// Raw storages (partitions, gamecards...) are exposed separately, so that any
+// filesystem parser can be layered on top of them and then mounted as usual
+
+pub trait StorageHandle {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<()>;
+    fn write(&mut self, offset: usize, buf: &[u8]) -> Result<()>;
+    fn flush(&mut self) -> Result<()>;
+    fn set_size(&mut self, size: usize) -> Result<()>;
+    fn get_size(&mut self) -> Result<usize>;
+}
+
+pub trait FileSystem {
+    fn create_file(&mut self, path: &str, attribute: FileAttribute, size: usize) -> Result<()>;
+    fn delete_file(&mut self, path: &str) -> Result<()>;
+    fn create_directory(&mut self, path: &str) -> Result<()>;
+    fn delete_directory(&mut self, path: &str) -> Result<()>;
+    fn delete_directory_recursively(&mut self, path: &str) -> Result<()>;
+    fn get_entry_type(&mut self, path: &str) -> Result<DirectoryEntryType>;
+    fn open_file(&mut self, path: &str, mode: FileOpenMode) -> Result<mem::Shared<dyn FileHandle>>;
+    fn open_directory(
+        &mut self,
+        path: &str,
+        mode: DirectoryOpenMode,
+    ) -> Result<mem::Shared<dyn DirectoryHandle>>;
+
+    // Optional operations, not every backend is able to support them
+
+    fn commit(&mut self) -> Result<()> {
+        // Backends without any kind of journaling have nothing to commit
+        Ok(())
+    }
+
+    fn rename_file(&mut self, _old_path: &str, _new_path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn rename_directory(&mut self, _old_path: &str, _new_path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn get_free_space_size(&mut self, _path: &str) -> Result<usize> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn get_total_space_size(&mut self, _path: &str) -> Result<usize> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn clean_directory_recursively(&mut self, _path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn get_file_time_stamp_raw(&mut self, _path: &str) -> Result<FileTimeStampRaw> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+}
+
+struct Device {
+    name: String,
+    fs: mem::Shared<dyn FileSystem>,
+    invalidate_on_sd_card_removal: bool,
+    invalidated: bool,
+}
+
+impl Device {
+    pub fn from(name: String, fs: mem::Shared<dyn FileSystem>) -> Self {
+        Self {
+            name,
+            fs,
+            invalidate_on_sd_card_removal: false,
+            invalidated: false,
+        }
+    }
+}
+
+pub struct File {
+    file: mem::Shared<dyn FileHandle>,
+    offset: usize,
+    needs_flush: bool,
+}
+
+impl File {
+    pub fn new(file: mem::Shared<dyn FileHandle>) -> Self {
+        Self {
+            file,
+            offset: 0,
+            needs_flush: false,
+        }
+    }
+
+    pub fn get_handle(&self) -> mem::Shared<dyn FileHandle> {
+        self.file.clone()
+    }
+
+    pub fn get_size(&mut self) -> Result<usize> {
+        self.file.get().get_size()
+    }
+
+    pub fn set_len(&mut self, size: usize) -> Result<()> {
+        self.file.get().set_size(size)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.get().flush()?;
+        self.needs_flush = false;
+        Ok(())
+    }
+
+    pub fn read_val<T: Copy + Default>(&mut self) -> Result<T> {
+        let mut t: T = Default::default();
+        let t_buf =
+            unsafe { slice::from_raw_parts_mut(&mut t as *mut T as *mut u8, cmem::size_of::<T>()) };
+        self.read_exact(t_buf)?;
+        Ok(t)
+    }
+
+    pub fn write_val<T: Copy>(&mut self, t: T) -> Result<()> {
+        let t_buf =
+            unsafe { slice::from_raw_parts(&t as *const T as *const u8, cmem::size_of::<T>()) };
+        self.write_all(t_buf)
+    }
+}
+
+impl io::Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read_size = self.file.get().read(self.offset, buf)?;
+        self.offset += read_size;
+        Ok(read_size)
+    }
+}
+
+impl io::Write for File {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.file
+            .get()
+            .write(self.offset, buf, FileWriteOption::None())?;
+        self.offset += buf.len();
+        self.needs_flush = true;
+        // Write command does not return the written size
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        File::flush(self)
+    }
+}
+
+impl io::Seek for File {
+    fn seek(&mut self, pos: io::SeekFrom) -> Result<u64> {
+        let end_offset = match pos {
+            io::SeekFrom::End(_) => self.get_size()? as u64,
+            _ => 0,
+        };
+        self.offset = io::compute_seek_offset(self.offset as u64, end_offset, pos)? as usize;
+        Ok(self.offset as u64)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        // Writes are no longer flushed one by one, so make sure nothing is left
+        // pending when the file is closed
+        if self.needs_flush {
+            let _ = self.flush();
+        }
+    }
+}
+
+pub struct Directory {
+    dir: mem::Shared<dyn DirectoryHandle>,
+}
+
+impl Directory {
+    pub fn new(dir: mem::Shared<dyn DirectoryHandle>) -> Self {
+        Self { dir }
+    }
+
+    pub fn get_entry_count(&mut self) -> Result<u64> {
+        self.dir.get().get_entry_count()
+    }
+
+    pub fn read(&mut self, entries: &mut [DirectoryEntry]) -> Result<usize> {
+        self.dir.get().read(entries)
+    }
+}
+
+// Amount of entries requested to fs on every read, to avoid one IPC request per
+// entry while iterating
+const READ_DIR_ENTRY_BATCH_COUNT: usize = 8;
+
+pub struct ReadDir {
+    dir: Directory,
+    entries: Vec<DirectoryEntry>,
+    entry_count: usize,
+    entry_index: usize,
+    finished: bool,
+}
+
+impl ReadDir {
+    pub fn new(dir: Directory) -> Self {
+        Self {
+            dir,
+            entries: vec![Default::default(); READ_DIR_ENTRY_BATCH_COUNT],
+            entry_count: 0,
+            entry_index: 0,
+            finished: false,
+        }
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = Result<DirectoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.entry_index >= self.entry_count {
+            if self.finished {
+                return None;
+            }
+
+            match self.dir.read(&mut self.entries) {
+                Ok(read_count) => {
+                    self.entry_count = read_count;
+                    self.entry_index = 0;
+                    if read_count < self.entries.len() {
+                        self.finished = true;
+                    }
+                    if read_count == 0 {
+                        return None;
+                    }
+                }
+                Err(rc) => {
+                    self.finished = true;
+                    self.entry_count = 0;
+                    return Some(Err(rc));
+                }
+            };
+        }
+
+        let entry = self.entries[self.entry_index];
+        self.entry_index += 1;
+        Some(Ok(entry))
+    }
+}
+
+static mut G_FSPSRV_SESSION: sync::Locked<mem::Shared<fspsrv::FileSystemProxy>> =
+    sync::Locked::new(false, mem::Shared::empty());
+static mut G_DEVICES: sync::Locked<Vec<Device>> = sync::Locked::new(false, Vec::new());
+
+fn find_device_by_name(name: &str) -> Result<mem::Shared<dyn FileSystem>> {
+    unsafe {
+        for device in G_DEVICES.get() {
+            if device.name == name {
+                result_return_if!(device.invalidated, results::lib::fs::ResultDeviceRemoved);
+                return Ok(device.fs.clone());
+            }
+        }
+        Err(results::lib::fs::ResultDeviceNotFound::make())
+    }
+}
+
+fn resolve_path(path: String) -> Result<(mem::Shared<dyn FileSystem>, String)> {
+    let (device_name, device_path) = path::resolve(&path)?;
+    let fs = find_device_by_name(&device_name)?;
+    Ok((fs, device_path))
+}
+
+pub fn initialize() -> Result<()> {
+    unsafe {
+        G_FSPSRV_SESSION.set(service::cmif::new_service_object()?);
+    }
+    Ok(())
+}
+
+pub fn is_initialized() -> bool {
+    unsafe { !G_FSPSRV_SESSION.get().is_null() }
+}
+
+pub fn finalize() {
+    unsafe {
+        G_DEVICES.get().clear();
+        G_FSPSRV_SESSION.get().reset();
+    }
+}
+
+pub fn mount(name: &str, fs: mem::Shared<dyn FileSystem>) -> Result<()> {
+    result_return_if!(
+        name.is_empty()
+            || name.contains(|c| (c == path::DEVICE_SEPARATOR) || (c == path::SEPARATOR)),
+        results::lib::fs::ResultInvalidPath
+    );
+    unsafe {
+        // Devices invalidated by a removal can be mounted again
+        G_DEVICES
+            .get()
+            .retain(|dev| !(dev.invalidated && (dev.name == name)));
+    }
+    result_return_if!(
+        find_device_by_name(name).is_ok(),
+        results::lib::fs::ResultDeviceAlreadyMounted
+    );
+
+    unsafe {
+        G_DEVICES.get().push(Device::from(String::from(name), fs));
+    }
+
+    Ok(())
+}
+
+pub fn mount_fsp_filesystem(name: &str, fs: mem::Shared<fspsrv::FileSystem>) -> Result<()> {
+    mount(name, mem::Shared::new(fsp::FileSystem::new(fs)))
+}
+
+pub fn open_sd_card_filesystem() -> Result<mem::Shared<dyn FileSystem>> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let sd_fs = unsafe {
+        G_FSPSRV_SESSION
+            .get()
+            .get()
+            .open_sd_card_filesystem()?
+            .to::<fspsrv::FileSystem>()
+    };
+    Ok(mem::Shared::new(fsp::FileSystem::new(sd_fs)))
+}
+
+pub fn mount_sd_card(name: &str) -> Result<()> {
+    mount(name, open_sd_card_filesystem()?)?;
+    subscribe_sd_card_removal(name)
+}
+
+pub struct SdCardDetectionEvent {
+    // The event is only signaled while its notifier is alive
+    _event_notifier: mem::Shared<fspsrv::EventNotifier>,
+    event: wait::RemoteEvent,
+}
+
+impl SdCardDetectionEvent {
+    pub fn get_event(&self) -> &wait::RemoteEvent {
+        &self.event
+    }
+
+    pub fn wait(&self, timeout: i64) -> Result<()> {
+        self.event.wait(timeout)
+    }
+}
+
+pub fn open_sd_card_detection_event() -> Result<SdCardDetectionEvent> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let event_notifier = unsafe {
+        G_FSPSRV_SESSION
+            .get()
+            .get()
+            .open_sd_card_detection_event_notifier()?
+            .to::<fspsrv::EventNotifier>()
+    };
+    let event_handle = event_notifier.get().get_event_handle()?;
+    Ok(SdCardDetectionEvent {
+        _event_notifier: event_notifier,
+        event: wait::RemoteEvent::new(event_handle.handle),
+    })
+}
+
+pub fn is_sd_card_inserted() -> Result<bool> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let device_operator = unsafe {
+        G_FSPSRV_SESSION
+            .get()
+            .get()
+            .open_device_operator()?
+            .to::<fspsrv::DeviceOperator>()
+    };
+    device_operator.get().is_sd_card_inserted()
+}
+
+pub fn subscribe_sd_card_removal(name: &str) -> Result<()> {
+    unsafe {
+        for device in G_DEVICES.get() {
+            if device.name == name {
+                device.invalidate_on_sd_card_removal = true;
+                return Ok(());
+            }
+        }
+        Err(results::lib::fs::ResultDeviceNotFound::make())
+    }
+}
+
+pub fn invalidate_sd_card_devices() {
+    unsafe {
+        for device in G_DEVICES.get() {
+            if device.invalidate_on_sd_card_removal {
+                device.invalidated = true;
+            }
+        }
+    }
+}
+
+// Meant to be called whenever the detection event gets signaled: if the card
+// was removed, every device subscribed to its removal fails with
+// ResultDeviceRemoved from then on (instead of using stale fsp-srv objects)
+// until it gets mounted again
+pub fn handle_sd_card_detection() -> Result<bool> {
+    let is_inserted = is_sd_card_inserted()?;
+    if !is_inserted {
+        invalidate_sd_card_devices();
+    }
+    Ok(is_inserted)
+}
+
+fn mount_save_data_impl(
+    name: &str,
+    save_data_space_id: SaveDataSpaceId,
+    attribute: SaveDataAttribute,
+) -> Result<()> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let save_data_fs = unsafe {
+        G_FSPSRV_SESSION
+            .get()
+            .get()
+            .open_save_data_filesystem(save_data_space_id, attribute)?
+            .to::<fspsrv::FileSystem>()
+    };
+    mount_fsp_filesystem(name, save_data_fs)
+}
+
+pub fn mount_save_data(name: &str, program_id: u64, user_id: UserId) -> Result<()> {
+    let attribute = SaveDataAttribute {
+        program_id,
+        user_id,
+        save_data_type: SaveDataType::Account,
+        ..Default::default()
+    };
+    mount_save_data_impl(name, SaveDataSpaceId::User, attribute)
+}
+
+pub fn mount_device_save_data(name: &str, program_id: u64) -> Result<()> {
+    let attribute = SaveDataAttribute {
+        program_id,
+        save_data_type: SaveDataType::Device,
+        ..Default::default()
+    };
+    mount_save_data_impl(name, SaveDataSpaceId::User, attribute)
+}
+
+pub fn mount_bcat_save_data(name: &str, program_id: u64) -> Result<()> {
+    // BCAT delivery storage is just a special kind of save data
+    let attribute = SaveDataAttribute {
+        program_id,
+        save_data_type: SaveDataType::Bcat,
+        ..Default::default()
+    };
+    mount_save_data_impl(name, SaveDataSpaceId::User, attribute)
+}
+
+pub fn mount_system_save_data(
+    name: &str,
+    save_data_space_id: SaveDataSpaceId,
+    system_save_data_id: u64,
+    user_id: UserId,
+) -> Result<()> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let attribute = SaveDataAttribute {
+        user_id,
+        system_save_data_id,
+        save_data_type: SaveDataType::System,
+        ..Default::default()
+    };
+    let save_data_fs = unsafe {
+        G_FSPSRV_SESSION
+            .get()
+            .get()
+            .open_save_data_filesystem_by_system_save_data_id(save_data_space_id, attribute)?
+            .to::<fspsrv::FileSystem>()
+    };
+    mount_fsp_filesystem(name, save_data_fs)
+}
+
+pub fn open_bis_storage(partition_id: BisPartitionId) -> Result<mem::Shared<dyn StorageHandle>> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let bis_storage = unsafe {
+        G_FSPSRV_SESSION
+            .get()
+            .get()
+            .open_bis_storage(partition_id)?
+            .to::<fspsrv::Storage>()
+    };
+    Ok(mem::Shared::new(fsp::Storage::new(bis_storage)))
+}
+
+pub fn open_game_card_storage(
+    handle: GameCardHandle,
+    partition: GameCardPartitionRaw,
+) -> Result<mem::Shared<dyn StorageHandle>> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let game_card_storage = unsafe {
+        G_FSPSRV_SESSION
+            .get()
+            .get()
+            .open_game_card_storage(handle, partition)?
+            .to::<fspsrv::Storage>()
+    };
+    Ok(mem::Shared::new(fsp::Storage::new(game_card_storage)))
+}
+
+pub fn open_data_storage(
+    storage_id: StorageId,
+    data_id: u64,
+) -> Result<mem::Shared<dyn StorageHandle>> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let data_storage = unsafe {
+        G_FSPSRV_SESSION
+            .get()
+            .get()
+            .open_data_storage_by_data_id(storage_id, data_id)?
+            .to::<fspsrv::Storage>()
+    };
+    Ok(mem::Shared::new(fsp::Storage::new(data_storage)))
+}
+
+// Storages installed content is looked up on, in order
+const CONTENT_STORAGE_IDS: [StorageId; 4] = [
+    StorageId::GameCard,
+    StorageId::SdCard,
+    StorageId::BuiltInUser,
+    StorageId::BuiltInSystem,
+];
+
+fn resolve_content_path_on(
+    lr_manager: &mem::Shared<lr::LocationResolverManager>,
+    storage_id: StorageId,
+    program_id: u64,
+    fs_type: FileSystemProxyType,
+) -> Result<lr::Path> {
+    let location_resolver = lr_manager
+        .get()
+        .open_location_resolver(storage_id)?
+        .to::<lr::LocationResolver>();
+    let content_path = lr::Path::new();
+    let content_path_buf = sf::Buffer::from_var(&content_path);
+    match fs_type {
+        FileSystemProxyType::Code | FileSystemProxyType::Rom | FileSystemProxyType::Logo => {
+            location_resolver
+                .get()
+                .resolve_program_path(program_id, content_path_buf)?
+        }
+        FileSystemProxyType::Control => location_resolver
+            .get()
+            .resolve_application_control_path(program_id, content_path_buf)?,
+        FileSystemProxyType::Manual => location_resolver
+            .get()
+            .resolve_application_html_document_path(program_id, content_path_buf)?,
+        FileSystemProxyType::Data => location_resolver
+            .get()
+            .resolve_data_path(program_id, content_path_buf)?,
+        _ => return Err(results::lib::fs::ResultContentPathNotResolvable::make()),
+    };
+    Ok(content_path)
+}
+
+// Only the base content is mounted (updates aren't applied), located through
+// lr. Meta, package and update filesystems can't be located this way, so
+// mount_content_path must be used for them
+pub fn mount_content(name: &str, program_id: u64, fs_type: FileSystemProxyType) -> Result<()> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let lr_manager = service::cmif::new_service_object::<lr::LocationResolverManager>()?;
+    let mut rc = results::lib::fs::ResultContentPathNotResolvable::make();
+    for storage_id in CONTENT_STORAGE_IDS.iter() {
+        match resolve_content_path_on(&lr_manager, *storage_id, program_id, fs_type) {
+            Ok(content_path) => {
+                let content_fs = unsafe {
+                    G_FSPSRV_SESSION
+                        .get()
+                        .get()
+                        .open_filesystem_with_id(
+                            fs_type,
+                            program_id,
+                            sf::Buffer::from_var(&content_path),
+                        )?
+                        .to::<fspsrv::FileSystem>()
+                };
+                return mount_fsp_filesystem(name, content_fs);
+            }
+            Err(resolve_rc) => rc = resolve_rc,
+        };
+    }
+    Err(rc)
+}
+
+pub fn mount_content_path(
+    name: &str,
+    program_id: u64,
+    fs_type: FileSystemProxyType,
+    content_path: &str,
+) -> Result<()> {
+    result_return_unless!(is_initialized(), results::lib::fs::ResultNotInitialized);
+
+    let content_path_buf = fspsrv::Path::from_str(content_path)?;
+    let content_fs = unsafe {
+        G_FSPSRV_SESSION
+            .get()
+            .get()
+            .open_filesystem_with_id(
+                fs_type,
+                program_id,
+                sf::Buffer::from_var(&content_path_buf),
+            )?
+            .to::<fspsrv::FileSystem>()
+    };
+    mount_fsp_filesystem(name, content_fs)
+}
+
+pub fn mount_romfs(name: &str) -> Result<()> {
+    mount(name, mem::Shared::new(romfs::open_self()?))
+}
+
+pub fn mount_ram(name: &str) -> Result<()> {
+    mount(name, mem::Shared::new(ram::FileSystem::new()))
+}
+
+pub fn unmount(name: &str) {
+    unsafe {
+        G_DEVICES.get().retain(|dev| dev.name != name);
+    }
+}
+
+pub fn commit(name: &str) -> Result<()> {
+    let fs = find_device_by_name(name)?;
+    fs.get().commit()
+}
+
+pub fn set_current_dir(path: String) -> Result<()> {
+    let (device_name, device_path) = path::resolve(&path)?;
+    let fs = find_device_by_name(&device_name)?;
+    result_return_unless!(
+        fs.get().get_entry_type(&device_path)? == DirectoryEntryType::Directory,
+        results::fs::ResultPathNotFound
+    );
+
+    path::set_current_dir(format!(
+        "{}{}{}",
+        device_name,
+        path::DEVICE_SEPARATOR,
+        device_path
+    ));
+    Ok(())
+}
+
+pub fn get_current_dir() -> Option<String> {
+    path::get_current_dir()
+}
+
+pub fn create_file(path: String, size: usize, attribute: FileAttribute) -> Result<()> {
+    let (fs, processed_path) = resolve_path(path)?;
+    fs.get().create_file(&processed_path, attribute, size)
+}
+
+pub fn delete_file(path: String) -> Result<()> {
+    let (fs, processed_path) = resolve_path(path)?;
+    fs.get().delete_file(&processed_path)
+}
+
+pub fn create_directory(path: String) -> Result<()> {
+    let (fs, processed_path) = resolve_path(path)?;
+    fs.get().create_directory(&processed_path)
+}
+
+pub fn delete_directory(path: String) -> Result<()> {
+    let (fs, processed_path) = resolve_path(path)?;
+    fs.get().delete_directory_recursively(&processed_path)
+}
+
+pub fn get_entry_type(path: String) -> Result<DirectoryEntryType> {
+    let (fs, processed_path) = resolve_path(path)?;
+    fs.get().get_entry_type(&processed_path)
+}
+
+pub fn rename(old_path: String, new_path: String) -> Result<()> {
+    let (fs, processed_old_path) = resolve_path(old_path)?;
+    let (new_fs, processed_new_path) = resolve_path(new_path)?;
+    // Renaming is done by the backend, thus both paths must be in the same device
+    result_return_unless!(
+        fs.is_same(&new_fs),
+        results::lib::fs::ResultDifferentDevices
+    );
+
+    match fs.get().get_entry_type(&processed_old_path)? {
+        DirectoryEntryType::Directory => fs
+            .get()
+            .rename_directory(&processed_old_path, &processed_new_path),
+        DirectoryEntryType::File => fs
+            .get()
+            .rename_file(&processed_old_path, &processed_new_path),
+    }
+}
+
+pub fn get_free_space(path: String) -> Result<usize> {
+    let (fs, processed_path) = resolve_path(path)?;
+    fs.get().get_free_space_size(&processed_path)
+}
+
+pub fn get_total_space(path: String) -> Result<usize> {
+    let (fs, processed_path) = resolve_path(path)?;
+    fs.get().get_total_space_size(&processed_path)
+}
+
+pub fn get_timestamp(path: String) -> Result<FileTimeStampRaw> {
+    let (fs, processed_path) = resolve_path(path)?;
+    fs.get().get_file_time_stamp_raw(&processed_path)
+}
+
+pub fn clean_directory(path: String) -> Result<()> {
+    let (fs, processed_path) = resolve_path(path)?;
+    fs.get().clean_directory_recursively(&processed_path)
+}
+
+bit_enum! {
+    FileOpenOption (u32) {
+        Create = bit!(0),
+        Read = bit!(1),
+        Write = bit!(2),
+        Append = bit!(3),
+        Truncate = bit!(4)
+    }
+}
+
+fn convert_file_open_option(option: FileOpenOption) -> FileOpenMode {
+    let mut mode = FileOpenMode::None();
+    if option.contains(FileOpenOption::Read()) {
+        mode |= FileOpenMode::Read();
+    }
+    if option.contains(FileOpenOption::Write()) {
+        mode |= FileOpenMode::Write();
+    }
+    if option.contains(FileOpenOption::Append()) {
+        mode |= FileOpenMode::Append();
+    }
+    mode
+}
+
+pub fn open_file(path: String, option: FileOpenOption) -> Result<File> {
+    let (fs, processed_path) = resolve_path(path)?;
+
+    let mode = convert_file_open_option(option);
+    let file = match fs.get().open_file(&processed_path, mode) {
+        Ok(file) => file,
+        Err(rc) => {
+            if results::fs::ResultPathNotFound::matches(rc)
+                && option.contains(FileOpenOption::Create())
+            {
+                // Create the file if it doesn't exist and we were told to do so
+                fs.get()
+                    .create_file(&processed_path, FileAttribute::None(), 0)?;
+                fs.get().open_file(&processed_path, mode)?
+            } else {
+                return Err(rc);
+            }
+        }
+    };
+    if option.contains(FileOpenOption::Truncate()) {
+        file.get().set_size(0)?;
+    }
+    let offset: usize = match option.contains(FileOpenOption::Append()) {
+        true => file.get().get_size().unwrap_or(0),
+        false => 0,
+    };
+
+    Ok(File {
+        file,
+        offset,
+        needs_flush: false,
+    })
+}
+
+bit_enum! {
+    DirectoryOpenOption (u32) {
+        Directories = bit!(0),
+        Files = bit!(1),
+        NoFileSize = bit!(2)
+    }
+}
+
+fn convert_directory_open_option(option: DirectoryOpenOption) -> DirectoryOpenMode {
+    let mut mode = DirectoryOpenMode::from(0);
+    if option.contains(DirectoryOpenOption::Directories()) {
+        mode |= DirectoryOpenMode::ReadDirectories();
+    }
+    if option.contains(DirectoryOpenOption::Files()) {
+        mode |= DirectoryOpenMode::ReadFiles();
+    }
+    if option.contains(DirectoryOpenOption::NoFileSize()) {
+        mode |= DirectoryOpenMode::NoFileSize();
+    }
+    mode
+}
+
+pub fn open_directory(path: String, option: DirectoryOpenOption) -> Result<Directory> {
+    let (fs, processed_path) = resolve_path(path)?;
+
+    let mode = convert_directory_open_option(option);
+    let dir = fs.get().open_directory(&processed_path, mode)?;
+    Ok(Directory::new(dir))
+}
+
+pub fn read_dir(path: String) -> Result<ReadDir> {
+    let dir = open_directory(
+        path,
+        DirectoryOpenOption::Directories() | DirectoryOpenOption::Files(),
+    )?;
+    Ok(ReadDir::new(dir))
+}
+
+pub struct WalkEntry {
+    pub path: String,
+    pub entry: DirectoryEntry,
+    pub depth: usize,
+}
+
+impl WalkEntry {
+    pub fn is_directory(&self) -> bool {
+        self.entry.entry_type == DirectoryEntryType::Directory
+    }
+}
+
+pub type WalkFilterFn = Box<dyn FnMut(&WalkEntry) -> bool>;
+
+// Depth-first traversal, where directories are yielded before their contents
+pub struct WalkDir {
+    stack: Vec<(String, usize, ReadDir)>,
+    max_depth: usize,
+    filter: Option<WalkFilterFn>,
+}
+
+impl WalkDir {
+    pub fn new(path: String) -> Result<Self> {
+        let root_dir = read_dir(path.clone())?;
+        Ok(Self {
+            stack: vec![(path, 0, root_dir)],
+            max_depth: usize::MAX,
+            filter: None,
+        })
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // Entries rejected by the filter are neither yielded nor descended into
+    pub fn filter_entry(mut self, filter: WalkFilterFn) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+}
+
+impl Iterator for WalkDir {
+    type Item = Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (dir_path, depth, dir) = self.stack.last_mut()?;
+            let entry = match dir.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(rc)) => return Some(Err(rc)),
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            // Joining an empty name would yield the parent directory again
+            let entry_name = match entry.name.get_str() {
+                Ok(entry_name) if !entry_name.is_empty() => entry_name,
+                _ => return Some(Err(results::lib::fs::ResultInvalidPath::make())),
+            };
+            let walk_entry = WalkEntry {
+                path: path::join(dir_path, entry_name),
+                entry,
+                depth: *depth + 1,
+            };
+            if let Some(filter) = self.filter.as_mut() {
+                if !filter(&walk_entry) {
+                    continue;
+                }
+            }
+
+            if walk_entry.is_directory() && (walk_entry.depth < self.max_depth) {
+                match read_dir(walk_entry.path.clone()) {
+                    Ok(sub_dir) => {
+                        self.stack
+                            .push((walk_entry.path.clone(), walk_entry.depth, sub_dir))
+                    }
+                    Err(rc) => return Some(Err(rc)),
+                };
+            }
+            return Some(Ok(walk_entry));
+        }
+    }
+}
+
+pub fn walk_dir(path: String) -> Result<WalkDir> {
+    WalkDir::new(path)
+}
+
+// Transfer buffer size used when copying files, big enough to avoid issuing
+// too many small IPC requests
+pub const COPY_BUFFER_SIZE: usize = 0x40000;
+
+pub type CopyProgressFn<'a> = &'a mut dyn FnMut(&str, usize, usize);
+
+fn copy_file_impl(
+    src_path: String,
+    dst_path: String,
+    buf: &mut [u8],
+    progress: &mut dyn FnMut(&str, usize, usize),
+) -> Result<()> {
+    let mut src_file = open_file(src_path.clone(), FileOpenOption::Read())?;
+    let file_size = src_file.get_size()?;
+
+    let mut dst_file = open_file(
+        dst_path,
+        FileOpenOption::Create() | FileOpenOption::Write() | FileOpenOption::Truncate(),
+    )?;
+    dst_file.set_len(file_size)?;
+
+    let mut copied_size: usize = 0;
+    progress(&src_path, copied_size, file_size);
+    while copied_size < file_size {
+        let read_size = src_file.read(buf)?;
+        result_return_if!(read_size == 0, results::lib::io::ResultUnexpectedEof);
+
+        dst_file.write_all(&buf[..read_size])?;
+        copied_size += read_size;
+        progress(&src_path, copied_size, file_size);
+    }
+    dst_file.flush()
+}
+
+pub fn copy_file(src_path: String, dst_path: String, progress: CopyProgressFn) -> Result<()> {
+    let mut buf = vec![0u8; COPY_BUFFER_SIZE];
+    copy_file_impl(src_path, dst_path, &mut buf, progress)
+}
+
+fn ensure_directory(path: String) -> Result<()> {
+    match create_directory(path) {
+        Err(rc) if !results::fs::ResultPathAlreadyExists::matches(rc) => Err(rc),
+        _ => Ok(()),
+    }
+}
+
+pub fn copy_tree(src_path: String, dst_path: String, progress: CopyProgressFn) -> Result<()> {
+    if get_entry_type(src_path.clone())? == DirectoryEntryType::File {
+        return copy_file(src_path, dst_path, progress);
+    }
+
+    let mut buf = vec![0u8; COPY_BUFFER_SIZE];
+    ensure_directory(dst_path.clone())?;
+    for walk_entry in walk_dir(src_path.clone())? {
+        let walk_entry = walk_entry?;
+        let relative_path = walk_entry.path[src_path.len()..].trim_start_matches(path::SEPARATOR);
+        let entry_dst_path = path::join(&dst_path, relative_path);
+
+        if walk_entry.is_directory() {
+            ensure_directory(entry_dst_path)?;
+        } else {
+            copy_file_impl(walk_entry.path, entry_dst_path, &mut buf, progress)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn remove_tree(path: String) -> Result<()> {
+    let (fs, processed_path) = resolve_path(path)?;
+    match fs.get().get_entry_type(&processed_path)? {
+        DirectoryEntryType::Directory => fs.get().delete_directory_recursively(&processed_path),
+        DirectoryEntryType::File => fs.get().delete_file(&processed_path),
+    }
+}
+
+pub fn move_tree(src_path: String, dst_path: String, progress: CopyProgressFn) -> Result<()> {
+    // Within the same device a plain rename is enough
+    let (src_fs, _) = resolve_path(src_path.clone())?;
+    let (dst_fs, _) = resolve_path(dst_path.clone())?;
+    if src_fs.is_same(&dst_fs) {
+        return rename(src_path, dst_path);
+    }
+
+    copy_tree(src_path.clone(), dst_path, progress)?;
+    remove_tree(src_path)
+}
+
+pub mod fsp;
+
+pub mod layered;
+
+pub mod mitm;
+
+pub mod path;
+
+pub mod pfs;
+
+pub mod ram;
+
+pub mod romfs;
+
+pub mod server;
+
+pub mod storage;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_file(path: &str, data: &[u8]) -> Result<()> {
+        let mut file = open_file(
+            String::from(path),
+            FileOpenOption::Create() | FileOpenOption::Write() | FileOpenOption::Append(),
+        )?;
+        file.write_all(data)
+    }
+
+    fn read_file(path: &str) -> Result<Vec<u8>> {
+        let mut file = open_file(String::from(path), FileOpenOption::Read())?;
+        let mut data: Vec<u8> = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn walk_paths(walk: WalkDir) -> Vec<(String, usize)> {
+        walk.map(|walk_entry| {
+            let walk_entry = walk_entry.unwrap();
+            (walk_entry.path, walk_entry.depth)
+        })
+        .collect()
+    }
+
+    fn check_tree_operations() {
+        mount_ram("tree").unwrap();
+        mount_ram("tree2").unwrap();
+        let big_file = vec![0xABu8; COPY_BUFFER_SIZE + 0x10];
+        create_directory(String::from("tree:/src")).unwrap();
+        write_file("tree:/src/a.txt", b"a").unwrap();
+        create_directory(String::from("tree:/src/dir")).unwrap();
+        write_file("tree:/src/dir/big.bin", &big_file).unwrap();
+        create_directory(String::from("tree:/src/dir/sub")).unwrap();
+        write_file("tree:/src/dir/sub/c.txt", b"c").unwrap();
+        create_directory(String::from("tree:/src/skip")).unwrap();
+        write_file("tree:/src/skip/d.txt", b"").unwrap();
+
+        // Directories are yielded right before their contents
+        assert_eq!(
+            walk_paths(walk_dir(String::from("tree:/src")).unwrap()),
+            vec![
+                (String::from("tree:/src/a.txt"), 1),
+                (String::from("tree:/src/dir"), 1),
+                (String::from("tree:/src/dir/big.bin"), 2),
+                (String::from("tree:/src/dir/sub"), 2),
+                (String::from("tree:/src/dir/sub/c.txt"), 3),
+                (String::from("tree:/src/skip"), 1),
+                (String::from("tree:/src/skip/d.txt"), 2),
+            ]
+        );
+        assert_eq!(
+            walk_paths(walk_dir(String::from("tree:/src")).unwrap().max_depth(1)),
+            vec![
+                (String::from("tree:/src/a.txt"), 1),
+                (String::from("tree:/src/dir"), 1),
+                (String::from("tree:/src/skip"), 1),
+            ]
+        );
+        assert_eq!(
+            walk_paths(
+                walk_dir(String::from("tree:/src/"))
+                    .unwrap()
+                    .filter_entry(Box::new(|walk_entry| {
+                        !walk_entry.path.ends_with("/skip") && !walk_entry.path.ends_with("/sub")
+                    }))
+            ),
+            vec![
+                (String::from("tree:/src/a.txt"), 1),
+                (String::from("tree:/src/dir"), 1),
+                (String::from("tree:/src/dir/big.bin"), 2),
+            ]
+        );
+        assert!(results::fs::ResultPathNotFound::matches(
+            walk_dir(String::from("tree:/none")).err().unwrap()
+        ));
+
+        // Copies between devices report the progress of every file
+        let mut progress_calls: Vec<(String, usize, usize)> = Vec::new();
+        copy_tree(
+            String::from("tree:/src"),
+            String::from("tree2:/dst"),
+            &mut |path, copied_size, total_size| {
+                progress_calls.push((String::from(path), copied_size, total_size))
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            progress_calls,
+            vec![
+                (String::from("tree:/src/a.txt"), 0, 1),
+                (String::from("tree:/src/a.txt"), 1, 1),
+                (String::from("tree:/src/dir/big.bin"), 0, big_file.len()),
+                (
+                    String::from("tree:/src/dir/big.bin"),
+                    COPY_BUFFER_SIZE,
+                    big_file.len()
+                ),
+                (
+                    String::from("tree:/src/dir/big.bin"),
+                    big_file.len(),
+                    big_file.len()
+                ),
+                (String::from("tree:/src/dir/sub/c.txt"), 0, 1),
+                (String::from("tree:/src/dir/sub/c.txt"), 1, 1),
+                (String::from("tree:/src/skip/d.txt"), 0, 0),
+            ]
+        );
+        assert_eq!(read_file("tree2:/dst/a.txt").unwrap(), b"a");
+        assert_eq!(read_file("tree2:/dst/dir/big.bin").unwrap(), big_file);
+        assert_eq!(read_file("tree2:/dst/dir/sub/c.txt").unwrap(), b"c");
+        assert!(read_file("tree2:/dst/skip/d.txt").unwrap().is_empty());
+        assert_eq!(
+            walk_paths(walk_dir(String::from("tree2:/dst")).unwrap()).len(),
+            7
+        );
+
+        // Single files are copied as well
+        copy_tree(
+            String::from("tree:/src/a.txt"),
+            String::from("tree2:/a.txt"),
+            &mut |_, _, _| {},
+        )
+        .unwrap();
+        assert_eq!(read_file("tree2:/a.txt").unwrap(), b"a");
+
+        remove_tree(String::from("tree2:/a.txt")).unwrap();
+        remove_tree(String::from("tree2:/dst/dir")).unwrap();
+        for &path in &["tree2:/a.txt", "tree2:/dst/dir", "tree2:/dst/dir/sub/c.txt"] {
+            assert!(results::fs::ResultPathNotFound::matches(
+                get_entry_type(String::from(path)).unwrap_err()
+            ));
+        }
+        assert_eq!(read_file("tree2:/dst/a.txt").unwrap(), b"a");
+        assert!(results::fs::ResultPathNotFound::matches(
+            remove_tree(String::from("tree2:/dst/dir")).unwrap_err()
+        ));
+
+        // Moves within a device are plain renames, without any progress
+        move_tree(
+            String::from("tree:/src"),
+            String::from("tree:/moved"),
+            &mut |_, _, _| panic!("Unexpected copy"),
+        )
+        .unwrap();
+        assert_eq!(read_file("tree:/moved/dir/sub/c.txt").unwrap(), b"c");
+        assert!(results::fs::ResultPathNotFound::matches(
+            get_entry_type(String::from("tree:/src")).unwrap_err()
+        ));
+
+        unmount("tree");
+        unmount("tree2");
+    }
+
+    // The device list is global, so everything depending on it is checked in
+    // a single test (and only through device-prefixed paths, the current
+    // directory being global too)
+    #[test]
+    fn mount_ram_devices() {
+        mount_ram("ram").unwrap();
+        assert!(results::lib::fs::ResultDeviceAlreadyMounted::matches(
+            mount_ram("ram").unwrap_err()
+        ));
+        for &name in &["", "ram:", "ram/dir"] {
+            assert!(results::lib::fs::ResultInvalidPath::matches(
+                mount_ram(name).unwrap_err()
+            ));
+        }
+
+        // Paths are resolved to the mounted device and normalized
+        let ram_fs = find_device_by_name("ram").unwrap();
+        let (fs, device_path) = resolve_path(String::from("ram:/dir//./sub/../file")).unwrap();
+        assert!(fs.is_same(&ram_fs));
+        assert_eq!(device_path, "/dir/file");
+        let (_, device_path) = resolve_path(String::from("ram:")).unwrap();
+        assert_eq!(device_path, "/");
+        assert!(results::lib::fs::ResultDeviceNotFound::matches(
+            resolve_path(String::from("none:/file")).err().unwrap()
+        ));
+        assert!(results::lib::fs::ResultInvalidPath::matches(
+            resolve_path(String::from(":/file")).err().unwrap()
+        ));
+        assert!(results::lib::fs::ResultPathOutsideOfRoot::matches(
+            resolve_path(String::from("ram:/dir/../..")).err().unwrap()
+        ));
+
+        create_directory(String::from("ram:/dir")).unwrap();
+        write_file("ram:/dir/file", b"ram contents").unwrap();
+        assert_eq!(read_file("ram:/dir/./file").unwrap(), b"ram contents");
+        assert_eq!(
+            get_entry_type(String::from("ram:/dir/")).unwrap(),
+            DirectoryEntryType::Directory
+        );
+        let entries: Vec<DirectoryEntry> = read_dir(String::from("ram:/dir"))
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name.get_str().unwrap(), "file");
+        assert_eq!(entries[0].file_size, 12);
+
+        // Each device has its own backend
+        mount_ram("ram2").unwrap();
+        assert!(results::fs::ResultPathNotFound::matches(
+            get_entry_type(String::from("ram2:/dir")).unwrap_err()
+        ));
+        assert!(results::lib::fs::ResultDifferentDevices::matches(
+            rename(String::from("ram:/dir/file"), String::from("ram2:/file")).unwrap_err()
+        ));
+        move_tree(
+            String::from("ram:/dir"),
+            String::from("ram2:/moved"),
+            &mut |_, _, _| {},
+        )
+        .unwrap();
+        assert_eq!(read_file("ram2:/moved/file").unwrap(), b"ram contents");
+        assert!(results::fs::ResultPathNotFound::matches(
+            get_entry_type(String::from("ram:/dir")).unwrap_err()
+        ));
+
+        // Invalidated devices fail until they are mounted again
+        subscribe_sd_card_removal("ram").unwrap();
+        invalidate_sd_card_devices();
+        assert!(results::lib::fs::ResultDeviceRemoved::matches(
+            resolve_path(String::from("ram:/file")).err().unwrap()
+        ));
+        assert!(read_file("ram2:/moved/file").is_ok());
+        mount_ram("ram").unwrap();
+        assert!(!find_device_by_name("ram").unwrap().is_same(&ram_fs));
+
+        check_tree_operations();
+
+        unmount("ram");
+        unmount("ram2");
+        for &name in &["ram", "ram2"] {
+            assert!(results::lib::fs::ResultDeviceNotFound::matches(
+                commit(name).unwrap_err()
+            ));
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{result::*, results, service::cmif::fspsrv, sync};
+use alloc::{string::String, vec::Vec};
+use core::mem as cmem;
+
+// Paths are made of an optional device name ("sdmc:") followed by the path
+// inside the device. Normalized paths inside a device always start with a
+// single slash, have no "." or ".." segments and no empty segments
+
+pub const DEVICE_SEPARATOR: char = ':';
+pub const SEPARATOR: char = '/';
+
+// Longest path (without NUL terminator) that fits in a fsp-srv path buffer
+pub const MAX_PATH_LEN: usize = cmem::size_of::<fspsrv::Path>() - 1;
+
+pub fn split_device(path: &str) -> (Option<&str>, &str) {
+    match path.find(|c| (c == DEVICE_SEPARATOR) || (c == SEPARATOR)) {
+        Some(index) if path[index..].starts_with(DEVICE_SEPARATOR) => {
+            (Some(&path[..index]), &path[index + 1..])
+        }
+        _ => (None, path),
+    }
+}
+
+pub fn normalize(path: &str) -> Result<String> {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split(SEPARATOR) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                result_return_if!(
+                    segments.pop().is_none(),
+                    results::lib::fs::ResultPathOutsideOfRoot
+                );
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut normalized_path = String::new();
+    for segment in segments {
+        normalized_path.push(SEPARATOR);
+        normalized_path.push_str(segment);
+    }
+    if normalized_path.is_empty() {
+        normalized_path.push(SEPARATOR);
+    }
+
+    result_return_unless!(
+        normalized_path.len() <= MAX_PATH_LEN,
+        results::lib::fs::ResultPathTooLong
+    );
+    Ok(normalized_path)
+}
+
+pub fn join(base_path: &str, path: &str) -> String {
+    if base_path.ends_with(SEPARATOR) {
+        format!("{}{}", base_path, path)
+    } else {
+        format!("{}{}{}", base_path, SEPARATOR, path)
+    }
+}
+
+pub fn parent(path: &str) -> Option<&str> {
+    let path = path.trim_end_matches(SEPARATOR);
+    match path.rfind(SEPARATOR) {
+        Some(0) => Some("/"),
+        Some(index) => Some(&path[..index]),
+        None => None,
+    }
+}
+
+pub fn file_name(path: &str) -> Option<&str> {
+    let path = path.trim_end_matches(SEPARATOR);
+    match path.rfind(SEPARATOR) {
+        Some(index) => Some(&path[index + 1..]),
+        None if !path.is_empty() => Some(path),
+        None => None,
+    }
+}
+
+static mut G_CURRENT_DIR: sync::Locked<String> = sync::Locked::new(false, String::new());
+
+pub(crate) fn set_current_dir(path: String) {
+    unsafe {
+        G_CURRENT_DIR.set(path);
+    }
+}
+
+pub fn get_current_dir() -> Option<String> {
+    unsafe {
+        let cur_dir = G_CURRENT_DIR.get();
+        if cur_dir.is_empty() {
+            None
+        } else {
+            Some(cur_dir.clone())
+        }
+    }
+}
+
+// Turns any path (absolute, device-relative or relative to the current
+// directory) into a device name and a normalized path inside that device
+
+pub fn resolve(path: &str) -> Result<(String, String)> {
+    match split_device(path) {
+        (Some(device), device_path) => {
+            result_return_if!(device.is_empty(), results::lib::fs::ResultInvalidPath);
+            Ok((String::from(device), normalize(device_path)?))
+        }
+        (None, relative_path) => {
+            let cur_dir = match get_current_dir() {
+                Some(cur_dir) => cur_dir,
+                None => return Err(results::lib::fs::ResultNoCurrentDirectory::make()),
+            };
+            let (cur_device, cur_device_path) = match split_device(&cur_dir) {
+                (Some(cur_device), cur_device_path) => (cur_device, cur_device_path),
+                (None, _) => return Err(results::lib::fs::ResultInvalidPath::make()),
+            };
+
+            // Paths starting with a slash are relative to the current device root
+            let device_path = if relative_path.starts_with(SEPARATOR) {
+                String::from(relative_path)
+            } else {
+                join(cur_device_path, relative_path)
+            };
+            Ok((String::from(cur_device), normalize(&device_path)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_normalized(path: &str, normalized_path: &str) {
+        assert_eq!(normalize(path).unwrap(), normalized_path);
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_normalized("", "/");
+        assert_normalized("/", "/");
+        assert_normalized("/a/b", "/a/b");
+        assert_normalized("a/b", "/a/b");
+
+        // Repeated separators and trailing slashes
+        assert_normalized("//a///b", "/a/b");
+        assert_normalized("/a/b/", "/a/b");
+        assert_normalized("/a/b//", "/a/b");
+
+        // "." segments
+        assert_normalized("/.", "/");
+        assert_normalized("/./a/./b/.", "/a/b");
+
+        // ".." segments
+        assert_normalized("/a/..", "/");
+        assert_normalized("/a/b/../c", "/a/c");
+        assert_normalized("/a/./../b/", "/b");
+    }
+
+    #[test]
+    fn normalize_outside_of_root() {
+        for &path in &["..", "/..", "/../a", "/a/../..", "/a/./../../b"] {
+            assert!(results::lib::fs::ResultPathOutsideOfRoot::matches(
+                normalize(path).unwrap_err()
+            ));
+        }
+    }
+
+    #[test]
+    fn normalize_path_length() {
+        let mut path = String::from("/");
+        path.push_str(&"a".repeat(MAX_PATH_LEN - 1));
+        assert_normalized(&path, &path);
+
+        // Only the normalized length counts
+        let mut long_path = path.clone();
+        long_path.push_str("/b/..");
+        assert_normalized(&long_path, &path);
+
+        path.push('a');
+        assert!(results::lib::fs::ResultPathTooLong::matches(
+            normalize(&path).unwrap_err()
+        ));
+    }
+
+    #[test]
+    fn split_device_prefix() {
+        assert_eq!(split_device("sdmc:/a/b"), (Some("sdmc"), "/a/b"));
+        assert_eq!(split_device("sdmc:"), (Some("sdmc"), ""));
+        assert_eq!(split_device(":/a"), (Some(""), "/a"));
+        assert_eq!(split_device("/a/b"), (None, "/a/b"));
+        // Only a separator before any slash is a device one
+        assert_eq!(split_device("a/b:c"), (None, "a/b:c"));
+    }
+
+    #[test]
+    fn path_components() {
+        assert_eq!(join("/a", "b"), "/a/b");
+        assert_eq!(join("/a/", "b"), "/a/b");
+        assert_eq!(parent("/a/b"), Some("/a"));
+        assert_eq!(parent("/a/b/"), Some("/a"));
+        assert_eq!(parent("/a"), Some("/"));
+        assert_eq!(parent("a"), None);
+        assert_eq!(file_name("/a/b"), Some("b"));
+        assert_eq!(file_name("/a/b/"), Some("b"));
+        assert_eq!(file_name("b"), Some("b"));
+        assert_eq!(file_name("/"), None);
+    }
+
+    // The current directory is global, so everything depending on it is
+    // checked in a single test
+    #[test]
+    fn resolve_paths() {
+        set_current_dir(String::new());
+        assert!(results::lib::fs::ResultNoCurrentDirectory::matches(
+            resolve("a/b").unwrap_err()
+        ));
+
+        // Device prefixes don't depend on the current directory
+        assert_eq!(
+            resolve("sdmc:/a/./b//c/").unwrap(),
+            (String::from("sdmc"), String::from("/a/b/c"))
+        );
+        assert_eq!(
+            resolve("sdmc:").unwrap(),
+            (String::from("sdmc"), String::from("/"))
+        );
+        assert!(results::lib::fs::ResultInvalidPath::matches(
+            resolve(":/a").unwrap_err()
+        ));
+        assert!(results::lib::fs::ResultPathOutsideOfRoot::matches(
+            resolve("sdmc:/a/../..").unwrap_err()
+        ));
+
+        set_current_dir(String::from("sdmc:/switch/app"));
+        assert_eq!(
+            resolve("data/../file.txt").unwrap(),
+            (String::from("sdmc"), String::from("/switch/app/file.txt"))
+        );
+        assert!(results::lib::fs::ResultPathOutsideOfRoot::matches(
+            resolve("../../..").unwrap_err()
+        ));
+        assert_eq!(
+            resolve(".").unwrap(),
+            (String::from("sdmc"), String::from("/switch/app"))
+        );
+        // Paths starting with a slash are relative to the current device's root
+        assert_eq!(
+            resolve("/file.txt").unwrap(),
+            (String::from("sdmc"), String::from("/file.txt"))
+        );
+
+        set_current_dir(String::new());
+    }
+}
@@ -0,0 +1,528 @@
+use crate::{
+    crypto::sha256,
+    fs,
+    fs::{path, ram, romfs, romfs::Source},
+    mem,
+    result::*,
+    results,
+};
+use alloc::{string::String, vec::Vec};
+use core::{mem as cmem, slice};
+
+// Read-only partition filesystems (PFS0, used by NSPs and ExeFS, and HFS0, used
+// by XCI partitions): a flat list of files after a header, an entry table and
+// a string table
+
+pub const PFS0_MAGIC: u32 = u32::from_le_bytes(*b"PFS0");
+pub const HFS0_MAGIC: u32 = u32::from_le_bytes(*b"HFS0");
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format {
+    Pfs0,
+    Hfs0,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct Header {
+    pub magic: u32,
+    pub file_count: u32,
+    pub string_table_size: u32,
+    pub reserved: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct Pfs0Entry {
+    pub offset: u64,
+    pub size: u64,
+    pub string_offset: u32,
+    pub reserved: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct Hfs0Entry {
+    pub offset: u64,
+    pub size: u64,
+    pub string_offset: u32,
+    pub hashed_region_size: u32,
+    pub reserved: u64,
+    pub hash: [u8; sha256::HASH_SIZE],
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+    pub name: String,
+    // Absolute offset inside the source
+    pub data_offset: usize,
+    pub data_size: usize,
+    // Only present in HFS0 entries: hash of the first hashed_region_size bytes
+    pub hashed_region_size: usize,
+    pub hash: Option<[u8; sha256::HASH_SIZE]>,
+}
+
+fn read_val<T: Copy + Default>(source: &mut dyn Source, offset: usize) -> Result<T> {
+    let mut t: T = Default::default();
+    let t_buf =
+        unsafe { slice::from_raw_parts_mut(&mut t as *mut T as *mut u8, cmem::size_of::<T>()) };
+    source.read(offset, t_buf)?;
+    Ok(t)
+}
+
+fn read_entry_name(string_table: &[u8], string_offset: u32) -> Result<String> {
+    let string_offset = string_offset as usize;
+    result_return_unless!(
+        string_offset < string_table.len(),
+        results::lib::fs::ResultInvalidPartitionEntry
+    );
+    let name_data = &string_table[string_offset..];
+    let name_len = name_data
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(name_data.len());
+    match core::str::from_utf8(&name_data[..name_len]) {
+        Ok(name) if !name.is_empty() => Ok(String::from(name)),
+        _ => Err(results::lib::fs::ResultInvalidPartitionEntry::make()),
+    }
+}
+
+pub struct FileSystem {
+    source: mem::Shared<dyn Source>,
+    format: Format,
+    entries: Vec<Entry>,
+    verify_hashes: bool,
+}
+
+impl FileSystem {
+    pub fn new(source: mem::Shared<dyn Source>) -> Result<Self> {
+        let header: Header = read_val(source.get(), 0)?;
+        let (format, entry_size) = match header.magic {
+            PFS0_MAGIC => (Format::Pfs0, cmem::size_of::<Pfs0Entry>()),
+            HFS0_MAGIC => (Format::Hfs0, cmem::size_of::<Hfs0Entry>()),
+            _ => return Err(results::lib::fs::ResultInvalidPartitionHeader::make()),
+        };
+
+        // Both tables must fit in the source before allocating anything for them
+        let source_size = source.get().get_size()? as u64;
+        let entry_table_offset = cmem::size_of::<Header>() as u64;
+        let data_offset = (header.file_count as u64)
+            .checked_mul(entry_size as u64)
+            .and_then(|entry_table_size| entry_table_size.checked_add(entry_table_offset))
+            .and_then(|string_table_offset| {
+                string_table_offset.checked_add(header.string_table_size as u64)
+            });
+        let data_offset = match data_offset {
+            Some(data_offset) if data_offset <= source_size => data_offset,
+            _ => return Err(results::lib::fs::ResultInvalidPartitionHeader::make()),
+        };
+
+        let string_table_offset = data_offset - header.string_table_size as u64;
+        let mut string_table = vec![0u8; header.string_table_size as usize];
+        source
+            .get()
+            .read(string_table_offset as usize, &mut string_table)?;
+
+        let data_region_size = source_size - data_offset;
+        let mut entries: Vec<Entry> = Vec::with_capacity(header.file_count as usize);
+        for i in 0..header.file_count as usize {
+            let entry_offset = entry_table_offset as usize + i * entry_size;
+            let (offset, size, string_offset, hashed_region_size, hash) = match format {
+                Format::Pfs0 => {
+                    let entry: Pfs0Entry = read_val(source.get(), entry_offset)?;
+                    (entry.offset, entry.size, entry.string_offset, 0, None)
+                }
+                Format::Hfs0 => {
+                    let entry: Hfs0Entry = read_val(source.get(), entry_offset)?;
+                    (
+                        entry.offset,
+                        entry.size,
+                        entry.string_offset,
+                        entry.hashed_region_size,
+                        Some(entry.hash),
+                    )
+                }
+            };
+            result_return_unless!(
+                (hashed_region_size as u64) <= size,
+                results::lib::fs::ResultInvalidPartitionEntry
+            );
+            // Entry offsets are relative to the data region, which ends with the source
+            result_return_unless!(
+                offset
+                    .checked_add(size)
+                    .map_or(false, |end_offset| end_offset <= data_region_size),
+                results::lib::fs::ResultInvalidPartitionEntry
+            );
+
+            entries.push(Entry {
+                name: read_entry_name(&string_table, string_offset)?,
+                data_offset: (data_offset + offset) as usize,
+                data_size: size as usize,
+                hashed_region_size: hashed_region_size as usize,
+                hash,
+            });
+        }
+
+        Ok(Self {
+            source,
+            format,
+            entries,
+            verify_hashes: false,
+        })
+    }
+
+    pub fn get_format(&self) -> Format {
+        self.format
+    }
+
+    pub fn get_entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    // When enabled, HFS0 files get their hash checked every time they're opened
+    pub fn set_verify_hashes(&mut self, verify_hashes: bool) {
+        self.verify_hashes = verify_hashes;
+    }
+
+    pub fn find_entry(&self, name: &str) -> Result<&Entry> {
+        match self.entries.iter().find(|entry| entry.name == name) {
+            Some(entry) => Ok(entry),
+            None => Err(results::fs::ResultPathNotFound::make()),
+        }
+    }
+
+    pub fn verify_entry(&self, entry: &Entry) -> Result<()> {
+        if let Some(hash) = entry.hash {
+            let mut hashed_region = vec![0u8; entry.hashed_region_size];
+            self.source
+                .get()
+                .read(entry.data_offset, &mut hashed_region)?;
+            result_return_unless!(
+                sha256::hash(&hashed_region) == hash,
+                results::lib::fs::ResultPartitionHashMismatch
+            );
+        }
+        Ok(())
+    }
+
+    fn find_path_entry(&self, path: &str) -> Result<&Entry> {
+        // Partitions have no directories, so only files right under the root
+        // are valid
+        let name = path.trim_start_matches(path::SEPARATOR);
+        result_return_if!(
+            name.is_empty() || name.contains(path::SEPARATOR),
+            results::fs::ResultPathNotFound
+        );
+        self.find_entry(name)
+    }
+}
+
+fn is_root_path(path: &str) -> bool {
+    path.trim_matches(path::SEPARATOR).is_empty()
+}
+
+impl fs::FileSystem for FileSystem {
+    fn create_file(
+        &mut self,
+        _path: &str,
+        _attribute: fs::FileAttribute,
+        _size: usize,
+    ) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn delete_file(&mut self, _path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn create_directory(&mut self, _path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn delete_directory(&mut self, _path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn delete_directory_recursively(&mut self, _path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn get_entry_type(&mut self, path: &str) -> Result<fs::DirectoryEntryType> {
+        if is_root_path(path) {
+            return Ok(fs::DirectoryEntryType::Directory);
+        }
+
+        self.find_path_entry(path)?;
+        Ok(fs::DirectoryEntryType::File)
+    }
+
+    fn open_file(
+        &mut self,
+        path: &str,
+        mode: fs::FileOpenMode,
+    ) -> Result<mem::Shared<dyn fs::FileHandle>> {
+        result_return_if!(
+            mode.contains(fs::FileOpenMode::Write()) || mode.contains(fs::FileOpenMode::Append()),
+            results::fs::ResultWriteNotPermitted
+        );
+
+        let entry = self.find_path_entry(path)?;
+        if self.verify_hashes {
+            self.verify_entry(entry)?;
+        }
+        Ok(mem::Shared::new(romfs::File::new(
+            self.source.clone(),
+            entry.data_offset,
+            entry.data_size,
+        )))
+    }
+
+    fn open_directory(
+        &mut self,
+        path: &str,
+        mode: fs::DirectoryOpenMode,
+    ) -> Result<mem::Shared<dyn fs::DirectoryHandle>> {
+        result_return_unless!(is_root_path(path), results::fs::ResultPathNotFound);
+
+        let mut entries: Vec<fs::DirectoryEntry> = Vec::new();
+        if mode.contains(fs::DirectoryOpenMode::ReadFiles()) {
+            for entry in &self.entries {
+                let mut dir_entry: fs::DirectoryEntry = Default::default();
+                dir_entry.name.set_str(&entry.name)?;
+                dir_entry.entry_type = fs::DirectoryEntryType::File;
+                if !mode.contains(fs::DirectoryOpenMode::NoFileSize()) {
+                    dir_entry.file_size = entry.data_size;
+                }
+                entries.push(dir_entry);
+            }
+        }
+        Ok(mem::Shared::new(ram::Directory::new(entries)))
+    }
+}
+
+pub fn open_file(path: String) -> Result<FileSystem> {
+    let file = fs::open_file(path, fs::FileOpenOption::Read())?;
+    FileSystem::new(mem::Shared::new(romfs::FileSource::new(
+        file.get_handle(),
+        0,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::FileSystem as _;
+
+    const FILES: &[(&str, &[u8])] = &[
+        ("main", b"main data"),
+        ("main.npdm", b"npdm"),
+        ("empty", b""),
+    ];
+
+    fn push_val<T: Copy>(image: &mut Vec<u8>, t: T) {
+        let t_buf =
+            unsafe { slice::from_raw_parts(&t as *const T as *const u8, cmem::size_of::<T>()) };
+        image.extend_from_slice(t_buf);
+    }
+
+    fn build_partition(format: Format) -> Vec<u8> {
+        let mut string_table: Vec<u8> = Vec::new();
+        let mut data: Vec<u8> = Vec::new();
+        let mut entries: Vec<u8> = Vec::new();
+        for &(name, file_data) in FILES {
+            let (offset, size, string_offset) = (
+                data.len() as u64,
+                file_data.len() as u64,
+                string_table.len() as u32,
+            );
+            match format {
+                Format::Pfs0 => push_val(
+                    &mut entries,
+                    Pfs0Entry {
+                        offset,
+                        size,
+                        string_offset,
+                        reserved: 0,
+                    },
+                ),
+                Format::Hfs0 => push_val(
+                    &mut entries,
+                    Hfs0Entry {
+                        offset,
+                        size,
+                        string_offset,
+                        hashed_region_size: file_data.len() as u32,
+                        reserved: 0,
+                        hash: sha256::hash(file_data),
+                    },
+                ),
+            }
+            string_table.extend_from_slice(name.as_bytes());
+            string_table.push(0);
+            data.extend_from_slice(file_data);
+        }
+        while (string_table.len() % 0x10) != 0 {
+            string_table.push(0);
+        }
+
+        let mut image: Vec<u8> = Vec::new();
+        push_val(
+            &mut image,
+            Header {
+                magic: match format {
+                    Format::Pfs0 => PFS0_MAGIC,
+                    Format::Hfs0 => HFS0_MAGIC,
+                },
+                file_count: FILES.len() as u32,
+                string_table_size: string_table.len() as u32,
+                reserved: 0,
+            },
+        );
+        image.extend_from_slice(&entries);
+        image.extend_from_slice(&string_table);
+        image.extend_from_slice(&data);
+        image
+    }
+
+    fn open_partition(image: Vec<u8>) -> Result<FileSystem> {
+        FileSystem::new(mem::Shared::new(image))
+    }
+
+    fn read_file(partition: &mut FileSystem, path: &str) -> Result<Vec<u8>> {
+        let file = partition.open_file(path, fs::FileOpenMode::Read())?;
+        let mut data = vec![0u8; 0x20];
+        let read_size = file.get().read(0, &mut data)?;
+        data.truncate(read_size);
+        Ok(data)
+    }
+
+    #[test]
+    fn read_pfs0_files() {
+        let mut partition = open_partition(build_partition(Format::Pfs0)).unwrap();
+        assert_eq!(partition.get_format(), Format::Pfs0);
+        assert_eq!(partition.get_entries().len(), FILES.len());
+
+        for &(name, file_data) in FILES {
+            let entry = partition.find_entry(name).unwrap();
+            assert_eq!(entry.data_size, file_data.len());
+            assert_eq!(entry.hash, None);
+
+            let path = format!("/{}", name);
+            assert_eq!(
+                partition.get_entry_type(&path).unwrap(),
+                fs::DirectoryEntryType::File
+            );
+            assert_eq!(read_file(&mut partition, &path).unwrap(), file_data);
+        }
+
+        assert!(results::fs::ResultPathNotFound::matches(
+            partition.get_entry_type("/missing").unwrap_err()
+        ));
+        assert!(results::fs::ResultPathNotFound::matches(
+            partition.get_entry_type("/main/main").unwrap_err()
+        ));
+        assert!(results::fs::ResultWriteNotPermitted::matches(
+            partition
+                .open_file("/main", fs::FileOpenMode::Write())
+                .err()
+                .unwrap()
+        ));
+    }
+
+    #[test]
+    fn list_pfs0_root() {
+        let mut partition = open_partition(build_partition(Format::Pfs0)).unwrap();
+        assert_eq!(
+            partition.get_entry_type("/").unwrap(),
+            fs::DirectoryEntryType::Directory
+        );
+
+        let dir = partition
+            .open_directory("/", fs::DirectoryOpenMode::ReadFiles())
+            .unwrap();
+        let mut dir_entries = [fs::DirectoryEntry::default(); 4];
+        assert_eq!(dir.get().read(&mut dir_entries).unwrap(), FILES.len());
+        for (dir_entry, &(name, file_data)) in dir_entries.iter().zip(FILES) {
+            assert_eq!(dir_entry.name.get_str().unwrap(), name);
+            assert_eq!(dir_entry.entry_type, fs::DirectoryEntryType::File);
+            assert_eq!(dir_entry.file_size, file_data.len());
+        }
+    }
+
+    #[test]
+    fn verify_hfs0_hashes() {
+        let mut image = build_partition(Format::Hfs0);
+        let mut partition = open_partition(image.clone()).unwrap();
+        partition.set_verify_hashes(true);
+        assert_eq!(partition.get_format(), Format::Hfs0);
+        for &(name, file_data) in FILES {
+            assert_eq!(
+                partition.find_entry(name).unwrap().hash,
+                Some(sha256::hash(file_data))
+            );
+            assert_eq!(read_file(&mut partition, name).unwrap(), file_data);
+        }
+
+        // File data comes last in the image, flip the first byte of the first file
+        let data_size: usize = FILES.iter().map(|&(_, file_data)| file_data.len()).sum();
+        let data_offset = image.len() - data_size;
+        image[data_offset] ^= 0xFF;
+
+        let mut partition = open_partition(image).unwrap();
+        assert_eq!(read_file(&mut partition, "main").unwrap()[0], b'm' ^ 0xFF);
+        partition.set_verify_hashes(true);
+        assert!(results::lib::fs::ResultPartitionHashMismatch::matches(
+            read_file(&mut partition, "main").unwrap_err()
+        ));
+        assert_eq!(read_file(&mut partition, "main.npdm").unwrap(), b"npdm");
+    }
+
+    #[test]
+    fn reject_invalid_partitions() {
+        let mut image = build_partition(Format::Pfs0);
+        image[0] = b'X';
+        assert!(results::lib::fs::ResultInvalidPartitionHeader::matches(
+            open_partition(image).err().unwrap()
+        ));
+
+        // First entry's string offset, past the string table
+        let mut image = build_partition(Format::Pfs0);
+        let string_offset_offset = cmem::size_of::<Header>() + 0x10;
+        image[string_offset_offset..string_offset_offset + 4]
+            .copy_from_slice(&0x1000u32.to_le_bytes());
+        assert!(results::lib::fs::ResultInvalidPartitionEntry::matches(
+            open_partition(image).err().unwrap()
+        ));
+
+        // Entry table larger than the whole image
+        let mut image = build_partition(Format::Hfs0);
+        image[4..8].copy_from_slice(&0x1000u32.to_le_bytes());
+        assert!(open_partition(image).is_err());
+    }
+
+    #[test]
+    fn reject_oversized_partitions() {
+        // Huge counts and sizes must be rejected before allocating anything
+        for &(field_offset, value) in &[(4, u32::MAX), (8, u32::MAX), (8, 0x1000)] {
+            for &format in &[Format::Pfs0, Format::Hfs0] {
+                let mut image = build_partition(format);
+                image[field_offset..field_offset + 4].copy_from_slice(&value.to_le_bytes());
+                assert!(results::lib::fs::ResultInvalidPartitionHeader::matches(
+                    open_partition(image).err().unwrap()
+                ));
+            }
+        }
+
+        // First entry's offset and size, overflowing or past the end of the image
+        let entry_offset = cmem::size_of::<Header>();
+        for &(offset, size) in &[(u64::MAX, 1), (0, u64::MAX), (0, 0x1000), (0x1000, 0)] {
+            let mut image = build_partition(Format::Pfs0);
+            image[entry_offset..entry_offset + 8].copy_from_slice(&offset.to_le_bytes());
+            image[entry_offset + 8..entry_offset + 16].copy_from_slice(&size.to_le_bytes());
+            assert!(results::lib::fs::ResultInvalidPartitionEntry::matches(
+                open_partition(image).err().unwrap()
+            ));
+        }
+    }
+}
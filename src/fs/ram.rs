@@ -0,0 +1,349 @@
+use crate::{fs, mem, result::*, results};
+use alloc::{string::String, vec::Vec};
+use core::cmp;
+
+// Simple in-memory filesystem, mostly meant for temporary/virtual drives
+// (everything is lost once the filesystem object is dropped)
+
+enum NodeData {
+    Directory,
+    File(mem::Shared<Vec<u8>>),
+}
+
+struct Node {
+    path: String,
+    data: NodeData,
+}
+
+impl Node {
+    pub fn new_directory(path: &str) -> Self {
+        Self {
+            path: String::from(path),
+            data: NodeData::Directory,
+        }
+    }
+
+    pub fn new_file(path: &str, size: usize) -> Self {
+        Self {
+            path: String::from(path),
+            data: NodeData::File(mem::Shared::new(vec![0; size])),
+        }
+    }
+
+    pub fn get_entry_type(&self) -> fs::DirectoryEntryType {
+        match self.data {
+            NodeData::Directory => fs::DirectoryEntryType::Directory,
+            NodeData::File(_) => fs::DirectoryEntryType::File,
+        }
+    }
+}
+
+// Paths are stored without trailing slashes, the root directory being the empty
+// path
+
+fn normalize_path(path: &str) -> &str {
+    path.trim_end_matches('/')
+}
+
+fn split_parent_path(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(index) => (&path[..index], &path[index + 1..]),
+        None => ("", path),
+    }
+}
+
+fn is_descendant_path(path: &str, dir_path: &str) -> bool {
+    path.starts_with(dir_path) && path[dir_path.len()..].starts_with('/')
+}
+
+pub struct File {
+    data: mem::Shared<Vec<u8>>,
+    mode: fs::FileOpenMode,
+}
+
+impl fs::FileHandle for File {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        result_return_unless!(
+            self.mode.contains(fs::FileOpenMode::Read()),
+            results::fs::ResultReadNotPermitted
+        );
+
+        let data = self.data.get();
+        if offset >= data.len() {
+            return Ok(0);
+        }
+
+        let read_size = cmp::min(buf.len(), data.len() - offset);
+        buf[..read_size].copy_from_slice(&data[offset..offset + read_size]);
+        Ok(read_size)
+    }
+
+    fn write(&mut self, offset: usize, buf: &[u8], _option: fs::FileWriteOption) -> Result<()> {
+        result_return_unless!(
+            self.mode.contains(fs::FileOpenMode::Write()),
+            results::fs::ResultWriteNotPermitted
+        );
+
+        let data = self.data.get();
+        let end_offset = offset + buf.len();
+        if end_offset > data.len() {
+            result_return_unless!(
+                self.mode.contains(fs::FileOpenMode::Append()),
+                results::fs::ResultFileExtensionWithoutOpenModeAllowAppend
+            );
+            data.resize(end_offset, 0);
+        }
+
+        data[offset..end_offset].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        // Nothing to flush, writes go straight to memory
+        Ok(())
+    }
+
+    fn set_size(&mut self, size: usize) -> Result<()> {
+        result_return_unless!(
+            self.mode.contains(fs::FileOpenMode::Write()),
+            results::fs::ResultWriteNotPermitted
+        );
+
+        self.data.get().resize(size, 0);
+        Ok(())
+    }
+
+    fn get_size(&mut self) -> Result<usize> {
+        Ok(self.data.get().len())
+    }
+}
+
+pub struct Directory {
+    entries: Vec<fs::DirectoryEntry>,
+    entry_index: usize,
+}
+
+impl Directory {
+    pub fn new(entries: Vec<fs::DirectoryEntry>) -> Self {
+        Self {
+            entries,
+            entry_index: 0,
+        }
+    }
+}
+
+impl fs::DirectoryHandle for Directory {
+    fn read(&mut self, entries: &mut [fs::DirectoryEntry]) -> Result<usize> {
+        let read_count = cmp::min(entries.len(), self.entries.len() - self.entry_index);
+        entries[..read_count]
+            .copy_from_slice(&self.entries[self.entry_index..self.entry_index + read_count]);
+        self.entry_index += read_count;
+        Ok(read_count)
+    }
+
+    fn get_entry_count(&mut self) -> Result<u64> {
+        Ok(self.entries.len() as u64)
+    }
+}
+
+pub struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+impl FileSystem {
+    pub const fn new() -> Self {
+        Self { nodes: Vec::new() }
+    }
+
+    fn find_node(&self, path: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.path == path)
+    }
+
+    fn get_entry_type_impl(&self, path: &str) -> Result<fs::DirectoryEntryType> {
+        if path.is_empty() {
+            return Ok(fs::DirectoryEntryType::Directory);
+        }
+
+        match self.find_node(path) {
+            Some(index) => Ok(self.nodes[index].get_entry_type()),
+            None => Err(results::fs::ResultPathNotFound::make()),
+        }
+    }
+
+    fn ensure_new_entry(&self, path: &str) -> Result<()> {
+        result_return_if!(
+            path.is_empty() || self.find_node(path).is_some(),
+            results::fs::ResultPathAlreadyExists
+        );
+
+        let (parent_path, _) = split_parent_path(path);
+        result_return_unless!(
+            self.get_entry_type_impl(parent_path)? == fs::DirectoryEntryType::Directory,
+            results::fs::ResultPathNotFound
+        );
+        Ok(())
+    }
+
+    fn find_node_with_type(&self, path: &str, entry_type: fs::DirectoryEntryType) -> Result<usize> {
+        match self.find_node(path) {
+            Some(index) if self.nodes[index].get_entry_type() == entry_type => Ok(index),
+            _ => Err(results::fs::ResultPathNotFound::make()),
+        }
+    }
+}
+
+impl fs::FileSystem for FileSystem {
+    fn create_file(
+        &mut self,
+        path: &str,
+        _attribute: fs::FileAttribute,
+        size: usize,
+    ) -> Result<()> {
+        let path = normalize_path(path);
+        self.ensure_new_entry(path)?;
+
+        self.nodes.push(Node::new_file(path, size));
+        Ok(())
+    }
+
+    fn delete_file(&mut self, path: &str) -> Result<()> {
+        let index = self.find_node_with_type(normalize_path(path), fs::DirectoryEntryType::File)?;
+
+        self.nodes.remove(index);
+        Ok(())
+    }
+
+    fn create_directory(&mut self, path: &str) -> Result<()> {
+        let path = normalize_path(path);
+        self.ensure_new_entry(path)?;
+
+        self.nodes.push(Node::new_directory(path));
+        Ok(())
+    }
+
+    fn delete_directory(&mut self, path: &str) -> Result<()> {
+        let path = normalize_path(path);
+        let index = self.find_node_with_type(path, fs::DirectoryEntryType::Directory)?;
+        result_return_if!(
+            self.nodes
+                .iter()
+                .any(|node| is_descendant_path(&node.path, path)),
+            results::fs::ResultDirectoryNotEmpty
+        );
+
+        self.nodes.remove(index);
+        Ok(())
+    }
+
+    fn delete_directory_recursively(&mut self, path: &str) -> Result<()> {
+        let path = normalize_path(path);
+        self.find_node_with_type(path, fs::DirectoryEntryType::Directory)?;
+
+        self.nodes
+            .retain(|node| (node.path != path) && !is_descendant_path(&node.path, path));
+        Ok(())
+    }
+
+    fn get_entry_type(&mut self, path: &str) -> Result<fs::DirectoryEntryType> {
+        self.get_entry_type_impl(normalize_path(path))
+    }
+
+    fn open_file(
+        &mut self,
+        path: &str,
+        mode: fs::FileOpenMode,
+    ) -> Result<mem::Shared<dyn fs::FileHandle>> {
+        let index = self.find_node_with_type(normalize_path(path), fs::DirectoryEntryType::File)?;
+
+        match self.nodes[index].data {
+            NodeData::File(ref data) => Ok(mem::Shared::new(File {
+                data: data.clone(),
+                mode,
+            })),
+            NodeData::Directory => Err(results::fs::ResultPathNotFound::make()),
+        }
+    }
+
+    fn open_directory(
+        &mut self,
+        path: &str,
+        mode: fs::DirectoryOpenMode,
+    ) -> Result<mem::Shared<dyn fs::DirectoryHandle>> {
+        let path = normalize_path(path);
+        result_return_unless!(
+            self.get_entry_type_impl(path)? == fs::DirectoryEntryType::Directory,
+            results::fs::ResultPathNotFound
+        );
+
+        let mut entries: Vec<fs::DirectoryEntry> = Vec::new();
+        for node in &self.nodes {
+            let (parent_path, name) = split_parent_path(&node.path);
+            if parent_path != path {
+                continue;
+            }
+
+            let mut entry: fs::DirectoryEntry = Default::default();
+            entry.name.set_str(name)?;
+            entry.entry_type = node.get_entry_type();
+            match node.data {
+                NodeData::Directory => {
+                    if !mode.contains(fs::DirectoryOpenMode::ReadDirectories()) {
+                        continue;
+                    }
+                }
+                NodeData::File(ref data) => {
+                    if !mode.contains(fs::DirectoryOpenMode::ReadFiles()) {
+                        continue;
+                    }
+                    if !mode.contains(fs::DirectoryOpenMode::NoFileSize()) {
+                        entry.file_size = data.get().len();
+                    }
+                }
+            };
+            entries.push(entry);
+        }
+
+        Ok(mem::Shared::new(Directory::new(entries)))
+    }
+
+    fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<()> {
+        let new_path = normalize_path(new_path);
+        let index =
+            self.find_node_with_type(normalize_path(old_path), fs::DirectoryEntryType::File)?;
+        self.ensure_new_entry(new_path)?;
+
+        self.nodes[index].path = String::from(new_path);
+        Ok(())
+    }
+
+    fn rename_directory(&mut self, old_path: &str, new_path: &str) -> Result<()> {
+        let old_path = normalize_path(old_path);
+        let new_path = normalize_path(new_path);
+        self.find_node_with_type(old_path, fs::DirectoryEntryType::Directory)?;
+        self.ensure_new_entry(new_path)?;
+        // A directory can't be moved inside itself
+        result_return_if!(
+            is_descendant_path(new_path, old_path),
+            results::fs::ResultUnsupportedOperation
+        );
+
+        for node in &mut self.nodes {
+            if (node.path == old_path) || is_descendant_path(&node.path, old_path) {
+                node.path = format!("{}{}", new_path, &node.path[old_path.len()..]);
+            }
+        }
+        Ok(())
+    }
+
+    fn clean_directory_recursively(&mut self, path: &str) -> Result<()> {
+        let path = normalize_path(path);
+        result_return_unless!(
+            self.get_entry_type_impl(path)? == fs::DirectoryEntryType::Directory,
+            results::fs::ResultPathNotFound
+        );
+
+        self.nodes
+            .retain(|node| !is_descendant_path(&node.path, path));
+        Ok(())
+    }
+}
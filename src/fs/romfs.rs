@@ -0,0 +1,876 @@
+use crate::{fs, mem, nro, result::*, results};
+use alloc::vec::Vec;
+use core::{cmp, mem as cmem, ptr, slice};
+
+// Read-only RomFS filesystem, parsed over any kind of byte source
+
+pub trait Source {
+    // Must fill the entire buffer, failing otherwise
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<()>;
+    fn get_size(&mut self) -> Result<usize>;
+}
+
+impl Source for Vec<u8> {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        let end_offset = offset.checked_add(buf.len());
+        match end_offset {
+            Some(end_offset) if end_offset <= self.len() => {
+                buf.copy_from_slice(&self[offset..end_offset]);
+                Ok(())
+            }
+            _ => Err(results::lib::fs::ResultSourceOutOfBounds::make()),
+        }
+    }
+
+    fn get_size(&mut self) -> Result<usize> {
+        Ok(self.len())
+    }
+}
+
+pub struct FileSource {
+    file: mem::Shared<dyn fs::FileHandle>,
+    base_offset: usize,
+}
+
+impl FileSource {
+    pub fn new(file: mem::Shared<dyn fs::FileHandle>, base_offset: usize) -> Self {
+        Self { file, base_offset }
+    }
+}
+
+impl Source for FileSource {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        let mut read_offset = 0;
+        while read_offset < buf.len() {
+            let read_size = self.file.get().read(
+                self.base_offset + offset + read_offset,
+                &mut buf[read_offset..],
+            )?;
+            result_return_if!(read_size == 0, results::lib::fs::ResultSourceOutOfBounds);
+            read_offset += read_size;
+        }
+        Ok(())
+    }
+
+    fn get_size(&mut self) -> Result<usize> {
+        Ok(self.file.get().get_size()?.saturating_sub(self.base_offset))
+    }
+}
+
+pub struct StorageSource {
+    storage: mem::Shared<dyn fs::StorageHandle>,
+}
+
+impl StorageSource {
+    pub fn new(storage: mem::Shared<dyn fs::StorageHandle>) -> Self {
+        Self { storage }
+    }
+}
+
+impl Source for StorageSource {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        self.storage.get().read(offset, buf)
+    }
+
+    fn get_size(&mut self) -> Result<usize> {
+        self.storage.get().get_size()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct Header {
+    pub header_size: u64,
+    pub dir_hash_table_offset: u64,
+    pub dir_hash_table_size: u64,
+    pub dir_table_offset: u64,
+    pub dir_table_size: u64,
+    pub file_hash_table_offset: u64,
+    pub file_hash_table_size: u64,
+    pub file_table_offset: u64,
+    pub file_table_size: u64,
+    pub file_data_offset: u64,
+}
+
+pub const EMPTY_ENTRY: u32 = u32::MAX;
+pub const ROOT_DIRECTORY_OFFSET: u32 = 0;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct DirectoryEntryHeader {
+    pub parent: u32,
+    pub sibling: u32,
+    pub child_dir: u32,
+    pub child_file: u32,
+    pub hash_sibling: u32,
+    pub name_len: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct FileEntryHeader {
+    pub parent: u32,
+    pub sibling: u32,
+    pub data_offset: u64,
+    pub data_size: u64,
+    pub hash_sibling: u32,
+    pub name_len: u32,
+}
+
+pub fn calculate_path_hash(parent: u32, name: &str, table_count: usize) -> usize {
+    let mut hash = parent ^ 123456789;
+    for byte in name.bytes() {
+        hash = hash.rotate_right(5) ^ (byte as u32);
+    }
+    (hash as usize) % table_count
+}
+
+fn read_table<T: Copy + Default>(
+    source: &mut dyn Source,
+    offset: u64,
+    size: u64,
+) -> Result<Vec<T>> {
+    // Table locations come from the header, so they must be checked before
+    // allocating anything for them
+    let source_size = source.get_size()? as u64;
+    result_return_unless!(
+        (offset <= source_size) && (size <= (source_size - offset)),
+        results::lib::fs::ResultInvalidRomFsHeader
+    );
+
+    let count = size as usize / cmem::size_of::<T>();
+    let mut table: Vec<T> = vec![Default::default(); count];
+    let table_buf = unsafe {
+        slice::from_raw_parts_mut(table.as_mut_ptr() as *mut u8, count * cmem::size_of::<T>())
+    };
+    source.read(offset as usize, table_buf)?;
+    Ok(table)
+}
+
+fn read_entry_header<T: Copy + Default>(table: &[u8], offset: u32) -> Result<T> {
+    let offset = offset as usize;
+    let header_size = cmem::size_of::<T>();
+    result_return_unless!(
+        (offset % 4 == 0) && (offset + header_size <= table.len()),
+        results::lib::fs::ResultInvalidRomFsEntry
+    );
+
+    unsafe { Ok(ptr::read_unaligned(table.as_ptr().add(offset) as *const T)) }
+}
+
+// Every entry is at least as big as its header, which gives an upper bound for
+// the length of any valid entry chain (longer ones must be cyclic)
+fn get_max_entry_count<T>(table: &[u8]) -> usize {
+    table.len() / cmem::size_of::<T>()
+}
+
+fn read_entry_name(table: &[u8], offset: u32, header_size: usize, name_len: u32) -> Result<&str> {
+    let name_offset = offset as usize + header_size;
+    let name_end_offset = name_offset + name_len as usize;
+    result_return_unless!(
+        name_end_offset <= table.len(),
+        results::lib::fs::ResultInvalidRomFsEntry
+    );
+
+    core::str::from_utf8(&table[name_offset..name_end_offset])
+        .map_err(|_| results::lib::fs::ResultInvalidRomFsEntry::make())
+}
+
+pub struct File {
+    source: mem::Shared<dyn Source>,
+    data_offset: usize,
+    data_size: usize,
+}
+
+impl File {
+    pub fn new(source: mem::Shared<dyn Source>, data_offset: usize, data_size: usize) -> Self {
+        Self {
+            source,
+            data_offset,
+            data_size,
+        }
+    }
+}
+
+impl fs::FileHandle for File {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if offset >= self.data_size {
+            return Ok(0);
+        }
+
+        let read_size = cmp::min(buf.len(), self.data_size - offset);
+        self.source
+            .get()
+            .read(self.data_offset + offset, &mut buf[..read_size])?;
+        Ok(read_size)
+    }
+
+    fn write(&mut self, _offset: usize, _buf: &[u8], _option: fs::FileWriteOption) -> Result<()> {
+        Err(results::fs::ResultWriteNotPermitted::make())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_size(&mut self, _size: usize) -> Result<()> {
+        Err(results::fs::ResultWriteNotPermitted::make())
+    }
+
+    fn get_size(&mut self) -> Result<usize> {
+        Ok(self.data_size)
+    }
+}
+
+pub struct Directory {
+    entries: Vec<fs::DirectoryEntry>,
+    entry_index: usize,
+}
+
+impl fs::DirectoryHandle for Directory {
+    fn read(&mut self, entries: &mut [fs::DirectoryEntry]) -> Result<usize> {
+        let read_count = cmp::min(entries.len(), self.entries.len() - self.entry_index);
+        entries[..read_count]
+            .copy_from_slice(&self.entries[self.entry_index..self.entry_index + read_count]);
+        self.entry_index += read_count;
+        Ok(read_count)
+    }
+
+    fn get_entry_count(&mut self) -> Result<u64> {
+        Ok(self.entries.len() as u64)
+    }
+}
+
+pub struct FileSystem {
+    source: mem::Shared<dyn Source>,
+    header: Header,
+    dir_hash_table: Vec<u32>,
+    dir_table: Vec<u8>,
+    file_hash_table: Vec<u32>,
+    file_table: Vec<u8>,
+}
+
+impl FileSystem {
+    pub fn new(source: mem::Shared<dyn Source>) -> Result<Self> {
+        let mut header: Header = Default::default();
+        let header_buf = unsafe {
+            slice::from_raw_parts_mut(&mut header as *mut _ as *mut u8, cmem::size_of::<Header>())
+        };
+        source.get().read(0, header_buf)?;
+        result_return_unless!(
+            header.header_size == cmem::size_of::<Header>() as u64,
+            results::lib::fs::ResultInvalidRomFsHeader
+        );
+
+        let dir_hash_table = read_table::<u32>(
+            source.get(),
+            header.dir_hash_table_offset,
+            header.dir_hash_table_size,
+        )?;
+        let dir_table =
+            read_table::<u8>(source.get(), header.dir_table_offset, header.dir_table_size)?;
+        let file_hash_table = read_table::<u32>(
+            source.get(),
+            header.file_hash_table_offset,
+            header.file_hash_table_size,
+        )?;
+        let file_table = read_table::<u8>(
+            source.get(),
+            header.file_table_offset,
+            header.file_table_size,
+        )?;
+        result_return_if!(
+            dir_hash_table.is_empty() || file_hash_table.is_empty(),
+            results::lib::fs::ResultInvalidRomFsHeader
+        );
+
+        Ok(Self {
+            source,
+            header,
+            dir_hash_table,
+            dir_table,
+            file_hash_table,
+            file_table,
+        })
+    }
+
+    pub fn get_header(&self) -> Header {
+        self.header
+    }
+
+    fn get_directory(&self, offset: u32) -> Result<(DirectoryEntryHeader, &str)> {
+        let entry = read_entry_header::<DirectoryEntryHeader>(&self.dir_table, offset)?;
+        let name = read_entry_name(
+            &self.dir_table,
+            offset,
+            cmem::size_of::<DirectoryEntryHeader>(),
+            entry.name_len,
+        )?;
+        Ok((entry, name))
+    }
+
+    fn get_file(&self, offset: u32) -> Result<(FileEntryHeader, &str)> {
+        let entry = read_entry_header::<FileEntryHeader>(&self.file_table, offset)?;
+        let name = read_entry_name(
+            &self.file_table,
+            offset,
+            cmem::size_of::<FileEntryHeader>(),
+            entry.name_len,
+        )?;
+        Ok((entry, name))
+    }
+
+    fn find_child_directory(&self, parent: u32, name: &str) -> Result<u32> {
+        let hash = calculate_path_hash(parent, name, self.dir_hash_table.len());
+        let mut cur_offset = self.dir_hash_table[hash];
+        let mut remaining_count = get_max_entry_count::<DirectoryEntryHeader>(&self.dir_table);
+        while cur_offset != EMPTY_ENTRY {
+            result_return_if!(
+                remaining_count == 0,
+                results::lib::fs::ResultInvalidRomFsEntry
+            );
+            remaining_count -= 1;
+
+            let (entry, entry_name) = self.get_directory(cur_offset)?;
+            if (entry.parent == parent) && (entry_name == name) {
+                return Ok(cur_offset);
+            }
+            cur_offset = entry.hash_sibling;
+        }
+        Err(results::fs::ResultPathNotFound::make())
+    }
+
+    fn find_child_file(&self, parent: u32, name: &str) -> Result<u32> {
+        let hash = calculate_path_hash(parent, name, self.file_hash_table.len());
+        let mut cur_offset = self.file_hash_table[hash];
+        let mut remaining_count = get_max_entry_count::<FileEntryHeader>(&self.file_table);
+        while cur_offset != EMPTY_ENTRY {
+            result_return_if!(
+                remaining_count == 0,
+                results::lib::fs::ResultInvalidRomFsEntry
+            );
+            remaining_count -= 1;
+
+            let (entry, entry_name) = self.get_file(cur_offset)?;
+            if (entry.parent == parent) && (entry_name == name) {
+                return Ok(cur_offset);
+            }
+            cur_offset = entry.hash_sibling;
+        }
+        Err(results::fs::ResultPathNotFound::make())
+    }
+
+    fn find_directory(&self, path: &str) -> Result<u32> {
+        let mut cur_offset = ROOT_DIRECTORY_OFFSET;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            cur_offset = self.find_child_directory(cur_offset, name)?;
+        }
+        Ok(cur_offset)
+    }
+
+    fn find_file(&self, path: &str) -> Result<u32> {
+        let path = path.trim_end_matches('/');
+        let (parent_path, name) = match path.rfind('/') {
+            Some(index) => (&path[..index], &path[index + 1..]),
+            None => ("", path),
+        };
+        result_return_if!(name.is_empty(), results::fs::ResultPathNotFound);
+
+        let parent = self.find_directory(parent_path)?;
+        self.find_child_file(parent, name)
+    }
+}
+
+impl fs::FileSystem for FileSystem {
+    fn create_file(
+        &mut self,
+        _path: &str,
+        _attribute: fs::FileAttribute,
+        _size: usize,
+    ) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn delete_file(&mut self, _path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn create_directory(&mut self, _path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn delete_directory(&mut self, _path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn delete_directory_recursively(&mut self, _path: &str) -> Result<()> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn get_entry_type(&mut self, path: &str) -> Result<fs::DirectoryEntryType> {
+        if self.find_file(path).is_ok() {
+            return Ok(fs::DirectoryEntryType::File);
+        }
+
+        self.find_directory(path)?;
+        Ok(fs::DirectoryEntryType::Directory)
+    }
+
+    fn open_file(
+        &mut self,
+        path: &str,
+        mode: fs::FileOpenMode,
+    ) -> Result<mem::Shared<dyn fs::FileHandle>> {
+        result_return_if!(
+            mode.contains(fs::FileOpenMode::Write()) || mode.contains(fs::FileOpenMode::Append()),
+            results::fs::ResultWriteNotPermitted
+        );
+
+        let (entry, _) = self.get_file(self.find_file(path)?)?;
+        // File data must be entirely inside the source, so that reads never overflow
+        let source_size = self.source.get().get_size()? as u64;
+        match self.header.file_data_offset.checked_add(entry.data_offset) {
+            Some(data_offset)
+                if (data_offset <= source_size)
+                    && (entry.data_size <= (source_size - data_offset)) =>
+            {
+                Ok(mem::Shared::new(File::new(
+                    self.source.clone(),
+                    data_offset as usize,
+                    entry.data_size as usize,
+                )))
+            }
+            _ => Err(results::lib::fs::ResultInvalidRomFsEntry::make()),
+        }
+    }
+
+    fn open_directory(
+        &mut self,
+        path: &str,
+        mode: fs::DirectoryOpenMode,
+    ) -> Result<mem::Shared<dyn fs::DirectoryHandle>> {
+        let (dir_entry, _) = self.get_directory(self.find_directory(path)?)?;
+
+        let mut entries: Vec<fs::DirectoryEntry> = Vec::new();
+        if mode.contains(fs::DirectoryOpenMode::ReadDirectories()) {
+            let mut cur_offset = dir_entry.child_dir;
+            let mut remaining_count = get_max_entry_count::<DirectoryEntryHeader>(&self.dir_table);
+            while cur_offset != EMPTY_ENTRY {
+                result_return_if!(
+                    remaining_count == 0,
+                    results::lib::fs::ResultInvalidRomFsEntry
+                );
+                remaining_count -= 1;
+
+                let (child_entry, child_name) = self.get_directory(cur_offset)?;
+                let mut entry: fs::DirectoryEntry = Default::default();
+                entry.name.set_str(child_name)?;
+                entry.entry_type = fs::DirectoryEntryType::Directory;
+                entries.push(entry);
+                cur_offset = child_entry.sibling;
+            }
+        }
+        if mode.contains(fs::DirectoryOpenMode::ReadFiles()) {
+            let mut cur_offset = dir_entry.child_file;
+            let mut remaining_count = get_max_entry_count::<FileEntryHeader>(&self.file_table);
+            while cur_offset != EMPTY_ENTRY {
+                result_return_if!(
+                    remaining_count == 0,
+                    results::lib::fs::ResultInvalidRomFsEntry
+                );
+                remaining_count -= 1;
+
+                let (child_entry, child_name) = self.get_file(cur_offset)?;
+                let mut entry: fs::DirectoryEntry = Default::default();
+                entry.name.set_str(child_name)?;
+                entry.entry_type = fs::DirectoryEntryType::File;
+                if !mode.contains(fs::DirectoryOpenMode::NoFileSize()) {
+                    entry.file_size = child_entry.data_size as usize;
+                }
+                entries.push(entry);
+                cur_offset = child_entry.sibling;
+            }
+        }
+
+        Ok(mem::Shared::new(Directory {
+            entries,
+            entry_index: 0,
+        }))
+    }
+}
+
+pub fn open_self() -> Result<FileSystem> {
+    let nro = nro::open_self()?;
+    match nro.open_romfs() {
+        Err(rc)
+            if results::lib::nro::ResultAssetsNotFound::matches(rc)
+                || results::lib::nro::ResultAssetNotFound::matches(rc) =>
+        {
+            Err(results::lib::fs::ResultNroRomFsNotFound::make())
+        }
+        romfs => romfs,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Directory 0 is the root, and parents are given as indices
+    struct TestDirectory {
+        parent: usize,
+        name: &'static str,
+    }
+
+    struct TestFile {
+        parent: usize,
+        name: &'static str,
+        data: &'static [u8],
+    }
+
+    const DIRECTORIES: &[TestDirectory] = &[
+        TestDirectory {
+            parent: 0,
+            name: "",
+        },
+        TestDirectory {
+            parent: 0,
+            name: "dir",
+        },
+        TestDirectory {
+            parent: 1,
+            name: "subdir",
+        },
+    ];
+
+    const FILES: &[TestFile] = &[
+        TestFile {
+            parent: 0,
+            name: "root.txt",
+            data: b"root file",
+        },
+        TestFile {
+            parent: 1,
+            name: "a.bin",
+            data: b"\x00\x01\x02\x03",
+        },
+        TestFile {
+            parent: 1,
+            name: "b.bin",
+            data: b"",
+        },
+        TestFile {
+            parent: 2,
+            name: "nested.txt",
+            data: b"nested file contents",
+        },
+    ];
+
+    const DIRECTORY_ENTRY_SIZE: usize = cmem::size_of::<DirectoryEntryHeader>();
+    const FILE_ENTRY_SIZE: usize = cmem::size_of::<FileEntryHeader>();
+
+    fn align_up(size: usize) -> usize {
+        (size + 3) & !3
+    }
+
+    fn push_u32(buf: &mut Vec<u8>, value: u32) {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_u64(buf: &mut Vec<u8>, value: u64) {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_name(buf: &mut Vec<u8>, name: &str) {
+        buf.extend_from_slice(name.as_bytes());
+        buf.resize(align_up(buf.len()), 0);
+    }
+
+    fn get_entry_offsets(name_lens: &[usize], entry_size: usize) -> Vec<u32> {
+        let mut offsets: Vec<u32> = Vec::new();
+        let mut offset = 0;
+        for name_len in name_lens {
+            offsets.push(offset as u32);
+            offset += entry_size + align_up(*name_len);
+        }
+        offsets
+    }
+
+    fn find_next(indices: &[usize], after: usize, offsets: &[u32]) -> u32 {
+        match indices.iter().find(|&&index| index > after) {
+            Some(&index) => offsets[index],
+            None => EMPTY_ENTRY,
+        }
+    }
+
+    fn find_first(indices: &[usize], offsets: &[u32]) -> u32 {
+        match indices.first() {
+            Some(&index) => offsets[index],
+            None => EMPTY_ENTRY,
+        }
+    }
+
+    // Builds an image laid out like the official tools do: header, file data and
+    // then the hash and entry tables
+    // Also used by the NRO tests, as the embedded RomFS
+    pub(crate) fn build_romfs(hash_table_count: usize) -> Vec<u8> {
+        let dir_offsets = get_entry_offsets(
+            &DIRECTORIES
+                .iter()
+                .map(|dir| dir.name.len())
+                .collect::<Vec<_>>(),
+            DIRECTORY_ENTRY_SIZE,
+        );
+        let file_offsets = get_entry_offsets(
+            &FILES.iter().map(|file| file.name.len()).collect::<Vec<_>>(),
+            FILE_ENTRY_SIZE,
+        );
+        let get_child_dirs = |parent: usize| -> Vec<usize> {
+            (1..DIRECTORIES.len())
+                .filter(|&index| DIRECTORIES[index].parent == parent)
+                .collect()
+        };
+        let get_child_files = |parent: usize| -> Vec<usize> {
+            (0..FILES.len())
+                .filter(|&index| FILES[index].parent == parent)
+                .collect()
+        };
+
+        let mut file_data: Vec<u8> = Vec::new();
+        let mut file_data_offsets: Vec<u64> = Vec::new();
+        for file in FILES {
+            file_data_offsets.push(file_data.len() as u64);
+            file_data.extend_from_slice(file.data);
+            file_data.resize(align_up(file_data.len()), 0);
+        }
+
+        let mut dir_hash_table = vec![EMPTY_ENTRY; hash_table_count];
+        let mut dir_table: Vec<u8> = Vec::new();
+        for (i, dir) in DIRECTORIES.iter().enumerate() {
+            let parent_offset = dir_offsets[dir.parent];
+            let hash = calculate_path_hash(parent_offset, dir.name, hash_table_count);
+            let sibling = match i {
+                0 => EMPTY_ENTRY,
+                _ => find_next(&get_child_dirs(dir.parent), i, &dir_offsets),
+            };
+            push_u32(&mut dir_table, parent_offset);
+            push_u32(&mut dir_table, sibling);
+            push_u32(&mut dir_table, find_first(&get_child_dirs(i), &dir_offsets));
+            push_u32(
+                &mut dir_table,
+                find_first(&get_child_files(i), &file_offsets),
+            );
+            push_u32(&mut dir_table, dir_hash_table[hash]);
+            push_u32(&mut dir_table, dir.name.len() as u32);
+            push_name(&mut dir_table, dir.name);
+            dir_hash_table[hash] = dir_offsets[i];
+        }
+
+        let mut file_hash_table = vec![EMPTY_ENTRY; hash_table_count];
+        let mut file_table: Vec<u8> = Vec::new();
+        for (i, file) in FILES.iter().enumerate() {
+            let parent_offset = dir_offsets[file.parent];
+            let hash = calculate_path_hash(parent_offset, file.name, hash_table_count);
+            push_u32(&mut file_table, parent_offset);
+            push_u32(
+                &mut file_table,
+                find_next(&get_child_files(file.parent), i, &file_offsets),
+            );
+            push_u64(&mut file_table, file_data_offsets[i]);
+            push_u64(&mut file_table, file.data.len() as u64);
+            push_u32(&mut file_table, file_hash_table[hash]);
+            push_u32(&mut file_table, file.name.len() as u32);
+            push_name(&mut file_table, file.name);
+            file_hash_table[hash] = file_offsets[i];
+        }
+
+        let header_size = cmem::size_of::<Header>() as u64;
+        let hash_table_size = (hash_table_count * cmem::size_of::<u32>()) as u64;
+        let file_data_offset = header_size;
+        let dir_hash_table_offset = file_data_offset + file_data.len() as u64;
+        let dir_table_offset = dir_hash_table_offset + hash_table_size;
+        let file_hash_table_offset = dir_table_offset + dir_table.len() as u64;
+        let file_table_offset = file_hash_table_offset + hash_table_size;
+
+        let mut image: Vec<u8> = Vec::new();
+        for value in &[
+            header_size,
+            dir_hash_table_offset,
+            hash_table_size,
+            dir_table_offset,
+            dir_table.len() as u64,
+            file_hash_table_offset,
+            hash_table_size,
+            file_table_offset,
+            file_table.len() as u64,
+            file_data_offset,
+        ] {
+            push_u64(&mut image, *value);
+        }
+        image.extend_from_slice(&file_data);
+        for entry in dir_hash_table {
+            push_u32(&mut image, entry);
+        }
+        image.extend_from_slice(&dir_table);
+        for entry in file_hash_table {
+            push_u32(&mut image, entry);
+        }
+        image.extend_from_slice(&file_table);
+        image
+    }
+
+    fn open_romfs(image: Vec<u8>) -> Result<FileSystem> {
+        FileSystem::new(mem::Shared::new(image))
+    }
+
+    fn read_file(romfs: &mut FileSystem, path: &str) -> Result<Vec<u8>> {
+        let file = fs::FileSystem::open_file(romfs, path, fs::FileOpenMode::Read())?;
+        let mut data = vec![0u8; file.get().get_size()?];
+        let read_size = file.get().read(0, &mut data)?;
+        assert_eq!(read_size, data.len());
+        Ok(data)
+    }
+
+    #[test]
+    fn read_files() {
+        for hash_table_count in &[1, 3, 7] {
+            let mut romfs = open_romfs(build_romfs(*hash_table_count)).unwrap();
+            assert_eq!(read_file(&mut romfs, "/root.txt").unwrap(), b"root file");
+            assert_eq!(
+                read_file(&mut romfs, "/dir/a.bin").unwrap(),
+                b"\x00\x01\x02\x03"
+            );
+            assert!(read_file(&mut romfs, "/dir/b.bin").unwrap().is_empty());
+            assert_eq!(
+                read_file(&mut romfs, "/dir/subdir/nested.txt").unwrap(),
+                b"nested file contents"
+            );
+
+            assert!(results::fs::ResultPathNotFound::matches(
+                read_file(&mut romfs, "/dir/missing.txt").unwrap_err()
+            ));
+            assert!(results::fs::ResultPathNotFound::matches(
+                read_file(&mut romfs, "/subdir/nested.txt").unwrap_err()
+            ));
+        }
+    }
+
+    #[test]
+    fn get_entry_types() {
+        let mut romfs = open_romfs(build_romfs(3)).unwrap();
+        assert_eq!(
+            fs::FileSystem::get_entry_type(&mut romfs, "/").unwrap(),
+            fs::DirectoryEntryType::Directory
+        );
+        assert_eq!(
+            fs::FileSystem::get_entry_type(&mut romfs, "/dir/subdir").unwrap(),
+            fs::DirectoryEntryType::Directory
+        );
+        assert_eq!(
+            fs::FileSystem::get_entry_type(&mut romfs, "/dir/a.bin").unwrap(),
+            fs::DirectoryEntryType::File
+        );
+        assert!(fs::FileSystem::get_entry_type(&mut romfs, "/missing").is_err());
+    }
+
+    #[test]
+    fn list_directories() {
+        let mut romfs = open_romfs(build_romfs(3)).unwrap();
+        let dir = fs::FileSystem::open_directory(
+            &mut romfs,
+            "/dir",
+            fs::DirectoryOpenMode::ReadDirectories() | fs::DirectoryOpenMode::ReadFiles(),
+        )
+        .unwrap();
+        assert_eq!(dir.get().get_entry_count().unwrap(), 3);
+
+        let mut entries: Vec<fs::DirectoryEntry> = vec![Default::default(); 4];
+        assert_eq!(dir.get().read(&mut entries).unwrap(), 3);
+        assert_eq!(entries[0].name.get_str().unwrap(), "subdir");
+        assert_eq!(entries[0].entry_type, fs::DirectoryEntryType::Directory);
+        assert_eq!(entries[1].name.get_str().unwrap(), "a.bin");
+        assert_eq!(entries[1].file_size, 4);
+        assert_eq!(entries[2].name.get_str().unwrap(), "b.bin");
+        assert_eq!(entries[2].entry_type, fs::DirectoryEntryType::File);
+    }
+
+    #[test]
+    fn reject_oversized_tables() {
+        let mut image = build_romfs(3);
+        // Directory table size
+        image[0x20..0x28].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(results::lib::fs::ResultInvalidRomFsHeader::matches(
+            open_romfs(image).err().unwrap()
+        ));
+
+        let mut image = build_romfs(3);
+        // File hash table offset
+        image[0x28..0x30].copy_from_slice(&0x10000000u64.to_le_bytes());
+        assert!(results::lib::fs::ResultInvalidRomFsHeader::matches(
+            open_romfs(image).err().unwrap()
+        ));
+    }
+
+    #[test]
+    fn reject_cyclic_entry_chains() {
+        // With a single hash bucket every entry is chained, so making the last
+        // file inserted point to itself makes a cycle any failed lookup walks into
+        let mut image = build_romfs(1);
+        let mut file_table_offset_bytes = [0u8; 8];
+        file_table_offset_bytes.copy_from_slice(&image[0x38..0x40]);
+        let file_table_offset = u64::from_le_bytes(file_table_offset_bytes) as usize;
+        let last_file_offset = file_table_offset
+            + (0..FILES.len() - 1)
+                .map(|index| FILE_ENTRY_SIZE + align_up(FILES[index].name.len()))
+                .sum::<usize>();
+        let hash_sibling_offset = last_file_offset + 0x18;
+        let self_offset = (last_file_offset - file_table_offset) as u32;
+        image[hash_sibling_offset..hash_sibling_offset + 4]
+            .copy_from_slice(&self_offset.to_le_bytes());
+
+        let mut romfs = open_romfs(image).unwrap();
+        assert!(results::lib::fs::ResultInvalidRomFsEntry::matches(
+            read_file(&mut romfs, "/missing.txt").unwrap_err()
+        ));
+    }
+
+    #[test]
+    fn reject_out_of_bounds_files() {
+        let image = build_romfs(3);
+        let image_size = image.len() as u64;
+        let mut file_table_offset_bytes = [0u8; 8];
+        file_table_offset_bytes.copy_from_slice(&image[0x38..0x40]);
+        // "/root.txt" is the first file entry
+        let file_entry_offset = u64::from_le_bytes(file_table_offset_bytes) as usize;
+
+        for &(data_offset, data_size) in &[
+            (u64::MAX, 1),
+            (0, u64::MAX),
+            (image_size, 1),
+            (image_size - 0x50, 0x10),
+        ] {
+            let mut image = image.clone();
+            image[file_entry_offset + 0x8..file_entry_offset + 0x10]
+                .copy_from_slice(&data_offset.to_le_bytes());
+            image[file_entry_offset + 0x10..file_entry_offset + 0x18]
+                .copy_from_slice(&data_size.to_le_bytes());
+
+            let mut romfs = open_romfs(image).unwrap();
+            assert!(results::lib::fs::ResultInvalidRomFsEntry::matches(
+                read_file(&mut romfs, "/root.txt").unwrap_err()
+            ));
+            assert_eq!(
+                read_file(&mut romfs, "/dir/a.bin").unwrap(),
+                b"\x00\x01\x02\x03"
+            );
+        }
+
+        // File data offset from the header
+        let mut image = image;
+        image[0x48..0x50].copy_from_slice(&u64::MAX.to_le_bytes());
+        let mut romfs = open_romfs(image).unwrap();
+        assert!(results::lib::fs::ResultInvalidRomFsEntry::matches(
+            read_file(&mut romfs, "/root.txt").unwrap_err()
+        ));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{
+    fs,
+    ipc::cmif::sf,
+    mem,
+    result::*,
+    results,
+    service::cmif::{
+        fspsrv,
+        fspsrv::{IDirectory, IFile, IFileSystem},
+    },
+};
+use alloc::string::String;
+use core::cmp;
+
+// Server objects exposing fs backends through fsp-srv's interfaces, the inverse
+// of the adapters in fs::fsp
+
+// Backends get normalized paths, never going above their root
+fn read_path(path_buf: &sf::InPointerBuffer) -> Result<String> {
+    fs::path::normalize(&path_buf.get_string())
+}
+
+pub struct File {
+    session: sf::Session,
+    file: mem::Shared<dyn fs::FileHandle>,
+}
+
+impl File {
+    pub fn new(file: mem::Shared<dyn fs::FileHandle>) -> Self {
+        Self {
+            session: sf::Session::new(),
+            file,
+        }
+    }
+}
+
+impl IFile for File {
+    fn read(
+        &mut self,
+        _option: fspsrv::FileReadOption,
+        offset: usize,
+        size: usize,
+        buf: sf::OutNonSecureMapAliasBuffer,
+    ) -> Result<usize> {
+        let read_size = cmp::min(size, buf.size);
+        self.file
+            .get()
+            .read(offset, &mut buf.get_mut_slice::<u8>()[..read_size])
+    }
+
+    fn write(
+        &mut self,
+        option: fspsrv::FileWriteOption,
+        offset: usize,
+        size: usize,
+        buf: sf::InNonSecureMapAliasBuffer,
+    ) -> Result<()> {
+        let write_size = cmp::min(size, buf.size);
+        self.file
+            .get()
+            .write(offset, &buf.get_slice::<u8>()[..write_size], option)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.get().flush()
+    }
+
+    fn set_size(&mut self, size: usize) -> Result<()> {
+        self.file.get().set_size(size)
+    }
+
+    fn get_size(&mut self) -> Result<usize> {
+        self.file.get().get_size()
+    }
+
+    fn operate_range(
+        &mut self,
+        _operation_id: fspsrv::OperationId,
+        _offset: usize,
+        _size: usize,
+    ) -> Result<fspsrv::FileQueryRangeInfo> {
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+}
+
+impl sf::IObject for File {
+    fn get_session(&mut self) -> &mut sf::Session {
+        &mut self.session
+    }
+
+    fn get_command_table(&self) -> sf::CommandMetadataTable {
+        self.get_interface_command_table()
+    }
+}
+
+pub struct Directory {
+    session: sf::Session,
+    dir: mem::Shared<dyn fs::DirectoryHandle>,
+}
+
+impl Directory {
+    pub fn new(dir: mem::Shared<dyn fs::DirectoryHandle>) -> Self {
+        Self {
+            session: sf::Session::new(),
+            dir,
+        }
+    }
+}
+
+impl IDirectory for Directory {
+    fn read(&mut self, out_entries: sf::OutMapAliasBuffer) -> Result<u64> {
+        let read_count = self
+            .dir
+            .get()
+            .read(out_entries.get_mut_slice::<fs::DirectoryEntry>())?;
+        Ok(read_count as u64)
+    }
+
+    fn get_entry_count(&mut self) -> Result<u64> {
+        self.dir.get().get_entry_count()
+    }
+}
+
+impl sf::IObject for Directory {
+    fn get_session(&mut self) -> &mut sf::Session {
+        &mut self.session
+    }
+
+    fn get_command_table(&self) -> sf::CommandMetadataTable {
+        self.get_interface_command_table()
+    }
+}
+
+pub struct FileSystem {
+    session: sf::Session,
+    fs: mem::Shared<dyn fs::FileSystem>,
+}
+
+impl FileSystem {
+    pub fn new(fs: mem::Shared<dyn fs::FileSystem>) -> Self {
+        Self {
+            session: sf::Session::new(),
+            fs,
+        }
+    }
+}
+
+impl IFileSystem for FileSystem {
+    fn create_file(
+        &mut self,
+        attribute: fspsrv::FileAttribute,
+        size: usize,
+        path_buf: sf::InPointerBuffer,
+    ) -> Result<()> {
+        self.fs
+            .get()
+            .create_file(&read_path(&path_buf)?, attribute, size)
+    }
+
+    fn delete_file(&mut self, path_buf: sf::InPointerBuffer) -> Result<()> {
+        self.fs.get().delete_file(&read_path(&path_buf)?)
+    }
+
+    fn create_directory(&mut self, path_buf: sf::InPointerBuffer) -> Result<()> {
+        self.fs.get().create_directory(&read_path(&path_buf)?)
+    }
+
+    fn delete_directory(&mut self, path_buf: sf::InPointerBuffer) -> Result<()> {
+        self.fs.get().delete_directory(&read_path(&path_buf)?)
+    }
+
+    fn delete_directory_recursively(&mut self, path_buf: sf::InPointerBuffer) -> Result<()> {
+        self.fs
+            .get()
+            .delete_directory_recursively(&read_path(&path_buf)?)
+    }
+
+    fn rename_file(
+        &mut self,
+        old_path_buf: sf::InPointerBuffer,
+        new_path_buf: sf::InPointerBuffer,
+    ) -> Result<()> {
+        self.fs
+            .get()
+            .rename_file(&read_path(&old_path_buf)?, &read_path(&new_path_buf)?)
+    }
+
+    fn rename_directory(
+        &mut self,
+        old_path_buf: sf::InPointerBuffer,
+        new_path_buf: sf::InPointerBuffer,
+    ) -> Result<()> {
+        self.fs
+            .get()
+            .rename_directory(&read_path(&old_path_buf)?, &read_path(&new_path_buf)?)
+    }
+
+    fn get_entry_type(&mut self, path_buf: sf::InPointerBuffer) -> Result<fs::DirectoryEntryType> {
+        self.fs.get().get_entry_type(&read_path(&path_buf)?)
+    }
+
+    fn open_file(
+        &mut self,
+        mode: fspsrv::FileOpenMode,
+        path_buf: sf::InPointerBuffer,
+    ) -> Result<mem::Shared<dyn sf::IObject>> {
+        let file = self.fs.get().open_file(&read_path(&path_buf)?, mode)?;
+        Ok(mem::Shared::new(File::new(file)))
+    }
+
+    fn open_directory(
+        &mut self,
+        mode: fspsrv::DirectoryOpenMode,
+        path_buf: sf::InPointerBuffer,
+    ) -> Result<mem::Shared<dyn sf::IObject>> {
+        let dir = self.fs.get().open_directory(&read_path(&path_buf)?, mode)?;
+        Ok(mem::Shared::new(Directory::new(dir)))
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.fs.get().commit()
+    }
+
+    fn get_free_space_size(&mut self, path_buf: sf::InPointerBuffer) -> Result<usize> {
+        self.fs.get().get_free_space_size(&read_path(&path_buf)?)
+    }
+
+    fn get_total_space_size(&mut self, path_buf: sf::InPointerBuffer) -> Result<usize> {
+        self.fs.get().get_total_space_size(&read_path(&path_buf)?)
+    }
+
+    fn clean_directory_recursively(&mut self, path_buf: sf::InPointerBuffer) -> Result<()> {
+        self.fs
+            .get()
+            .clean_directory_recursively(&read_path(&path_buf)?)
+    }
+
+    fn get_file_time_stamp_raw(
+        &mut self,
+        path_buf: sf::InPointerBuffer,
+    ) -> Result<fs::FileTimeStampRaw> {
+        self.fs
+            .get()
+            .get_file_time_stamp_raw(&read_path(&path_buf)?)
+    }
+}
+
+impl sf::IObject for FileSystem {
+    fn get_session(&mut self) -> &mut sf::Session {
+        &mut self.session
+    }
+
+    fn get_command_table(&self) -> sf::CommandMetadataTable {
+        self.get_interface_command_table()
+    }
+}
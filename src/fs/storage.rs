@@ -0,0 +1,52 @@
+use crate::{fs, mem, result::*, results};
+
+// View over a region of another storage, mostly useful to access a single
+// partition of a raw disk image
+
+pub struct SubStorage {
+    storage: mem::Shared<dyn fs::StorageHandle>,
+    offset: usize,
+    size: usize,
+}
+
+impl SubStorage {
+    pub fn new(storage: mem::Shared<dyn fs::StorageHandle>, offset: usize, size: usize) -> Self {
+        Self {
+            storage,
+            offset,
+            size,
+        }
+    }
+
+    fn check_range(&self, offset: usize, size: usize) -> Result<()> {
+        match offset.checked_add(size) {
+            Some(end_offset) if end_offset <= self.size => Ok(()),
+            _ => Err(results::lib::fs::ResultSourceOutOfBounds::make()),
+        }
+    }
+}
+
+impl fs::StorageHandle for SubStorage {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        self.check_range(offset, buf.len())?;
+        self.storage.get().read(self.offset + offset, buf)
+    }
+
+    fn write(&mut self, offset: usize, buf: &[u8]) -> Result<()> {
+        self.check_range(offset, buf.len())?;
+        self.storage.get().write(self.offset + offset, buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.storage.get().flush()
+    }
+
+    fn set_size(&mut self, _size: usize) -> Result<()> {
+        // The region is fixed by its parent storage
+        Err(results::fs::ResultUnsupportedOperation::make())
+    }
+
+    fn get_size(&mut self) -> Result<usize> {
+        Ok(self.size)
+    }
+}
@@ -1,4 +1,5 @@
-use crate::version;
+use crate::{sync, version};
+use core::{ptr, slice, str};
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Derivative)]
 #[derivative(Default)]
@@ -77,3 +78,26 @@ impl Version {
         version::Version::new(self.get_major(), self.get_minor(), self.get_micro())
     }
 }
+
+static mut G_ARGV: sync::Locked<*const u8> = sync::Locked::new(false, ptr::null());
+
+pub(crate) fn set_argv(argv: *const u8) {
+    unsafe {
+        G_ARGV.set(argv);
+    }
+}
+
+pub fn get_argv() -> Option<&'static str> {
+    unsafe {
+        let argv = *G_ARGV.get();
+        if argv.is_null() {
+            return None;
+        }
+
+        let mut argv_len: usize = 0;
+        while *argv.add(argv_len) != 0 {
+            argv_len += 1;
+        }
+        str::from_utf8(slice::from_raw_parts(argv, argv_len)).ok()
+    }
+}
@@ -0,0 +1,477 @@
+use crate::{result::*, results};
+use alloc::{string::String, vec::Vec};
+use core::{cmp, str};
+
+// Minimal no_std replacements for std::io's traits
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SeekFrom {
+    Start(u64),
+    Current(i64),
+    End(i64),
+}
+
+const DEFAULT_BUFFER_SIZE: usize = 0x1000;
+
+pub trait Read {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let mut read_offset = 0;
+        while read_offset < buf.len() {
+            let read_size = self.read(&mut buf[read_offset..])?;
+            result_return_if!(read_size == 0, results::lib::io::ResultUnexpectedEof);
+            read_offset += read_size;
+        }
+        Ok(())
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let start_len = buf.len();
+        let mut chunk = vec![0u8; DEFAULT_BUFFER_SIZE];
+        loop {
+            let read_size = self.read(&mut chunk)?;
+            if read_size == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..read_size]);
+        }
+        Ok(buf.len() - start_len)
+    }
+
+    fn read_to_string(&mut self, string: &mut String) -> Result<usize> {
+        let mut buf: Vec<u8> = Vec::new();
+        let read_size = self.read_to_end(&mut buf)?;
+        match str::from_utf8(&buf) {
+            Ok(buf_str) => {
+                string.push_str(buf_str);
+                Ok(read_size)
+            }
+            Err(_) => Err(results::lib::io::ResultInvalidUtf8::make()),
+        }
+    }
+}
+
+pub trait Write {
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+    fn flush(&mut self) -> Result<()>;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        let mut write_offset = 0;
+        while write_offset < buf.len() {
+            let write_size = self.write(&buf[write_offset..])?;
+            result_return_if!(write_size == 0, results::lib::io::ResultWriteZero);
+            write_offset += write_size;
+        }
+        Ok(())
+    }
+}
+
+pub trait Seek {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+    fn stream_position(&mut self) -> Result<u64> {
+        self.seek(SeekFrom::Current(0))
+    }
+}
+
+pub fn compute_seek_offset(cur_offset: u64, end_offset: u64, pos: SeekFrom) -> Result<u64> {
+    let (base_offset, relative_offset) = match pos {
+        SeekFrom::Start(offset) => return Ok(offset),
+        SeekFrom::Current(offset) => (cur_offset, offset),
+        SeekFrom::End(offset) => (end_offset, offset),
+    };
+
+    let new_offset = if relative_offset >= 0 {
+        base_offset.checked_add(relative_offset as u64)
+    } else {
+        base_offset.checked_sub(relative_offset.wrapping_neg() as u64)
+    };
+    match new_offset {
+        Some(new_offset) => Ok(new_offset),
+        None => Err(results::lib::io::ResultInvalidSeek::make()),
+    }
+}
+
+pub struct BufReader<R: Read> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+}
+
+impl<R: Read> BufReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_BUFFER_SIZE, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; capacity],
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    pub fn fill_buf(&mut self) -> Result<&[u8]> {
+        if self.pos >= self.filled {
+            self.filled = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    pub fn consume(&mut self, amount: usize) {
+        self.pos = cmp::min(self.pos + amount, self.filled);
+    }
+
+    pub fn read_until(&mut self, delimiter: u8, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut read_size = 0;
+        loop {
+            let (found, used) = {
+                let available = self.fill_buf()?;
+                match available.iter().position(|&byte| byte == delimiter) {
+                    Some(index) => {
+                        buf.extend_from_slice(&available[..=index]);
+                        (true, index + 1)
+                    }
+                    None => {
+                        buf.extend_from_slice(available);
+                        (available.is_empty(), available.len())
+                    }
+                }
+            };
+            self.consume(used);
+            read_size += used;
+            if found {
+                return Ok(read_size);
+            }
+        }
+    }
+
+    pub fn read_line(&mut self, string: &mut String) -> Result<usize> {
+        let mut buf: Vec<u8> = Vec::new();
+        let read_size = self.read_until(b'\n', &mut buf)?;
+        match str::from_utf8(&buf) {
+            Ok(line) => {
+                string.push_str(line);
+                Ok(read_size)
+            }
+            Err(_) => Err(results::lib::io::ResultInvalidUtf8::make()),
+        }
+    }
+}
+
+impl<R: Read> Read for BufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // Skip our buffer entirely for big reads when it's empty
+        if (self.pos >= self.filled) && (buf.len() >= self.buf.len()) {
+            return self.inner.read(buf);
+        }
+
+        let available = self.fill_buf()?;
+        let read_size = cmp::min(buf.len(), available.len());
+        buf[..read_size].copy_from_slice(&available[..read_size]);
+        self.consume(read_size);
+        Ok(read_size)
+    }
+}
+
+impl<R: Read + Seek> Seek for BufReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        // The inner reader is ahead of us by the amount of buffered data
+        let buffered_size = (self.filled - self.pos) as i64;
+        let new_offset = match pos {
+            SeekFrom::Current(offset) => {
+                self.inner.seek(SeekFrom::Current(offset - buffered_size))?
+            }
+            _ => self.inner.seek(pos)?,
+        };
+        self.pos = 0;
+        self.filled = 0;
+        Ok(new_offset)
+    }
+}
+
+pub struct BufWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    capacity: usize,
+}
+
+impl<W: Write> BufWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_BUFFER_SIZE, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    fn flush_buf(&mut self) -> Result<()> {
+        if !self.buf.is_empty() {
+            self.inner.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BufWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if (self.buf.len() + buf.len()) > self.capacity {
+            self.flush_buf()?;
+        }
+
+        // Big writes go straight to the inner writer
+        if buf.len() >= self.capacity {
+            self.inner.write(buf)
+        } else {
+            self.buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Seek> Seek for BufWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.flush_buf()?;
+        self.inner.seek(pos)
+    }
+}
+
+impl<W: Write> Drop for BufWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush_buf();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MemoryStream<'a> {
+        data: &'a mut Vec<u8>,
+        offset: usize,
+        read_sizes: Vec<usize>,
+        write_sizes: Vec<usize>,
+        flush_count: usize,
+    }
+
+    impl<'a> MemoryStream<'a> {
+        fn new(data: &'a mut Vec<u8>) -> Self {
+            Self {
+                data,
+                offset: 0,
+                read_sizes: Vec::new(),
+                write_sizes: Vec::new(),
+                flush_count: 0,
+            }
+        }
+    }
+
+    impl<'a> Read for MemoryStream<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let offset = cmp::min(self.offset, self.data.len());
+            let read_size = cmp::min(buf.len(), self.data.len() - offset);
+            buf[..read_size].copy_from_slice(&self.data[offset..offset + read_size]);
+            self.offset = offset + read_size;
+            self.read_sizes.push(read_size);
+            Ok(read_size)
+        }
+    }
+
+    impl<'a> Write for MemoryStream<'a> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let end_offset = self.offset + buf.len();
+            if end_offset > self.data.len() {
+                self.data.resize(end_offset, 0);
+            }
+            self.data[self.offset..end_offset].copy_from_slice(buf);
+            self.offset = end_offset;
+            self.write_sizes.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.flush_count += 1;
+            Ok(())
+        }
+    }
+
+    impl<'a> Seek for MemoryStream<'a> {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            self.offset =
+                compute_seek_offset(self.offset as u64, self.data.len() as u64, pos)? as usize;
+            Ok(self.offset as u64)
+        }
+    }
+
+    fn read_bytes<R: Read>(reader: &mut R, size: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; size];
+        reader.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn compute_seek_offsets() {
+        assert_eq!(compute_seek_offset(10, 20, SeekFrom::Start(5)).unwrap(), 5);
+        assert_eq!(
+            compute_seek_offset(10, 20, SeekFrom::Current(3)).unwrap(),
+            13
+        );
+        assert_eq!(
+            compute_seek_offset(10, 20, SeekFrom::Current(-10)).unwrap(),
+            0
+        );
+        assert_eq!(compute_seek_offset(10, 20, SeekFrom::End(0)).unwrap(), 20);
+        assert_eq!(compute_seek_offset(10, 20, SeekFrom::End(-4)).unwrap(), 16);
+        assert_eq!(
+            compute_seek_offset(u64::MAX, 0, SeekFrom::Current(i64::MIN)).unwrap(),
+            i64::MAX as u64
+        );
+
+        for &(cur_offset, end_offset, pos) in &[
+            (10, 20, SeekFrom::Current(-11)),
+            (10, 20, SeekFrom::End(-21)),
+            (u64::MAX, 0, SeekFrom::Current(1)),
+            (0, u64::MAX, SeekFrom::End(i64::MAX)),
+            (0, 0, SeekFrom::Current(i64::MIN)),
+        ] {
+            assert!(results::lib::io::ResultInvalidSeek::matches(
+                compute_seek_offset(cur_offset, end_offset, pos).unwrap_err()
+            ));
+        }
+    }
+
+    #[test]
+    fn buf_reader_lines() {
+        let mut data = b"first line\nsecond\n\nlast".to_vec();
+        let mut reader = BufReader::with_capacity(4, MemoryStream::new(&mut data));
+
+        assert_eq!(reader.fill_buf().unwrap(), b"firs");
+        reader.consume(2);
+        // Data left in the buffer is returned without reading again
+        assert_eq!(reader.fill_buf().unwrap(), b"rs");
+        assert_eq!(reader.get_ref().read_sizes, vec![4]);
+
+        let mut line = String::new();
+        assert_eq!(reader.read_line(&mut line).unwrap(), 9);
+        assert_eq!(line, "rst line\n");
+        let mut buf: Vec<u8> = Vec::new();
+        assert_eq!(reader.read_until(b'\n', &mut buf).unwrap(), 7);
+        assert_eq!(buf, b"second\n");
+
+        // Lines are appended, and the last one has no delimiter
+        assert_eq!(reader.read_line(&mut line).unwrap(), 1);
+        assert_eq!(reader.read_line(&mut line).unwrap(), 4);
+        assert_eq!(line, "rst line\n\nlast");
+        assert_eq!(reader.read_line(&mut line).unwrap(), 0);
+        assert!(reader.fill_buf().unwrap().is_empty());
+
+        let mut data = vec![b'a', 0xFF, b'\n'];
+        let mut reader = BufReader::new(MemoryStream::new(&mut data));
+        assert!(results::lib::io::ResultInvalidUtf8::matches(
+            reader.read_line(&mut line).unwrap_err()
+        ));
+    }
+
+    #[test]
+    fn buf_reader_reads_and_seeks() {
+        let mut data: Vec<u8> = (0..32).collect();
+        let mut reader = BufReader::with_capacity(8, MemoryStream::new(&mut data));
+
+        assert_eq!(read_bytes(&mut reader, 3), vec![0, 1, 2]);
+        // The inner stream is ahead of the reader by the buffered data
+        assert_eq!(reader.get_ref().offset, 8);
+        assert_eq!(reader.stream_position().unwrap(), 3);
+        assert_eq!(read_bytes(&mut reader, 2), vec![3, 4]);
+
+        // Seeking back over buffered data
+        assert_eq!(reader.seek(SeekFrom::Current(-4)).unwrap(), 1);
+        assert_eq!(read_bytes(&mut reader, 2), vec![1, 2]);
+        assert_eq!(reader.seek(SeekFrom::Current(5)).unwrap(), 8);
+        assert_eq!(read_bytes(&mut reader, 1), vec![8]);
+        assert!(results::lib::io::ResultInvalidSeek::matches(
+            reader.seek(SeekFrom::Current(-10)).unwrap_err()
+        ));
+        assert_eq!(read_bytes(&mut reader, 1), vec![9]);
+
+        assert_eq!(reader.seek(SeekFrom::End(-4)).unwrap(), 28);
+        assert_eq!(read_bytes(&mut reader, 4), vec![28, 29, 30, 31]);
+        assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+
+        // Reads at least as big as the buffer skip it when it's empty
+        assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 4);
+        reader.get_mut().read_sizes.clear();
+        let mut buf = [0u8; 10];
+        assert_eq!(reader.read(&mut buf).unwrap(), 10);
+        assert_eq!(buf, [4, 5, 6, 7, 8, 9, 10, 11, 12, 13]);
+        assert_eq!(reader.get_ref().read_sizes, vec![10]);
+        assert_eq!(read_bytes(&mut reader, 2), vec![14, 15]);
+        assert_eq!(reader.get_ref().read_sizes, vec![10, 8]);
+    }
+
+    #[test]
+    fn buf_writer_flushes() {
+        let mut data: Vec<u8> = Vec::new();
+        {
+            let mut writer = BufWriter::with_capacity(8, MemoryStream::new(&mut data));
+            writer.write_all(b"abc").unwrap();
+            writer.write_all(b"defg").unwrap();
+            assert!(writer.get_ref().write_sizes.is_empty());
+
+            // Exceeding the capacity flushes the buffered data first
+            writer.write_all(b"hi").unwrap();
+            assert_eq!(writer.get_ref().write_sizes, vec![7]);
+
+            // Big writes go straight to the inner writer
+            writer.write_all(b"0123456789").unwrap();
+            assert_eq!(writer.get_ref().write_sizes, vec![7, 2, 10]);
+            assert_eq!(*writer.get_ref().data, b"abcdefghi0123456789");
+
+            writer.write_all(b"jk").unwrap();
+            writer.flush().unwrap();
+            assert_eq!(writer.get_ref().write_sizes, vec![7, 2, 10, 2]);
+            assert_eq!(writer.get_ref().flush_count, 1);
+
+            // Seeking flushes too, so that data ends up where it was written
+            writer.write_all(b"lm").unwrap();
+            assert_eq!(writer.seek(SeekFrom::Start(1)).unwrap(), 1);
+            writer.write_all(b"B").unwrap();
+            assert_eq!(writer.get_ref().write_sizes, vec![7, 2, 10, 2, 2]);
+        }
+        // Dropping the writer flushes what was left
+        assert_eq!(data, b"aBcdefghi0123456789jklm");
+    }
+}
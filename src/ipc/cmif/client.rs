@@ -9,7 +9,7 @@ pub fn write_command_on_ipc_buffer(
     data_size: u32,
 ) {
     unsafe {
-        let mut ipc_buf = get_ipc_buffer();
+        let mut ipc_buf = ctx.message_buffer;
 
         let has_special_header = ctx.in_params.send_process_id
             || !ctx.in_params.copy_handles.is_empty()
@@ -78,7 +78,7 @@ pub fn write_command_on_ipc_buffer(
 #[inline(always)]
 pub fn read_command_response_from_ipc_buffer(ctx: &mut CommandContext) {
     unsafe {
-        let mut ipc_buf = get_ipc_buffer();
+        let mut ipc_buf = ctx.message_buffer;
 
         let command_header = ipc_buf as *mut CommandHeader;
         ipc_buf = command_header.offset(1) as *mut u8;
@@ -114,7 +114,7 @@ pub fn write_request_command_on_ipc_buffer(
     domain_command_type: DomainCommandType,
 ) {
     unsafe {
-        let ipc_buf = get_ipc_buffer();
+        let ipc_buf = ctx.message_buffer;
 
         let has_data_header = request_id.is_some();
         let mut data_size = DATA_PADDING + ctx.in_params.data_size;
@@ -178,7 +178,7 @@ pub fn write_request_command_on_ipc_buffer(
 #[inline(always)]
 pub fn read_request_command_response_from_ipc_buffer(ctx: &mut CommandContext) -> Result<()> {
     unsafe {
-        let ipc_buf = get_ipc_buffer();
+        let ipc_buf = ctx.message_buffer;
         read_command_response_from_ipc_buffer(ctx);
 
         let mut data_offset = get_aligned_data_offset(ctx.out_params.data_words_offset, ipc_buf);
@@ -207,7 +207,7 @@ pub fn read_request_command_response_from_ipc_buffer(ctx: &mut CommandContext) -
 #[inline(always)]
 pub fn write_control_command_on_ipc_buffer(ctx: &mut CommandContext, request_id: ControlRequestId) {
     unsafe {
-        let ipc_buf = get_ipc_buffer();
+        let ipc_buf = ctx.message_buffer;
         let data_size =
             DATA_PADDING + cmem::size_of::<DataHeader>() as u32 + ctx.in_params.data_size;
 
@@ -225,7 +225,7 @@ pub fn write_control_command_on_ipc_buffer(ctx: &mut CommandContext, request_id:
 #[inline(always)]
 pub fn read_control_command_response_from_ipc_buffer(ctx: &mut CommandContext) -> Result<()> {
     unsafe {
-        let ipc_buf = get_ipc_buffer();
+        let ipc_buf = ctx.message_buffer;
 
         read_command_response_from_ipc_buffer(ctx);
         let mut data_offset = get_aligned_data_offset(ctx.out_params.data_words_offset, ipc_buf);
@@ -18,6 +18,7 @@ pub struct ObjectInfo {
     pub handle: svc::Handle,
     pub domain_object_id: DomainObjectId,
     pub owns_handle: bool,
+    pub transport_id: transport::TransportId,
 }
 
 impl ObjectInfo {
@@ -26,6 +27,7 @@ impl ObjectInfo {
             handle: 0,
             domain_object_id: 0,
             owns_handle: false,
+            transport_id: transport::KERNEL_TRANSPORT_ID,
         }
     }
 
@@ -34,6 +36,7 @@ impl ObjectInfo {
             handle,
             domain_object_id: 0,
             owns_handle: true,
+            transport_id: transport::KERNEL_TRANSPORT_ID,
         }
     }
 
@@ -45,6 +48,21 @@ impl ObjectInfo {
             handle: parent_handle,
             domain_object_id,
             owns_handle: false,
+            transport_id: transport::KERNEL_TRANSPORT_ID,
+        }
+    }
+
+    // Sessions going through an in-process transport, which never own their
+    // (fake) handles
+    pub const fn from_transport_handle(
+        handle: svc::Handle,
+        transport_id: transport::TransportId,
+    ) -> Self {
+        Self {
+            handle,
+            domain_object_id: 0,
+            owns_handle: false,
+            transport_id,
         }
     }
 
@@ -362,6 +380,7 @@ pub struct CommandContext {
     pub object_info: ObjectInfo,
     pub in_params: CommandIn,
     pub out_params: CommandOut,
+    message_buffer: *mut u8,
     send_statics: ArrayVec<[SendStaticDescriptor; MAX_COUNT]>,
     receive_statics: ArrayVec<[ReceiveStaticDescriptor; MAX_COUNT]>,
     send_buffers: ArrayVec<[BufferDescriptor; MAX_COUNT]>,
@@ -379,6 +398,7 @@ impl CommandContext {
             object_info: ObjectInfo::new(),
             in_params: CommandIn::empty(),
             out_params: CommandOut::empty(),
+            message_buffer: core::ptr::null_mut(),
             send_statics: ArrayVec::new(),
             receive_statics: ArrayVec::new(),
             send_buffers: ArrayVec::new(),
@@ -394,6 +414,7 @@ impl CommandContext {
     pub fn new_client(object_info: ObjectInfo) -> Self {
         let mut ctx = Self::empty();
         ctx.object_info = object_info;
+        ctx.message_buffer = transport::get_message_buffer(object_info.transport_id);
         ctx
     }
 
@@ -415,13 +436,22 @@ impl CommandContext {
         }
     }
 
-    pub fn new_server(object_info: ObjectInfo, pointer_buffer: *mut u8) -> Self {
+    pub fn new_server(
+        object_info: ObjectInfo,
+        pointer_buffer: *mut u8,
+        message_buffer: *mut u8,
+    ) -> Self {
         let mut ctx = Self::empty();
         ctx.object_info = object_info;
         ctx.pointer_buffer = pointer_buffer;
+        ctx.message_buffer = message_buffer;
         ctx
     }
 
+    pub fn get_message_buffer(&self) -> *mut u8 {
+        self.message_buffer
+    }
+
     pub fn add_send_static(&mut self, send_static: SendStaticDescriptor) -> Result<()> {
         match self.send_statics.try_push(send_static) {
             Ok(()) => Ok(()),
@@ -690,11 +720,14 @@ impl CommandContext {
     }
 
     pub fn pop_object(&mut self) -> Result<ObjectInfo> {
-        let object_info: ObjectInfo;
+        let mut object_info: ObjectInfo;
         if self.object_info.is_domain() {
             let domain_object_id = self.out_params.pop_domain_object()?;
             object_info =
                 ObjectInfo::from_domain_object_id(self.object_info.handle, domain_object_id);
+            // Domain objects live in the same session, so they go through the same
+            // transport
+            object_info.transport_id = self.object_info.transport_id;
         } else {
             let handle: sf::MoveHandle = self.out_params.pop_handle()?;
             object_info = ObjectInfo::from_handle(handle.handle);
@@ -39,15 +39,45 @@ impl<'a> ServerContext<'a> {
     }
 }
 
+// Checks that the given range is fully inside the message buffer
 #[inline(always)]
-pub fn read_command_from_ipc_buffer(ctx: &mut CommandContext) -> CommandType {
+fn check_message_range(ipc_buf: *mut u8, offset: *mut u8, size: usize) -> Result<()> {
+    let start_offset = offset as usize - ipc_buf as usize;
+    match start_offset.checked_add(size) {
+        Some(end_offset) if end_offset <= MESSAGE_BUFFER_SIZE => Ok(()),
+        _ => Err(results::hipc::ResultInvalidRequestSize::make()),
+    }
+}
+
+// Arrays must fit both in the message buffer and in the array they're read into
+#[inline(always)]
+fn check_message_array<T>(
+    ipc_buf: *mut u8,
+    offset: *mut u8,
+    count: u32,
+    capacity: usize,
+) -> Result<()> {
+    result_return_unless!(
+        count as usize <= capacity,
+        results::hipc::ResultInvalidRequestSize
+    );
+    check_message_range(ipc_buf, offset, count as usize * cmem::size_of::<T>())
+}
+
+#[inline(always)]
+pub fn read_command_from_ipc_buffer(ctx: &mut CommandContext) -> Result<CommandType> {
     unsafe {
-        let mut ipc_buf = get_ipc_buffer();
+        let base_ipc_buf = ctx.message_buffer;
+        let mut ipc_buf = base_ipc_buf;
 
         let command_header = ipc_buf as *mut CommandHeader;
         ipc_buf = command_header.offset(1) as *mut u8;
 
-        let command_type = (*command_header).get_command_type();
+        let command_type = convert_command_type((*command_header).get_command_type());
+        result_return_if!(
+            command_type == CommandType::Invalid,
+            results::hipc::ResultUnknownCommandType
+        );
         let data_size = (*command_header).get_data_word_count() * cmem::size_of::<u32>() as u32;
         ctx.in_params.data_size = data_size;
 
@@ -58,34 +88,82 @@ pub fn read_command_from_ipc_buffer(ctx: &mut CommandContext) -> CommandType {
             ctx.in_params.send_process_id = (*special_header).get_send_process_id();
             if ctx.in_params.send_process_id {
                 let process_id_ptr = ipc_buf as *mut u64;
-                ctx.in_params.process_id = *process_id_ptr;
+                // The process ID isn't 8-byte aligned inside the message
+                ctx.in_params.process_id = core::ptr::read_unaligned(process_id_ptr);
                 ipc_buf = process_id_ptr.offset(1) as *mut u8;
             }
 
             let copy_handle_count = (*special_header).get_copy_handle_count();
+            check_message_array::<svc::Handle>(
+                base_ipc_buf,
+                ipc_buf,
+                copy_handle_count,
+                ctx.in_params.copy_handles.capacity(),
+            )?;
             ipc_buf =
                 read_array_from_buffer(ipc_buf, copy_handle_count, &mut ctx.in_params.copy_handles);
             let move_handle_count = (*special_header).get_move_handle_count();
+            check_message_array::<svc::Handle>(
+                base_ipc_buf,
+                ipc_buf,
+                move_handle_count,
+                ctx.in_params.move_handles.capacity(),
+            )?;
             ipc_buf =
                 read_array_from_buffer(ipc_buf, move_handle_count, &mut ctx.in_params.move_handles);
         }
 
         let send_static_count = (*command_header).get_send_static_count();
+        check_message_array::<SendStaticDescriptor>(
+            base_ipc_buf,
+            ipc_buf,
+            send_static_count,
+            ctx.send_statics.capacity(),
+        )?;
         ipc_buf = read_array_from_buffer(ipc_buf, send_static_count, &mut ctx.send_statics);
         let send_buffer_count = (*command_header).get_send_buffer_count();
+        check_message_array::<BufferDescriptor>(
+            base_ipc_buf,
+            ipc_buf,
+            send_buffer_count,
+            ctx.send_buffers.capacity(),
+        )?;
         ipc_buf = read_array_from_buffer(ipc_buf, send_buffer_count, &mut ctx.send_buffers);
         let receive_buffer_count = (*command_header).get_receive_buffer_count();
+        check_message_array::<BufferDescriptor>(
+            base_ipc_buf,
+            ipc_buf,
+            receive_buffer_count,
+            ctx.receive_buffers.capacity(),
+        )?;
         ipc_buf = read_array_from_buffer(ipc_buf, receive_buffer_count, &mut ctx.receive_buffers);
         let exchange_buffer_count = (*command_header).get_exchange_buffer_count();
+        check_message_array::<BufferDescriptor>(
+            base_ipc_buf,
+            ipc_buf,
+            exchange_buffer_count,
+            ctx.exchange_buffers.capacity(),
+        )?;
         ipc_buf = read_array_from_buffer(ipc_buf, exchange_buffer_count, &mut ctx.exchange_buffers);
 
+        check_message_range(base_ipc_buf, ipc_buf, data_size as usize)?;
         ctx.in_params.data_words_offset = ipc_buf;
         ipc_buf = ipc_buf.offset(data_size as isize);
 
-        let receive_static_count = (*command_header).get_receive_static_count();
+        // A count of 0xFF stands for a single receive static
+        let receive_static_count = match (*command_header).get_receive_static_count() {
+            0xFF => 1,
+            count => count,
+        };
+        check_message_array::<ReceiveStaticDescriptor>(
+            base_ipc_buf,
+            ipc_buf,
+            receive_static_count,
+            ctx.receive_statics.capacity(),
+        )?;
         read_array_from_buffer(ipc_buf, receive_static_count, &mut ctx.receive_statics);
 
-        convert_command_type(command_type)
+        Ok(command_type)
     }
 }
 
@@ -96,7 +174,7 @@ pub fn write_command_response_on_ipc_buffer(
     data_size: u32,
 ) {
     unsafe {
-        let mut ipc_buf = get_ipc_buffer();
+        let mut ipc_buf = ctx.message_buffer;
 
         let command_header = ipc_buf as *mut CommandHeader;
         ipc_buf = command_header.offset(1) as *mut u8;
@@ -171,19 +249,44 @@ pub fn read_request_command_from_ipc_buffer(
     unsafe {
         let mut domain_command_type = DomainCommandType::Invalid;
         let mut domain_object_id: DomainObjectId = 0;
-        let ipc_buf = get_ipc_buffer();
+        let ipc_buf = ctx.message_buffer;
         let mut data_offset = get_aligned_data_offset(ctx.in_params.data_words_offset, ipc_buf);
+        // The data words were already checked to be inside the message buffer
+        let data_end = ctx
+            .in_params
+            .data_words_offset
+            .add(ctx.in_params.data_size as usize);
 
         let mut data_header = data_offset as *mut DataHeader;
         if ctx.object_info.is_domain() {
+            result_return_unless!(
+                ctx.in_params.data_size
+                    >= DATA_PADDING + cmem::size_of::<DomainInDataHeader>() as u32,
+                results::cmif::ResultInvalidHeaderSize
+            );
             let domain_header = data_offset as *mut DomainInDataHeader;
             data_offset = domain_header.offset(1) as *mut u8;
             ctx.in_params.data_size -= cmem::size_of::<DomainInDataHeader>() as u32;
 
-            domain_command_type = (*domain_header).command_type;
+            // Read the type as a raw value, since it may not be a valid enum variant
+            domain_command_type = match *(domain_header as *const u8) {
+                1 => DomainCommandType::SendMessage,
+                2 => DomainCommandType::Close,
+                _ => return Err(results::cmif::ResultInvalidInputHeader::make()),
+            };
             let object_count = (*domain_header).object_count;
             domain_object_id = (*domain_header).domain_object_id;
-            let objects_offset = data_offset.offset((*domain_header).data_size as isize);
+            result_return_unless!(
+                object_count as usize <= ctx.in_params.objects.capacity(),
+                results::cmif::ResultInvalidInObjectCount
+            );
+            let objects_offset = data_offset.wrapping_add((*domain_header).data_size as usize);
+            let objects_end = objects_offset
+                .wrapping_add(object_count as usize * cmem::size_of::<DomainObjectId>());
+            result_return_unless!(
+                objects_end <= data_end,
+                results::cmif::ResultInvalidHeaderSize
+            );
             read_array_from_buffer(
                 objects_offset,
                 object_count as u32,
@@ -194,6 +297,7 @@ pub fn read_request_command_from_ipc_buffer(
         }
 
         let mut rq_id: u32 = 0;
+        let mut has_data_header = false;
         if ctx.in_params.data_size >= DATA_PADDING {
             ctx.in_params.data_size -= DATA_PADDING;
             if ctx.in_params.data_size >= cmem::size_of::<DataHeader>() as u32 {
@@ -205,8 +309,14 @@ pub fn read_request_command_from_ipc_buffer(
                 rq_id = (*data_header).value;
                 data_offset = data_header.offset(1) as *mut u8;
                 ctx.in_params.data_size -= cmem::size_of::<DataHeader>() as u32;
+                has_data_header = true;
             }
         }
+        // Only domain close requests come without a data header
+        result_return_unless!(
+            has_data_header || (domain_command_type == DomainCommandType::Close),
+            results::cmif::ResultInvalidHeaderSize
+        );
 
         ctx.in_params.data_offset = data_offset;
         Ok((rq_id, domain_command_type, domain_object_id))
@@ -220,7 +330,7 @@ pub fn write_request_command_response_on_ipc_buffer(
     request_type: CommandType,
 ) {
     unsafe {
-        let ipc_buf = get_ipc_buffer();
+        let ipc_buf = ctx.message_buffer;
         let mut data_size =
             DATA_PADDING + cmem::size_of::<DataHeader>() as u32 + ctx.out_params.data_size;
         if ctx.object_info.is_domain() {
@@ -260,9 +370,13 @@ pub fn write_request_command_response_on_ipc_buffer(
 #[inline(always)]
 pub fn read_control_command_from_ipc_buffer(ctx: &mut CommandContext) -> Result<ControlRequestId> {
     unsafe {
-        let ipc_buf = get_ipc_buffer();
+        let ipc_buf = ctx.message_buffer;
         let mut data_offset = get_aligned_data_offset(ctx.in_params.data_words_offset, ipc_buf);
 
+        result_return_unless!(
+            ctx.in_params.data_size >= DATA_PADDING + cmem::size_of::<DataHeader>() as u32,
+            results::cmif::ResultInvalidHeaderSize
+        );
         let data_header = data_offset as *mut DataHeader;
         data_offset = data_header.offset(1) as *mut u8;
 
@@ -270,11 +384,18 @@ pub fn read_control_command_from_ipc_buffer(ctx: &mut CommandContext) -> Result<
             (*data_header).magic == IN_DATA_HEADER_MAGIC,
             results::cmif::ResultInvalidInputHeader
         );
-        let control_rq_id = (*data_header).value;
+        let control_rq_id = match (*data_header).value {
+            0 => ControlRequestId::ConvertCurrentObjectToDomain,
+            1 => ControlRequestId::CopyFromCurrentDomain,
+            2 => ControlRequestId::CloneCurrentObject,
+            3 => ControlRequestId::QueryPointerBufferSize,
+            4 => ControlRequestId::CloneCurrentObjectEx,
+            _ => return Err(results::cmif::ResultInvalidCommandRequestId::make()),
+        };
 
         ctx.in_params.data_offset = data_offset;
         ctx.in_params.data_size -= DATA_PADDING + cmem::size_of::<DataHeader>() as u32;
-        Ok(cmem::transmute(control_rq_id))
+        Ok(control_rq_id)
     }
 }
 
@@ -285,7 +406,7 @@ pub fn write_control_command_response_on_ipc_buffer(
     control_type: CommandType,
 ) {
     unsafe {
-        let ipc_buf = get_ipc_buffer();
+        let ipc_buf = ctx.message_buffer;
         let mut data_size =
             DATA_PADDING + cmem::size_of::<DataHeader>() as u32 + ctx.out_params.data_size;
         data_size = (data_size + 1) & !1;
@@ -310,6 +431,16 @@ pub fn write_close_command_response_on_ipc_buffer(ctx: &mut CommandContext) {
     write_command_response_on_ipc_buffer(ctx, CommandType::Close, 0);
 }
 
+// Raw parameters must be inside the data actually sent by the client
+#[inline(always)]
+fn check_raw_data_read<T>(ctx: &ServerContext) -> Result<()> {
+    result_return_unless!(
+        ctx.raw_data_walker.get_end_offset::<T>() <= ctx.ctx.in_params.data_size as isize,
+        results::cmif::ResultInvalidHeaderSize
+    );
+    Ok(())
+}
+
 pub trait CommandParameter<O> {
     fn after_request_read(ctx: &mut ServerContext) -> Result<O>;
     fn before_response_write(var: &Self, ctx: &mut ServerContext) -> Result<()>;
@@ -318,6 +449,7 @@ pub trait CommandParameter<O> {
 
 impl<T: Copy> CommandParameter<T> for T {
     default fn after_request_read(ctx: &mut ServerContext) -> Result<Self> {
+        check_raw_data_read::<Self>(ctx)?;
         Ok(ctx.raw_data_walker.advance_get())
     }
 
@@ -368,6 +500,7 @@ impl CommandParameter<sf::ProcessId> for sf::ProcessId {
         if ctx.ctx.in_params.send_process_id {
             // TODO: is this really how process ID works? (is the in raw u64 just
             // placeholder data?)
+            check_raw_data_read::<u64>(ctx)?;
             let _ = ctx.raw_data_walker.advance_get::<u64>();
             Ok(sf::ProcessId::from(ctx.ctx.in_params.process_id))
         } else {
@@ -430,6 +563,11 @@ pub trait IMitmServerObject: sf::IObject {
     fn new(info: sm::MitmProcessInfo) -> Self
     where
         Self: Sized;
+
+    // The forward session handle stays owned by the server, and the client might
+    // convert it into a domain later on (only control commands are safe to send
+    // through it directly)
+    fn set_forward_handle(&mut self, _forward_handle: svc::Handle) {}
 }
 
 fn create_server_object_impl<S: IServerObject + 'static>() -> mem::Shared<dyn sf::IObject> {
@@ -438,12 +576,15 @@ fn create_server_object_impl<S: IServerObject + 'static>() -> mem::Shared<dyn sf
 
 fn create_mitm_server_object_impl<S: IMitmServerObject + 'static>(
     info: sm::MitmProcessInfo,
+    forward_handle: svc::Handle,
 ) -> mem::Shared<dyn sf::IObject> {
-    mem::Shared::new(S::new(info))
+    let mut object = S::new(info);
+    object.set_forward_handle(forward_handle);
+    mem::Shared::new(object)
 }
 
 pub type NewServerFn = fn() -> mem::Shared<dyn sf::IObject>;
-pub type NewMitmServerFn = fn(sm::MitmProcessInfo) -> mem::Shared<dyn sf::IObject>;
+pub type NewMitmServerFn = fn(sm::MitmProcessInfo, svc::Handle) -> mem::Shared<dyn sf::IObject>;
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u8)]
@@ -631,7 +772,7 @@ impl ServerHolder {
     ) -> Result<Self> {
         let new_mitm_fn = self.get_new_mitm_server_fn()?;
         Ok(Self {
-            server: (new_mitm_fn)(info),
+            server: (new_mitm_fn)(info, forward_handle),
             info: ObjectInfo::from_handle(handle),
             new_server_fn: self.new_server_fn,
             new_mitm_server_fn: self.new_mitm_server_fn,
@@ -899,6 +1040,7 @@ impl<const P: usize> ServerManager<P> {
         domain_table: mem::Shared<DomainTable>,
     ) -> Result<()> {
         let is_domain = ctx.object_info.is_domain();
+        let message_buffer = ctx.message_buffer;
         let domain_table_clone = domain_table.clone();
         let mut do_handle_request = || -> Result<()> {
             let mut new_sessions: Vec<ServerHolder> = Vec::new();
@@ -906,9 +1048,12 @@ impl<const P: usize> ServerManager<P> {
                 let server_info = server_holder.info;
                 if server_info.handle == ctx.object_info.handle {
                     let send_to_forward_handle = || -> Result<()> {
-                        let ipc_buf = get_ipc_buffer();
                         unsafe {
-                            core::ptr::copy(ipc_buf_backup.as_ptr(), ipc_buf, ipc_buf_backup.len());
+                            core::ptr::copy(
+                                ipc_buf_backup.as_ptr(),
+                                message_buffer,
+                                ipc_buf_backup.len(),
+                            );
                         }
                         // Let the original service take care of the command for us.
                         svc::send_sync_request(server_holder.mitm_forward_info.handle)
@@ -1055,18 +1200,112 @@ impl<const P: usize> ServerManager<P> {
         Ok(())
     }
 
+    // Handles the request in the given message buffer, writing the response on it
+    fn handle_message(
+        &mut self,
+        server_info: ObjectInfo,
+        domain_table: mem::Shared<DomainTable>,
+        message_buffer: *mut u8,
+    ) -> Result<CommandType> {
+        let mut ipc_buf_backup: [u8; MESSAGE_BUFFER_SIZE] = [0; MESSAGE_BUFFER_SIZE];
+        unsafe {
+            core::ptr::copy(
+                message_buffer,
+                ipc_buf_backup.as_mut_ptr(),
+                ipc_buf_backup.len(),
+            )
+        };
+
+        let mut ctx = CommandContext::new_server(
+            server_info,
+            self.pointer_buffer.as_mut_ptr(),
+            message_buffer,
+        );
+        let command_type = match read_command_from_ipc_buffer(&mut ctx) {
+            Ok(command_type) => command_type,
+            Err(rc) => {
+                // Malformed messages get the parsing error as a plain request response
+                self.write_error_response(server_info, message_buffer, rc);
+                return Ok(CommandType::Invalid);
+            }
+        };
+        match command_type {
+            CommandType::Request | CommandType::RequestWithContext => {
+                let (rq_id, domain_cmd_type, domain_object_id) =
+                    match read_request_command_from_ipc_buffer(&mut ctx) {
+                        Ok(rq_info) => rq_info,
+                        Err(rc) => {
+                            write_request_command_response_on_ipc_buffer(
+                                &mut ctx,
+                                rc,
+                                command_type,
+                            );
+                            return Ok(command_type);
+                        }
+                    };
+                let mut base_info = server_info;
+                if server_info.is_domain() {
+                    // This is a domain request
+                    base_info.domain_object_id = domain_object_id;
+                    base_info.owns_handle = server_info.domain_object_id == domain_object_id;
+                }
+                ctx.object_info = base_info;
+                self.handle_request_command(
+                    &mut ctx,
+                    rq_id,
+                    command_type,
+                    domain_cmd_type,
+                    &ipc_buf_backup,
+                    domain_table,
+                )?;
+            }
+            CommandType::Control | CommandType::ControlWithContext => {
+                let control_rq_id = match read_control_command_from_ipc_buffer(&mut ctx) {
+                    Ok(control_rq_id) => control_rq_id,
+                    Err(rc) => {
+                        write_control_command_response_on_ipc_buffer(&mut ctx, rc, command_type);
+                        return Ok(command_type);
+                    }
+                };
+                self.handle_control_command(&mut ctx, control_rq_id as u32, command_type)?;
+            }
+            CommandType::Close => {
+                write_close_command_response_on_ipc_buffer(&mut ctx);
+            }
+            CommandType::LegacyRequest | CommandType::LegacyControl | CommandType::Invalid => {
+                // Legacy commands aren't supported, but clients still wait for a response
+                self.write_error_response(
+                    server_info,
+                    message_buffer,
+                    results::hipc::ResultUnknownCommandType::make(),
+                );
+            }
+        };
+
+        Ok(command_type)
+    }
+
+    // Written on a fresh context, since the request one might be partially filled
+    fn write_error_response(
+        &mut self,
+        server_info: ObjectInfo,
+        message_buffer: *mut u8,
+        rc: ResultCode,
+    ) {
+        let mut error_ctx = CommandContext::new_server(
+            server_info,
+            self.pointer_buffer.as_mut_ptr(),
+            message_buffer,
+        );
+        write_request_command_response_on_ipc_buffer(&mut error_ctx, rc, CommandType::Request);
+    }
+
     fn process_signaled_handle(&mut self, handle: svc::Handle) -> Result<()> {
         let mut server_found = false;
         let mut index: usize = 0;
         let mut should_close_session = false;
         let mut new_sessions: Vec<ServerHolder> = Vec::new();
-
-        let mut ctx = CommandContext::empty();
-        let mut command_type = CommandType::Invalid;
-        let mut domain_cmd_type = DomainCommandType::Invalid;
-        let mut rq_id: u32 = 0;
-        let mut ipc_buf_backup: [u8; 0x100] = [0; 0x100];
-        let mut domain_table: mem::Shared<DomainTable> = mem::Shared::empty();
+        let mut received_session: Option<(ObjectInfo, mem::Shared<DomainTable>)> = None;
 
         for server_holder in &mut self.server_holders {
             let server_info = server_holder.info;
@@ -1101,48 +1340,7 @@ impl<const P: usize> ServerManager<P> {
                             _ => {}
                         };
 
-                        unsafe {
-                            core::ptr::copy(
-                                get_ipc_buffer(),
-                                ipc_buf_backup.as_mut_ptr(),
-                                ipc_buf_backup.len(),
-                            )
-                        };
-
-                        ctx = CommandContext::new_server(
-                            server_info,
-                            self.pointer_buffer.as_mut_ptr(),
-                        );
-                        command_type = read_command_from_ipc_buffer(&mut ctx);
-                        match command_type {
-                            CommandType::Request | CommandType::RequestWithContext => {
-                                match read_request_command_from_ipc_buffer(&mut ctx) {
-                                    Ok((request_id, domain_command_type, domain_object_id)) => {
-                                        let mut base_info = server_info;
-                                        if server_info.is_domain() {
-                                            // This is a domain request
-                                            base_info.domain_object_id = domain_object_id;
-                                            base_info.owns_handle =
-                                                server_info.domain_object_id == domain_object_id;
-                                        }
-                                        ctx.object_info = base_info;
-                                        domain_cmd_type = domain_command_type;
-                                        rq_id = request_id;
-                                        domain_table = server_holder.domain_table.clone();
-                                    }
-                                    Err(rc) => return Err(rc),
-                                };
-                            }
-                            CommandType::Control | CommandType::ControlWithContext => {
-                                match read_control_command_from_ipc_buffer(&mut ctx) {
-                                    Ok(control_rq_id) => {
-                                        rq_id = control_rq_id as u32;
-                                    }
-                                    Err(rc) => return Err(rc),
-                                };
-                            }
-                            _ => {}
-                        }
+                        received_session = Some((server_info, server_holder.domain_table.clone()));
                     }
                     WaitHandleType::Server => {
                         let new_handle = svc::accept_session(handle)?;
@@ -1183,29 +1381,17 @@ impl<const P: usize> ServerManager<P> {
             }
         };
 
-        match command_type {
-            CommandType::Request | CommandType::RequestWithContext => {
-                self.handle_request_command(
-                    &mut ctx,
-                    rq_id,
-                    command_type,
-                    domain_cmd_type,
-                    &ipc_buf_backup,
-                    domain_table,
-                )?;
-                reply_impl()?;
-            }
-            CommandType::Control | CommandType::ControlWithContext => {
-                self.handle_control_command(&mut ctx, rq_id, command_type)?;
-                reply_impl()?;
-            }
-            CommandType::Close => {
-                write_close_command_response_on_ipc_buffer(&mut ctx);
-                reply_impl()?;
-                should_close_session = true;
-            }
-            _ => { /* TODO - or maybe nothing to do here? */ }
-        };
+        if let Some((server_info, domain_table)) = received_session {
+            // Requests received through the kernel are always on this thread's TLS buffer
+            match self.handle_message(server_info, domain_table, get_ipc_buffer())? {
+                CommandType::Close => {
+                    reply_impl()?;
+                    should_close_session = true;
+                }
+                // Every other message (malformed ones included) has a response written
+                _ => reply_impl()?,
+            };
+        }
 
         if should_close_session {
             self.server_holders.remove(index);
@@ -1219,6 +1405,27 @@ impl<const P: usize> ServerManager<P> {
         }
     }
 
+    // Handles the request in the given message buffer for the given session
+    // without receiving or replying through the kernel (for in-process transports)
+    pub fn process_message(&mut self, handle: svc::Handle, message_buffer: *mut u8) -> Result<()> {
+        let index = match self.server_holders.iter().position(|server_holder| {
+            (server_holder.info.handle == handle)
+                && (server_holder.handle_type == WaitHandleType::Session)
+        }) {
+            Some(index) => index,
+            None => return Err(results::os::ResultInvalidHandle::make()),
+        };
+
+        let server_info = self.server_holders[index].info;
+        let domain_table = self.server_holders[index].domain_table.clone();
+        if let CommandType::Close =
+            self.handle_message(server_info, domain_table, message_buffer)?
+        {
+            self.server_holders.remove(index);
+        }
+        Ok(())
+    }
+
     pub fn register_server<S: IServerObject + 'static>(
         &mut self,
         handle: svc::Handle,
@@ -1303,3 +1510,459 @@ impl<const P: usize> ServerManager<P> {
         Ok(())
     }
 }
+
+// First fake handle given to loopback sessions, meant to never collide with
+// actual kernel handles
+const LOOPBACK_HANDLE_BASE: svc::Handle = 0x7F000000;
+
+// In-process transport: requests are handled right away by its own
+// ServerManager on the transport's message buffer, with sessions identified
+// by fake handles, so no kernel is involved. Only the sessions opened through
+// it use it, and it stays registered until dropped. Note that objects returned
+// by commands are only supported on domain sessions (otherwise actual kernel
+// sessions would be created for them)
+pub struct LoopbackTransport<const P: usize> {
+    message_buffer: [u8; MESSAGE_BUFFER_SIZE],
+    manager: ServerManager<P>,
+    next_handle: svc::Handle,
+    transport_id: transport::TransportId,
+}
+
+impl<const P: usize> LoopbackTransport<P> {
+    // Transports are registered by address, hence the shared (boxed) object
+    pub fn new() -> Result<mem::Shared<Self>> {
+        let mut loopback = mem::Shared::new(Self {
+            message_buffer: [0; MESSAGE_BUFFER_SIZE],
+            manager: ServerManager::new()?,
+            next_handle: LOOPBACK_HANDLE_BASE,
+            transport_id: transport::KERNEL_TRANSPORT_ID,
+        });
+
+        let transport_ptr: *mut dyn transport::Transport = loopback.get();
+        loopback.get().transport_id = transport::register_transport(transport_ptr);
+        Ok(loopback)
+    }
+
+    pub fn get_manager(&mut self) -> &mut ServerManager<P> {
+        &mut self.manager
+    }
+
+    pub fn get_transport_id(&self) -> transport::TransportId {
+        self.transport_id
+    }
+
+    // The returned sessions don't own their fake handles, so nothing is ever
+    // closed through the kernel for them
+    pub fn open_session<S: IServerObject + 'static>(&mut self) -> sf::Session {
+        self.open_object_session(mem::Shared::new(S::new()))
+    }
+
+    pub fn open_object_session(&mut self, object: mem::Shared<dyn sf::IObject>) -> sf::Session {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+
+        let mut server_holder = ServerHolder::new_session(handle, object);
+        server_holder.info.owns_handle = false;
+        self.manager.server_holders.push(server_holder);
+        sf::Session::from(ObjectInfo::from_transport_handle(handle, self.transport_id))
+    }
+}
+
+impl<const P: usize> Drop for LoopbackTransport<P> {
+    fn drop(&mut self) {
+        transport::unregister_transport(self.transport_id);
+    }
+}
+
+impl<const P: usize> transport::Transport for LoopbackTransport<P> {
+    fn get_message_buffer(&mut self) -> *mut u8 {
+        self.message_buffer.as_mut_ptr()
+    }
+
+    fn send_sync_request(&mut self, handle: svc::Handle) -> Result<()> {
+        let message_buffer = self.message_buffer.as_mut_ptr();
+        self.manager.process_message(handle, message_buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fs,
+        ipc::tipc,
+        service::{
+            cmif::{fspsrv, fspsrv::IFile, IClientObject},
+            tipc::IClientObject as _,
+        },
+    };
+
+    fn open_ram_file_session(loopback: &mut LoopbackTransport<0>) -> fspsrv::File {
+        let mut ram_fs = fs::ram::FileSystem::new();
+        fs::FileSystem::create_file(&mut ram_fs, "/file", fs::FileAttribute::None(), 0).unwrap();
+        let ram_file = fs::FileSystem::open_file(
+            &mut ram_fs,
+            "/file",
+            fs::FileOpenMode::Read() | fs::FileOpenMode::Write() | fs::FileOpenMode::Append(),
+        )
+        .unwrap();
+
+        fspsrv::File::new(
+            loopback.open_object_session(mem::Shared::new(fs::server::File::new(ram_file))),
+        )
+    }
+
+    #[test]
+    fn loopback_file_round_trip() {
+        let loopback = LoopbackTransport::<0>::new().unwrap();
+        let mut file = open_ram_file_session(loopback.get());
+
+        let data = *b"loopback";
+        file.write(
+            fspsrv::FileWriteOption::Flush(),
+            0,
+            data.len(),
+            sf::Buffer::from_array(&data),
+        )
+        .unwrap();
+        assert_eq!(file.get_size().unwrap(), data.len());
+
+        let mut read_data = [0u8; 8];
+        let read_size = file
+            .read(
+                fspsrv::FileReadOption::None(),
+                0,
+                read_data.len(),
+                sf::Buffer::from_mut(read_data.as_mut_ptr(), read_data.len()),
+            )
+            .unwrap();
+        assert_eq!(read_size, data.len());
+        assert_eq!(read_data, data);
+
+        file.set_size(4).unwrap();
+        assert_eq!(file.get_size().unwrap(), 4);
+    }
+
+    #[test]
+    fn loopback_session_after_transport_drop() {
+        let loopback = LoopbackTransport::<0>::new().unwrap();
+        let mut file = open_ram_file_session(loopback.get());
+        drop(loopback);
+
+        // Unregistered transports must fail instead of falling back to the kernel
+        assert!(results::hipc::ResultSessionClosed::matches(
+            file.get_size().unwrap_err()
+        ));
+    }
+
+    const FAKE_PORT_HANDLE: svc::Handle = 0x1234;
+    const FAKE_SESSION_HANDLE: svc::Handle = 0x5678;
+
+    // Just enough of sm to check TIPC requests and responses through the loopback
+    struct FakeSm {
+        session: tipc::sf::Session,
+        client_registered: bool,
+        services: Vec<sm::ServiceName>,
+    }
+
+    impl FakeSm {
+        fn new() -> Self {
+            Self {
+                session: tipc::sf::Session::new(),
+                client_registered: false,
+                services: Vec::new(),
+            }
+        }
+
+        fn find_service(&self, name: sm::ServiceName) -> Option<usize> {
+            self.services.iter().position(|&service| service == name)
+        }
+    }
+
+    impl tipc::sf::IObject for FakeSm {
+        fn get_session(&mut self) -> &mut tipc::sf::Session {
+            &mut self.session
+        }
+
+        fn get_command_table(&self) -> tipc::sf::CommandMetadataTable {
+            vec![
+                ipc_tipc_interface_make_command_meta!(register_client: 0),
+                ipc_tipc_interface_make_command_meta!(get_service_handle: 1),
+                ipc_tipc_interface_make_command_meta!(register_service: 2),
+                ipc_tipc_interface_make_command_meta!(unregister_service: 3),
+                ipc_tipc_interface_make_command_meta!(atmosphere_has_service: 65100),
+            ]
+        }
+    }
+
+    impl IUserInterface for FakeSm {
+        fn register_client(&mut self, _process_id: tipc::sf::ProcessId) -> Result<()> {
+            self.client_registered = true;
+            Ok(())
+        }
+
+        fn get_service_handle(&mut self, name: sm::ServiceName) -> Result<tipc::sf::MoveHandle> {
+            result_return_unless!(self.client_registered, results::sm::ResultNotInitialized);
+            result_return_unless!(
+                self.find_service(name).is_some(),
+                results::os::ResultInvalidHandle
+            );
+            Ok(tipc::sf::MoveHandle::from(FAKE_SESSION_HANDLE))
+        }
+
+        fn register_service(
+            &mut self,
+            name: sm::ServiceName,
+            max_sessions: i32,
+            is_light: bool,
+        ) -> Result<tipc::sf::MoveHandle> {
+            result_return_unless!(
+                (max_sessions == 4) && !is_light,
+                results::hipc::ResultUnsupportedOperation
+            );
+            self.services.push(name);
+            Ok(tipc::sf::MoveHandle::from(FAKE_PORT_HANDLE))
+        }
+
+        fn unregister_service(&mut self, name: sm::ServiceName) -> Result<()> {
+            match self.find_service(name) {
+                Some(index) => {
+                    self.services.remove(index);
+                    Ok(())
+                }
+                None => Err(results::os::ResultInvalidHandle::make()),
+            }
+        }
+
+        fn detach_client(&mut self, _process_id: tipc::sf::ProcessId) -> Result<()> {
+            Err(results::hipc::ResultUnsupportedOperation::make())
+        }
+
+        fn atmosphere_install_mitm(
+            &mut self,
+            _name: sm::ServiceName,
+        ) -> Result<(tipc::sf::MoveHandle, tipc::sf::MoveHandle)> {
+            Err(results::hipc::ResultUnsupportedOperation::make())
+        }
+
+        fn atmosphere_uninstall_mitm(&mut self, _name: sm::ServiceName) -> Result<()> {
+            Err(results::hipc::ResultUnsupportedOperation::make())
+        }
+
+        fn atmosphere_acknowledge_mitm_session(
+            &mut self,
+            _name: sm::ServiceName,
+        ) -> Result<(sm::MitmProcessInfo, tipc::sf::MoveHandle)> {
+            Err(results::hipc::ResultUnsupportedOperation::make())
+        }
+
+        fn atmosphere_has_mitm(&mut self, _name: sm::ServiceName) -> Result<bool> {
+            Err(results::hipc::ResultUnsupportedOperation::make())
+        }
+
+        fn atmosphere_wait_mitm(&mut self, _name: sm::ServiceName) -> Result<()> {
+            Err(results::hipc::ResultUnsupportedOperation::make())
+        }
+
+        fn atmosphere_declare_future_mitm(&mut self, _name: sm::ServiceName) -> Result<()> {
+            Err(results::hipc::ResultUnsupportedOperation::make())
+        }
+
+        fn atmosphere_clear_future_mitm(&mut self, _name: sm::ServiceName) -> Result<()> {
+            Err(results::hipc::ResultUnsupportedOperation::make())
+        }
+
+        fn atmosphere_has_service(&mut self, name: sm::ServiceName) -> Result<bool> {
+            Ok(self.find_service(name).is_some())
+        }
+
+        fn atmosphere_wait_service(&mut self, _name: sm::ServiceName) -> Result<()> {
+            Err(results::hipc::ResultUnsupportedOperation::make())
+        }
+    }
+
+    #[test]
+    fn loopback_tipc_round_trip() {
+        let loopback = tipc::server::LoopbackTransport::new();
+        let mut sm = sm::UserInterface::new(
+            loopback
+                .get()
+                .open_object_session(mem::Shared::new(FakeSm::new())),
+        );
+        let service_name = sm::ServiceName::new(nul!("loopback"));
+        let other_service_name = sm::ServiceName::new(nul!("othersrv"));
+
+        // Failures sent by the server are returned as is
+        assert!(results::sm::ResultNotInitialized::matches(
+            sm.get_service_handle(service_name).unwrap_err()
+        ));
+        sm.register_client(tipc::sf::ProcessId::new()).unwrap();
+        assert!(results::os::ResultInvalidHandle::matches(
+            sm.get_service_handle(service_name).unwrap_err()
+        ));
+
+        let port_handle = sm.register_service(service_name, 4, false).unwrap();
+        assert_eq!(port_handle.handle, FAKE_PORT_HANDLE);
+        assert!(sm.atmosphere_has_service(service_name).unwrap());
+        assert!(!sm.atmosphere_has_service(other_service_name).unwrap());
+        let session_handle = sm.get_service_handle(service_name).unwrap();
+        assert_eq!(session_handle.handle, FAKE_SESSION_HANDLE);
+
+        sm.unregister_service(service_name).unwrap();
+        assert!(!sm.atmosphere_has_service(service_name).unwrap());
+        assert!(results::os::ResultInvalidHandle::matches(
+            sm.unregister_service(service_name).unwrap_err()
+        ));
+
+        // Commands missing from the server's command table
+        assert!(results::cmif::ResultInvalidCommandRequestId::matches(
+            sm.atmosphere_wait_service(service_name).unwrap_err()
+        ));
+
+        drop(loopback);
+        assert!(results::hipc::ResultSessionClosed::matches(
+            sm.atmosphere_has_service(service_name).unwrap_err()
+        ));
+    }
+
+    // Simple xorshift generator, so that failures can be reproduced from the seed
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill(&mut self, buf: &mut [u8]) {
+            for byte in buf.iter_mut() {
+                *byte = self.next() as u8;
+            }
+        }
+    }
+
+    const FUZZ_ITERATIONS: usize = 20000;
+
+    // Messages are parsed from a word-aligned buffer, like the actual TLS one
+    type MessageBuffer = [u32; MESSAGE_BUFFER_SIZE / 4];
+
+    fn as_bytes(message: &mut MessageBuffer) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(message.as_mut_ptr() as *mut u8, MESSAGE_BUFFER_SIZE)
+        }
+    }
+
+    fn write_valid_request(
+        message: &mut MessageBuffer,
+        object_info: ObjectInfo,
+        random: &mut Random,
+    ) {
+        let mut ctx = CommandContext::new_server(
+            object_info,
+            core::ptr::null_mut(),
+            message.as_mut_ptr() as *mut u8,
+        );
+        ctx.in_params.data_size = (random.next() % 0x40) as u32 & !3;
+        if object_info.is_domain() {
+            ctx.in_params
+                .add_domain_object(random.next() as DomainObjectId)
+                .unwrap();
+        }
+        client::write_request_command_on_ipc_buffer(
+            &mut ctx,
+            Some(random.next() as u32),
+            DomainCommandType::SendMessage,
+        );
+    }
+
+    // Whatever is in the buffer, parsing must either fail or leave everything
+    // pointing inside the message
+    fn parse_message(message: &mut MessageBuffer, object_info: ObjectInfo) {
+        let message_start = message.as_mut_ptr() as usize;
+        let message_end = message_start + MESSAGE_BUFFER_SIZE;
+
+        let mut ctx = CommandContext::new_server(
+            object_info,
+            core::ptr::null_mut(),
+            message.as_mut_ptr() as *mut u8,
+        );
+        let command_type = match read_command_from_ipc_buffer(&mut ctx) {
+            Ok(command_type) => command_type,
+            Err(_) => return,
+        };
+        let data_words_offset = ctx.in_params.data_words_offset as usize;
+        assert!(data_words_offset >= message_start);
+        assert!(data_words_offset + ctx.in_params.data_size as usize <= message_end);
+
+        let data_end = data_words_offset + ctx.in_params.data_size as usize;
+        let parsed = match command_type {
+            CommandType::Request | CommandType::RequestWithContext => {
+                read_request_command_from_ipc_buffer(&mut ctx).is_ok()
+            }
+            CommandType::Control | CommandType::ControlWithContext => {
+                read_control_command_from_ipc_buffer(&mut ctx).is_ok()
+            }
+            _ => false,
+        };
+        if parsed {
+            let data_offset = ctx.in_params.data_offset as usize;
+            assert!(data_offset >= data_words_offset);
+            assert!(data_offset + ctx.in_params.data_size as usize <= data_end);
+        }
+    }
+
+    fn fuzz_messages(object_info: ObjectInfo, seed: u64) {
+        let mut random = Random(seed);
+        let mut message: MessageBuffer = [0; MESSAGE_BUFFER_SIZE / 4];
+
+        for i in 0..FUZZ_ITERATIONS {
+            if (i % 2) == 0 {
+                random.fill(as_bytes(&mut message));
+            } else {
+                // Corrupt a few bytes of an otherwise valid request, which gets much
+                // further into the parser than fully random data
+                message = [0; MESSAGE_BUFFER_SIZE / 4];
+                write_valid_request(&mut message, object_info, &mut random);
+                for _ in 0..(1 + random.next() % 4) {
+                    let offset = (random.next() % 0x40) as usize;
+                    as_bytes(&mut message)[offset] = random.next() as u8;
+                }
+            }
+            parse_message(&mut message, object_info);
+        }
+    }
+
+    #[test]
+    fn fuzz_session_messages() {
+        fuzz_messages(ObjectInfo::from_handle(1), 0x1234_5678_9ABC_DEF0);
+    }
+
+    #[test]
+    fn fuzz_domain_messages() {
+        fuzz_messages(
+            ObjectInfo::from_domain_object_id(1, 1),
+            0x0FED_CBA9_8765_4321,
+        );
+    }
+
+    #[test]
+    fn parse_valid_request() {
+        let mut random = Random(1);
+        let mut message: MessageBuffer = [0; MESSAGE_BUFFER_SIZE / 4];
+        let object_info = ObjectInfo::from_handle(1);
+        write_valid_request(&mut message, object_info, &mut random);
+
+        let mut ctx = CommandContext::new_server(
+            object_info,
+            core::ptr::null_mut(),
+            message.as_mut_ptr() as *mut u8,
+        );
+        assert_eq!(
+            read_command_from_ipc_buffer(&mut ctx).unwrap(),
+            CommandType::Request
+        );
+        assert!(read_request_command_from_ipc_buffer(&mut ctx).is_ok());
+    }
+}
@@ -34,9 +34,11 @@ pub enum NativeHandleType {
 
 pub type BinderHandle = i32;
 
-pub trait IHOSBinderDriver {
-    ipc_cmif_interface_define_command!(transact_parcel: (binder_handle: BinderHandle, transaction_id: ParcelTransactionId, flags: u32, in_parcel: sf::InMapAliasBuffer, out_parcel: sf::OutMapAliasBuffer) => ());
-    ipc_cmif_interface_define_command!(adjust_refcount: (binder_handle: BinderHandle, add_value: i32, refcount_type: RefcountType) => ());
-    ipc_cmif_interface_define_command!(get_native_handle: (binder_handle: BinderHandle, handle_type: NativeHandleType) => (native_handle: sf::CopyHandle));
-    ipc_cmif_interface_define_command!(transact_parcel_auto: (binder_handle: BinderHandle, transaction_id: ParcelTransactionId, flags: u32, in_parcel: sf::InAutoSelectBuffer, out_parcel: sf::OutAutoSelectBuffer) => ());
+ipc_cmif_interface! {
+    IHOSBinderDriver, HOSBinderDriver {
+        transact_parcel [0]: (binder_handle: BinderHandle, transaction_id: ParcelTransactionId, flags: u32, in_parcel: sf::InMapAliasBuffer, out_parcel: sf::OutMapAliasBuffer) => ();
+        adjust_refcount [1]: (binder_handle: BinderHandle, add_value: i32, refcount_type: RefcountType) => ();
+        get_native_handle [2]: (binder_handle: BinderHandle, handle_type: NativeHandleType) => (native_handle: sf::CopyHandle);
+        transact_parcel_auto [3]: (binder_handle: BinderHandle, transaction_id: ParcelTransactionId, flags: u32, in_parcel: sf::InAutoSelectBuffer, out_parcel: sf::OutAutoSelectBuffer) => ();
+    }
 }
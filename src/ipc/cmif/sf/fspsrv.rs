@@ -30,32 +30,279 @@ bit_enum! {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum OperationId {
+    Clear = 0,
+    ClearSignature = 1,
+    InvalidateCache = 2,
+    QueryRange = 3,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct FileQueryRangeInfo {
+    pub aes_ctr_key_type: u32,
+    pub speed_emulation_type: u32,
+    pub reserved: [u32; 0xE],
+}
+
+bit_enum! {
+    DirectoryOpenMode (u32) {
+        ReadDirectories = bit!(0),
+        ReadFiles = bit!(1),
+        NoFileSize = bit!(31)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Derivative)]
+#[derivative(Default)]
 #[repr(u8)]
 pub enum DirectoryEntryType {
+    #[derivative(Default)]
     Directory = 0,
     File = 1,
 }
 
 pub type Path = util::CString<0x301>;
 
-pub trait IFile {
-    ipc_cmif_interface_define_command!(read: (option: FileReadOption, offset: usize, size: usize, buf: sf::OutNonSecureMapAliasBuffer) => (read_size: usize));
-    ipc_cmif_interface_define_command!(write: (option: FileWriteOption, offset: usize, size: usize, buf: sf::InNonSecureMapAliasBuffer) => ());
-    ipc_cmif_interface_define_command!(get_size: () => (size: usize));
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct DirectoryEntry {
+    pub name: Path,
+    pub pad_1: [u8; 3],
+    pub entry_type: DirectoryEntryType,
+    pub pad_2: [u8; 3],
+    pub file_size: usize,
+}
+
+ipc_cmif_interface! {
+    IFile, File {
+        read [0]: (option: FileReadOption, offset: usize, size: usize, buf: sf::OutNonSecureMapAliasBuffer) => (read_size: usize);
+        write [1]: (option: FileWriteOption, offset: usize, size: usize, buf: sf::InNonSecureMapAliasBuffer) => ();
+        flush [2]: () => ();
+        set_size [3]: (size: usize) => ();
+        get_size [4]: () => (size: usize);
+        operate_range [5, (4, 0, 0) =>]: (operation_id: OperationId, offset: usize, size: usize) => (info: FileQueryRangeInfo);
+    }
+}
+
+pub type StorageQueryRangeInfo = FileQueryRangeInfo;
+
+ipc_cmif_interface! {
+    IStorage, Storage {
+        read [0]: (offset: usize, size: usize, buf: sf::OutNonSecureMapAliasBuffer) => ();
+        write [1]: (offset: usize, size: usize, buf: sf::InNonSecureMapAliasBuffer) => ();
+        flush [2]: () => ();
+        set_size [3]: (size: usize) => ();
+        get_size [4]: () => (size: usize);
+        operate_range [5, (4, 0, 0) =>]: (operation_id: OperationId, offset: usize, size: usize) => (info: StorageQueryRangeInfo);
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Derivative)]
+#[derivative(Default)]
+#[repr(u32)]
+pub enum BisPartitionId {
+    #[derivative(Default)]
+    BootPartition1Root = 0,
+    BootPartition2Root = 10,
+    UserDataRoot = 20,
+    BootConfigAndPackage2Part1 = 21,
+    BootConfigAndPackage2Part2 = 22,
+    BootConfigAndPackage2Part3 = 23,
+    BootConfigAndPackage2Part4 = 24,
+    BootConfigAndPackage2Part5 = 25,
+    BootConfigAndPackage2Part6 = 26,
+    CalibrationBinary = 27,
+    CalibrationFile = 28,
+    SafeMode = 29,
+    User = 30,
+    System = 31,
+    SystemProperEncryption = 32,
+    SystemProperPartition = 33,
+    SignedSystemPartitionOnSafeMode = 34,
+    DeviceTreeBlob = 35,
+    System0 = 36,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Derivative)]
+#[derivative(Default)]
+#[repr(u32)]
+pub enum GameCardPartitionRaw {
+    #[derivative(Default)]
+    NormalReadOnly = 0,
+    SecureReadOnly = 1,
+    RootWriteOnly = 2,
 }
 
-pub trait IFileSystem {
-    ipc_cmif_interface_define_command!(create_file: (attribute: FileAttribute, size: usize, path_buf: sf::InPointerBuffer) => ());
-    ipc_cmif_interface_define_command!(delete_file: (path_buf: sf::InPointerBuffer) => ());
-    ipc_cmif_interface_define_command!(create_directory: (path_buf: sf::InPointerBuffer) => ());
-    ipc_cmif_interface_define_command!(delete_directory: (path_buf: sf::InPointerBuffer) => ());
-    ipc_cmif_interface_define_command!(delete_directory_recursively: (path_buf: sf::InPointerBuffer) => ());
-    ipc_cmif_interface_define_command!(get_entry_type: (path_buf: sf::InPointerBuffer) => (entry_type: DirectoryEntryType));
-    ipc_cmif_interface_define_command!(open_file: (mode: FileOpenMode, path_buf: sf::InPointerBuffer) => (file: mem::Shared<dyn sf::IObject>));
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct GameCardHandle {
+    pub value: u32,
 }
 
-pub trait IFileSystemProxy {
-    ipc_cmif_interface_define_command!(set_current_process: (process_id: sf::ProcessId) => ());
-    ipc_cmif_interface_define_command!(open_sd_card_filesystem: () => (sd_filesystem: mem::Shared<dyn sf::IObject>));
-    ipc_cmif_interface_define_command!(output_access_log_to_sd_card: (access_log: sf::InMapAliasBuffer) => ());
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Derivative)]
+#[derivative(Default)]
+#[repr(u32)]
+pub enum FileSystemProxyType {
+    #[derivative(Default)]
+    Code = 0,
+    Rom = 1,
+    Logo = 2,
+    Control = 3,
+    Manual = 4,
+    Meta = 5,
+    Data = 6,
+    Package = 7,
+    RegisteredUpdate = 8,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Derivative)]
+#[derivative(Default)]
+#[repr(u8)]
+pub enum StorageId {
+    #[derivative(Default)]
+    None = 0,
+    Host = 1,
+    GameCard = 2,
+    BuiltInSystem = 3,
+    BuiltInUser = 4,
+    SdCard = 5,
+    Any = 6,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct FileTimeStampRaw {
+    pub create: i64,
+    pub modify: i64,
+    pub access: i64,
+    pub is_valid: bool,
+    pub pad: [u8; 7],
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Derivative)]
+#[derivative(Default)]
+#[repr(u8)]
+pub enum SaveDataSpaceId {
+    #[derivative(Default)]
+    System = 0,
+    User = 1,
+    SdSystem = 2,
+    Temporary = 3,
+    SdUser = 4,
+    ProperSystem = 100,
+    SafeMode = 101,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Derivative)]
+#[derivative(Default)]
+#[repr(u8)]
+pub enum SaveDataType {
+    #[derivative(Default)]
+    System = 0,
+    Account = 1,
+    Bcat = 2,
+    Device = 3,
+    Temporary = 4,
+    Cache = 5,
+    SystemBcat = 6,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Derivative)]
+#[derivative(Default)]
+#[repr(u8)]
+pub enum SaveDataRank {
+    #[derivative(Default)]
+    Primary = 0,
+    Secondary = 1,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct UserId {
+    pub uid: [u64; 2],
+}
+
+impl UserId {
+    pub const fn new(uid: [u64; 2]) -> Self {
+        Self { uid }
+    }
+
+    pub const fn empty() -> Self {
+        Self::new([0; 2])
+    }
+
+    pub const fn is_valid(&self) -> bool {
+        (self.uid[0] != 0) || (self.uid[1] != 0)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct SaveDataAttribute {
+    pub program_id: u64,
+    pub user_id: UserId,
+    pub system_save_data_id: u64,
+    pub save_data_type: SaveDataType,
+    pub save_data_rank: SaveDataRank,
+    pub save_data_index: u16,
+    pub pad: u32,
+    pub reserved: [u64; 3],
+}
+
+ipc_cmif_interface! {
+    IDirectory, Directory {
+        read [0]: (out_entries: sf::OutMapAliasBuffer) => (read_count: u64);
+        get_entry_count [1]: () => (count: u64);
+    }
+}
+
+ipc_cmif_interface! {
+    IFileSystem, FileSystem {
+        create_file [0]: (attribute: FileAttribute, size: usize, path_buf: sf::InPointerBuffer) => ();
+        delete_file [1]: (path_buf: sf::InPointerBuffer) => ();
+        create_directory [2]: (path_buf: sf::InPointerBuffer) => ();
+        delete_directory [3]: (path_buf: sf::InPointerBuffer) => ();
+        delete_directory_recursively [4]: (path_buf: sf::InPointerBuffer) => ();
+        rename_file [5]: (old_path_buf: sf::InPointerBuffer, new_path_buf: sf::InPointerBuffer) => ();
+        rename_directory [6]: (old_path_buf: sf::InPointerBuffer, new_path_buf: sf::InPointerBuffer) => ();
+        get_entry_type [7]: (path_buf: sf::InPointerBuffer) => (entry_type: DirectoryEntryType);
+        open_file [8]: (mode: FileOpenMode, path_buf: sf::InPointerBuffer) => (file: mem::Shared<dyn sf::IObject> as mem::Shared<File>);
+        open_directory [9]: (mode: DirectoryOpenMode, path_buf: sf::InPointerBuffer) => (dir: mem::Shared<dyn sf::IObject> as mem::Shared<Directory>);
+        commit [10]: () => ();
+        get_free_space_size [11]: (path_buf: sf::InPointerBuffer) => (size: usize);
+        get_total_space_size [12]: (path_buf: sf::InPointerBuffer) => (size: usize);
+        clean_directory_recursively [13, (3, 0, 0) =>]: (path_buf: sf::InPointerBuffer) => ();
+        get_file_time_stamp_raw [14, (3, 0, 0) =>]: (path_buf: sf::InPointerBuffer) => (time_stamp: FileTimeStampRaw);
+    }
+}
+
+ipc_cmif_interface! {
+    IEventNotifier, EventNotifier {
+        get_event_handle [0]: () => (event_handle: sf::CopyHandle);
+    }
+}
+
+ipc_cmif_interface! {
+    IDeviceOperator, DeviceOperator {
+        is_sd_card_inserted [0]: () => (is_inserted: bool);
+    }
+}
+
+ipc_cmif_interface! {
+    IFileSystemProxy, FileSystemProxy {
+        set_current_process [1]: (process_id: sf::ProcessId) => ();
+        open_filesystem_with_patch [7, (2, 0, 0) =>]: (fs_type: FileSystemProxyType, program_id: u64) => (filesystem: mem::Shared<dyn sf::IObject> as mem::Shared<FileSystem>);
+        open_filesystem_with_id [8, (2, 0, 0) =>]: (fs_type: FileSystemProxyType, program_id: u64, path_buf: sf::InPointerBuffer) => (filesystem: mem::Shared<dyn sf::IObject> as mem::Shared<FileSystem>);
+        open_bis_storage [12]: (partition_id: BisPartitionId) => (bis_storage: mem::Shared<dyn sf::IObject> as mem::Shared<Storage>);
+        open_sd_card_filesystem [18]: () => (sd_filesystem: mem::Shared<dyn sf::IObject> as mem::Shared<FileSystem>);
+        open_game_card_storage [30]: (handle: GameCardHandle, partition: GameCardPartitionRaw) => (game_card_storage: mem::Shared<dyn sf::IObject> as mem::Shared<Storage>);
+        open_save_data_filesystem [51]: (save_data_space_id: SaveDataSpaceId, attribute: SaveDataAttribute) => (save_data_filesystem: mem::Shared<dyn sf::IObject> as mem::Shared<FileSystem>);
+        open_save_data_filesystem_by_system_save_data_id [52]: (save_data_space_id: SaveDataSpaceId, attribute: SaveDataAttribute) => (save_data_filesystem: mem::Shared<dyn sf::IObject> as mem::Shared<FileSystem>);
+        open_data_storage_by_data_id [202]: (storage_id: StorageId, data_id: u64) => (data_storage: mem::Shared<dyn sf::IObject> as mem::Shared<Storage>);
+        open_device_operator [400]: () => (device_operator: mem::Shared<dyn sf::IObject> as mem::Shared<DeviceOperator>);
+        open_sd_card_detection_event_notifier [500]: () => (event_notifier: mem::Shared<dyn sf::IObject> as mem::Shared<EventNotifier>);
+        output_access_log_to_sd_card [1006]: (access_log: sf::InMapAliasBuffer) => ();
+    }
 }
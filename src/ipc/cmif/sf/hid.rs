@@ -33,16 +33,21 @@ pub enum ControllerId {
     Handheld = 0x20,
 }
 
-pub trait IAppletResource {
-    ipc_cmif_interface_define_command!(get_shared_memory_handle: () => (shmem_handle: sf::CopyHandle));
+ipc_cmif_interface! {
+    IAppletResource, AppletResource {
+        get_shared_memory_handle [0]: () => (shmem_handle: sf::CopyHandle);
+    }
 }
 
-pub trait IHidServer {
-    ipc_cmif_interface_define_command!(create_applet_resource: (aruid: sf::ProcessId) => (applet_resource: mem::Shared<dyn sf::IObject>));
-    ipc_cmif_interface_define_command!(set_supported_npad_style_set: (aruid: sf::ProcessId, npad_style_tag: NpadStyleTag) => ());
-    ipc_cmif_interface_define_command!(set_supported_npad_id_type: (aruid: sf::ProcessId, controllers: sf::InPointerBuffer) => ());
-    ipc_cmif_interface_define_command!(activate_npad: (aruid: sf::ProcessId) => ());
-    ipc_cmif_interface_define_command!(deactivate_npad: (aruid: sf::ProcessId) => ());
-    ipc_cmif_interface_define_command!(set_npad_joy_assignment_mode_single: (aruid: sf::ProcessId, controller: ControllerId, joy_type: NpadJoyDeviceType) => ());
-    ipc_cmif_interface_define_command!(set_npad_joy_assignment_mode_dual: (aruid: sf::ProcessId, controller: ControllerId) => ());
+ipc_cmif_interface! {
+    IHidServer, HidServer {
+        create_applet_resource [0]: (aruid: sf::ProcessId) => (applet_resource: mem::Shared<dyn sf::IObject> as mem::Shared<AppletResource>);
+        set_supported_npad_style_set [100]: (aruid: sf::ProcessId, npad_style_tag: NpadStyleTag) => () send (npad_style_tag, aruid);
+        set_supported_npad_id_type [102]: (aruid: sf::ProcessId, controllers: sf::InPointerBuffer) => ();
+        activate_npad [103]: (aruid: sf::ProcessId) => ();
+        deactivate_npad [104]: (aruid: sf::ProcessId) => ();
+        activate_npad_with_revision [109, (5, 0, 0) =>]: (aruid: sf::ProcessId, revision: u32) => () send (revision, aruid);
+        set_npad_joy_assignment_mode_single [123]: (aruid: sf::ProcessId, controller: ControllerId, joy_type: NpadJoyDeviceType) => () send (controller, aruid, joy_type);
+        set_npad_joy_assignment_mode_dual [124]: (aruid: sf::ProcessId, controller: ControllerId) => () send (controller, aruid);
+    }
 }
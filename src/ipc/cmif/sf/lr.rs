@@ -0,0 +1,22 @@
+use crate::{
+    ipc::cmif::{sf, sf::fspsrv},
+    mem,
+    result::*,
+};
+
+pub type Path = fspsrv::Path;
+
+ipc_cmif_interface! {
+    ILocationResolver, LocationResolver {
+        resolve_program_path [0]: (program_id: u64, out_path: sf::OutFixedPointerBuffer<Path>) => ();
+        resolve_application_control_path [2]: (program_id: u64, out_path: sf::OutFixedPointerBuffer<Path>) => ();
+        resolve_application_html_document_path [3]: (program_id: u64, out_path: sf::OutFixedPointerBuffer<Path>) => ();
+        resolve_data_path [4]: (data_id: u64, out_path: sf::OutFixedPointerBuffer<Path>) => ();
+    }
+}
+
+ipc_cmif_interface! {
+    ILocationResolverManager, LocationResolverManager {
+        open_location_resolver [0]: (storage_id: fspsrv::StorageId) => (location_resolver: mem::Shared<dyn sf::IObject> as mem::Shared<LocationResolver>);
+    }
+}
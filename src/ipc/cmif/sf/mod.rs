@@ -237,11 +237,17 @@ impl Session {
                     None,
                     DomainCommandType::Close,
                 );
-                let _ = svc::send_sync_request(self.object_info.handle);
+                let _ = transport::send_sync_request(
+                    self.object_info.transport_id,
+                    self.object_info.handle,
+                );
             } else if self.object_info.owns_handle {
                 let mut ctx = CommandContext::new_client(self.object_info);
                 client::write_close_command_on_ipc_buffer(&mut ctx);
-                let _ = svc::send_sync_request(self.object_info.handle);
+                let _ = transport::send_sync_request(
+                    self.object_info.transport_id,
+                    self.object_info.handle,
+                );
             }
             if self.object_info.owns_handle {
                 let _ = svc::close_handle(self.object_info.handle);
@@ -285,18 +291,7 @@ impl CommandMetadata {
     }
 
     pub fn validate_version(&self) -> bool {
-        let ver = version::get_version();
-        if let Some(min_v) = self.min_ver {
-            if ver < min_v {
-                return false;
-            }
-        }
-        if let Some(max_v) = self.max_ver {
-            if ver > max_v {
-                return false;
-            }
-        }
-        true
+        validate_version_range(self.min_ver, self.max_ver)
     }
 
     pub fn matches(&self, rq_id: u32) -> bool {
@@ -304,6 +299,37 @@ impl CommandMetadata {
     }
 }
 
+pub fn validate_version_range(
+    min_ver: Option<version::Version>,
+    max_ver: Option<version::Version>,
+) -> bool {
+    let ver = version::get_version();
+    if let Some(min_v) = min_ver {
+        if ver < min_v {
+            return false;
+        }
+    }
+    if let Some(max_v) = max_ver {
+        if ver > max_v {
+            return false;
+        }
+    }
+    true
+}
+
+pub type VersionedRequestId = (u32, Option<version::Version>, Option<version::Version>);
+
+// Commands may have different IDs depending on the firmware version (or not
+// exist at all in some versions), this picks the one for the current version
+pub fn select_request_id(rq_ids: &[VersionedRequestId]) -> Result<u32> {
+    for &(rq_id, min_ver, max_ver) in rq_ids {
+        if validate_version_range(min_ver, max_ver) {
+            return Ok(rq_id);
+        }
+    }
+    Err(results::lib::ipc::ResultUnsupportedFirmware::make())
+}
+
 // This trait is analogous to N's IServiceObject type - the base for any kind of
 // IPC interface IClientObject (on service module) and IServerObject (on server
 // module) are wrappers for some specific kind of objects
@@ -383,3 +409,77 @@ pub mod mii;
 pub mod set;
 
 pub mod spl;
+
+pub mod ro;
+
+pub mod lr;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::cmif::{
+        hid::{HidServer, IHidServer},
+        pm::DebugMonitorInterface,
+        IClientObject,
+    };
+
+    fn get_valid_request_ids(object: &dyn IObject) -> Vec<u32> {
+        object
+            .get_command_table()
+            .iter()
+            .filter(|command| command.validate_version())
+            .map(|command| command.rq_id)
+            .collect()
+    }
+
+    // The firmware version is global, so every check depending on it is done in
+    // a single test
+    #[test]
+    fn select_versioned_request_ids() {
+        let moved_rq_ids = [
+            ipc_cmif_interface_make_request_id!(5, => (4, 1, 0)),
+            ipc_cmif_interface_make_request_id!(4, (5, 0, 0) =>),
+        ];
+        let bounded_rq_ids = [ipc_cmif_interface_make_request_id!(
+            10,
+            (3, 0, 0) => (4, 1, 0)
+        )];
+        let debug_monitor = DebugMonitorInterface::new(Session::new());
+        let hid = HidServer::new(Session::new());
+
+        for &(version, moved_rq_id, bounded_rq_id) in &[
+            (version::Version::new(1, 0, 0), 5, None),
+            (version::Version::new(3, 0, 0), 5, Some(10)),
+            (version::Version::new(4, 1, 0), 5, Some(10)),
+            (version::Version::new(5, 0, 0), 4, None),
+            (version::Version::new(12, 1, 0), 4, None),
+        ] {
+            version::set_version(version);
+            assert_eq!(select_request_id(&[(7, None, None)]).unwrap(), 7);
+            assert_eq!(select_request_id(&moved_rq_ids).unwrap(), moved_rq_id);
+            match bounded_rq_id {
+                Some(rq_id) => assert_eq!(select_request_id(&bounded_rq_ids).unwrap(), rq_id),
+                None => assert!(results::lib::ipc::ResultUnsupportedFirmware::matches(
+                    select_request_id(&bounded_rq_ids).unwrap_err()
+                )),
+            };
+
+            // Interfaces only expose the entries valid for the current version
+            assert_eq!(get_valid_request_ids(&debug_monitor), vec![moved_rq_id]);
+            assert_eq!(
+                get_valid_request_ids(&hid).contains(&109),
+                version >= version::Version::new(5, 0, 0)
+            );
+        }
+
+        // Clients fail before sending anything (the session here is invalid)
+        version::set_version(version::Version::new(4, 1, 0));
+        let mut hid = hid;
+        assert!(results::lib::ipc::ResultUnsupportedFirmware::matches(
+            hid.activate_npad_with_revision(ProcessId::new(), 3)
+                .unwrap_err()
+        ));
+
+        version::set_version(version::Version::empty());
+    }
+}
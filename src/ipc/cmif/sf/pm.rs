@@ -1,10 +1,13 @@
 use crate::result::*;
-// use crate::ipc::cmif::sf;
 
-pub trait IInformationInterface {
-    ipc_cmif_interface_define_command!(get_program_id: (process_id: u64) => (program_id: u64));
+ipc_cmif_interface! {
+    IInformationInterface, InformationInterface {
+        get_program_id [0]: (process_id: u64) => (program_id: u64);
+    }
 }
 
-pub trait IDebugMonitorInterface {
-    ipc_cmif_interface_define_command!(get_application_process_id: () => (process_id: u64));
+ipc_cmif_interface! {
+    IDebugMonitorInterface, DebugMonitorInterface {
+        get_application_process_id [5, => (4, 1, 0)] [4, (5, 0, 0) =>]: () => (process_id: u64);
+    }
 }
@@ -0,0 +1,11 @@
+use crate::{ipc::cmif::sf, result::*};
+
+ipc_cmif_interface! {
+    IRoInterface, RoInterface, JitRoInterface {
+        map_manual_load_module_memory [0]: (process_id: sf::ProcessId, nro_address: u64, nro_size: u64, bss_address: u64, bss_size: u64) => (out_address: u64);
+        unmap_manual_load_module_memory [1]: (process_id: sf::ProcessId, nro_address: u64) => ();
+        register_module_info [2]: (process_id: sf::ProcessId, nrr_address: u64, nrr_size: u64) => ();
+        unregister_module_info [3]: (process_id: sf::ProcessId, nrr_address: u64) => ();
+        initialize [4]: (process_id: sf::ProcessId, process_handle: sf::CopyHandle) => ();
+    }
+}
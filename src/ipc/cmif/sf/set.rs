@@ -17,6 +17,12 @@ pub struct FirmwareVersion {
     pub display_title: util::CString<0x80>,
 }
 
+pub type LanguageCode = util::CString<0x8>;
+
+pub trait ISettingsServer {
+    ipc_cmif_interface_define_command!(get_language_code: () => (language_code: LanguageCode));
+}
+
 pub trait ISystemSettingsServer {
     ipc_cmif_interface_define_command!(get_firmware_version: (out_version: sf::OutFixedPointerBuffer<FirmwareVersion>) => ());
 }
@@ -1,5 +1,8 @@
 use crate::{
-    ipc::cmif::{sf, sf::applet},
+    ipc::cmif::{
+        sf,
+        sf::{applet, dispdrv},
+    },
     mem,
     result::*,
     util,
@@ -25,30 +28,36 @@ pub enum DisplayServiceMode {
     Privileged = 1,
 }
 
-pub trait IManagerDisplayService {
-    ipc_cmif_interface_define_command!(create_managed_layer: (flags: LayerFlags, display_id: DisplayId, aruid: applet::AppletResourceUserId) => (id: LayerId));
-    ipc_cmif_interface_define_command!(destroy_managed_layer: (id: LayerId) => ());
+ipc_cmif_interface! {
+    IManagerDisplayService, ManagerDisplayService {
+        create_managed_layer [2010]: (flags: LayerFlags, display_id: DisplayId, aruid: applet::AppletResourceUserId) => (id: LayerId);
+        destroy_managed_layer [2011]: (id: LayerId) => ();
+    }
 }
 
-pub trait ISystemDisplayService {
-    ipc_cmif_interface_define_command!(get_z_order_count_min: (display_id: DisplayId) => (z: i64));
-    ipc_cmif_interface_define_command!(get_z_order_count_max: (display_id: DisplayId) => (z: i64));
-    ipc_cmif_interface_define_command!(set_layer_position: (x: f32, y: f32, id: LayerId) => ());
-    ipc_cmif_interface_define_command!(set_layer_size: (id: LayerId, width: u64, height: u64) => ());
-    ipc_cmif_interface_define_command!(set_layer_z: (id: LayerId, z: i64) => ());
-    ipc_cmif_interface_define_command!(set_layer_visibility: (visible: bool, id: LayerId) => ());
+ipc_cmif_interface! {
+    ISystemDisplayService, SystemDisplayService {
+        get_z_order_count_min [1200]: (display_id: DisplayId) => (z: i64);
+        get_z_order_count_max [1202]: (display_id: DisplayId) => (z: i64);
+        set_layer_position [2201]: (x: f32, y: f32, id: LayerId) => ();
+        set_layer_size [2203]: (id: LayerId, width: u64, height: u64) => ();
+        set_layer_z [2205]: (id: LayerId, z: i64) => ();
+        set_layer_visibility [2207]: (visible: bool, id: LayerId) => ();
+    }
 }
 
-pub trait IApplicationDisplayService {
-    ipc_cmif_interface_define_command!(get_relay_service: () => (relay_service: mem::Shared<dyn sf::IObject>));
-    ipc_cmif_interface_define_command!(get_system_display_service: () => (relay_service: mem::Shared<dyn sf::IObject>));
-    ipc_cmif_interface_define_command!(get_manager_display_service: () => (relay_service: mem::Shared<dyn sf::IObject>));
-    ipc_cmif_interface_define_command!(open_display: (name: DisplayName) => (id: DisplayId));
-    ipc_cmif_interface_define_command!(close_display: (id: DisplayId) => ());
-    ipc_cmif_interface_define_command!(open_layer: (name: DisplayName, id: LayerId, aruid: sf::ProcessId, out_native_window: sf::OutMapAliasBuffer) => (native_window_size: usize));
-    ipc_cmif_interface_define_command!(create_stray_layer: (flags: LayerFlags, display_id: DisplayId, out_native_window: sf::OutMapAliasBuffer) => (id: LayerId, native_window_size: usize));
-    ipc_cmif_interface_define_command!(destroy_stray_layer: (id: LayerId) => ());
-    ipc_cmif_interface_define_command!(get_display_vsync_event: (id: DisplayId) => (event_handle: sf::CopyHandle));
+ipc_cmif_interface! {
+    IApplicationDisplayService, ApplicationDisplayService {
+        get_relay_service [100]: () => (relay_service: mem::Shared<dyn sf::IObject> as mem::Shared<dispdrv::HOSBinderDriver>);
+        get_system_display_service [101]: () => (relay_service: mem::Shared<dyn sf::IObject> as mem::Shared<SystemDisplayService>);
+        get_manager_display_service [102]: () => (relay_service: mem::Shared<dyn sf::IObject> as mem::Shared<ManagerDisplayService>);
+        open_display [1010]: (name: DisplayName) => (id: DisplayId);
+        close_display [1020]: (id: DisplayId) => ();
+        open_layer [2020]: (name: DisplayName, id: LayerId, aruid: sf::ProcessId, out_native_window: sf::OutMapAliasBuffer) => (native_window_size: usize);
+        create_stray_layer [2030]: (flags: LayerFlags, display_id: DisplayId, out_native_window: sf::OutMapAliasBuffer) => (id: LayerId, native_window_size: usize);
+        destroy_stray_layer [2031]: (id: LayerId) => ();
+        get_display_vsync_event [5202]: (id: DisplayId) => (event_handle: sf::CopyHandle);
+    }
 }
 
 pub trait IRootService {
@@ -0,0 +1,500 @@
+use super::*;
+use crate::{
+    diag::{log, log::Logger},
+    ipc::cmif,
+};
+use alloc::vec::Vec;
+use core::{fmt, slice};
+
+// Decoding of raw HIPC messages (plus their CMIF headers, when present) into
+// something printable, meant for inspecting what was actually sent/received
+// Decoding only looks at the given bytes, nothing is dereferenced or modified
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DomainHeader {
+    In(cmif::DomainInDataHeader),
+    Out(cmif::DomainOutDataHeader),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct DecodedMessage {
+    pub header: CommandHeader,
+    pub special_header: Option<CommandSpecialHeader>,
+    pub process_id: Option<u64>,
+    pub copy_handles: Vec<svc::Handle>,
+    pub move_handles: Vec<svc::Handle>,
+    pub send_statics: Vec<SendStaticDescriptor>,
+    pub send_buffers: Vec<BufferDescriptor>,
+    pub receive_buffers: Vec<BufferDescriptor>,
+    pub exchange_buffers: Vec<BufferDescriptor>,
+    pub receive_statics: Vec<ReceiveStaticDescriptor>,
+    pub data_words: Vec<u32>,
+    // Offset of the 16-byte aligned data (CMIF headers and raw data) inside the data words
+    pub data_offset: usize,
+    pub domain_header: Option<DomainHeader>,
+    pub domain_object_ids: Vec<cmif::DomainObjectId>,
+    pub data_header: Option<cmif::DataHeader>,
+}
+
+struct MessageReader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> MessageReader<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer, offset: 0 }
+    }
+
+    fn read<T: Copy>(&mut self) -> Result<T> {
+        let t = read_at::<T>(self.buffer, self.offset)?;
+        self.offset += core::mem::size_of::<T>();
+        Ok(t)
+    }
+
+    fn read_array<T: Copy>(&mut self, count: u32) -> Result<Vec<T>> {
+        let mut array: Vec<T> = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            array.push(self.read()?);
+        }
+        Ok(array)
+    }
+}
+
+fn read_at<T: Copy>(buffer: &[u8], offset: usize) -> Result<T> {
+    match offset.checked_add(core::mem::size_of::<T>()) {
+        Some(end_offset) if end_offset <= buffer.len() => unsafe {
+            Ok(ptr::read_unaligned(buffer.as_ptr().add(offset) as *const T))
+        },
+        _ => Err(results::hipc::ResultInvalidRequestSize::make()),
+    }
+}
+
+// Whether the data has a domain header in front can't be known from the message
+// itself, so it's guessed from where (if anywhere) the CMIF magic is found
+fn decode_cmif_data(message: &mut DecodedMessage, data: &[u8]) {
+    let is_data_header_magic = |magic: u32| {
+        (magic == cmif::IN_DATA_HEADER_MAGIC) || (magic == cmif::OUT_DATA_HEADER_MAGIC)
+    };
+    let domain_header_size = core::mem::size_of::<cmif::DomainInDataHeader>();
+
+    if let Ok(data_header) = read_at::<cmif::DataHeader>(data, 0) {
+        if is_data_header_magic(data_header.magic) {
+            message.data_header = Some(data_header);
+            return;
+        }
+    }
+
+    let data_header = match read_at::<cmif::DataHeader>(data, domain_header_size) {
+        Ok(data_header) if is_data_header_magic(data_header.magic) => Some(data_header),
+        _ => None,
+    };
+    match data_header {
+        Some(data_header) if data_header.magic == cmif::OUT_DATA_HEADER_MAGIC => {
+            if let Ok(domain_header) = read_at::<cmif::DomainOutDataHeader>(data, 0) {
+                // Out object IDs follow the raw data, whose size isn't known here
+                message.domain_header = Some(DomainHeader::Out(domain_header));
+                message.data_header = Some(data_header);
+            }
+        }
+        _ => {
+            // The domain command type is an enum, so it must be checked before reading
+            // the whole header
+            let is_valid_command_type = match data.first() {
+                Some(&command_type) => {
+                    (command_type == cmif::DomainCommandType::SendMessage as u8)
+                        || (command_type == cmif::DomainCommandType::Close as u8)
+                }
+                None => false,
+            };
+            if !is_valid_command_type {
+                return;
+            }
+
+            if let Ok(domain_header) = read_at::<cmif::DomainInDataHeader>(data, 0) {
+                // Domain close requests are the only ones without a data header
+                let is_close = (domain_header.command_type == cmif::DomainCommandType::Close)
+                    && (domain_header.data_size == 0);
+                if data_header.is_some() || is_close {
+                    let objects_offset = domain_header_size + domain_header.data_size as usize;
+                    for i in 0..domain_header.object_count as usize {
+                        match read_at::<cmif::DomainObjectId>(
+                            data,
+                            objects_offset + i * core::mem::size_of::<cmif::DomainObjectId>(),
+                        ) {
+                            Ok(domain_object_id) => {
+                                message.domain_object_ids.push(domain_object_id)
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                    message.domain_header = Some(DomainHeader::In(domain_header));
+                    message.data_header = data_header;
+                }
+            }
+        }
+    }
+}
+
+pub fn decode(buffer: &[u8]) -> Result<DecodedMessage> {
+    let mut reader = MessageReader::new(buffer);
+    let mut message: DecodedMessage = Default::default();
+
+    message.header = reader.read()?;
+    let mut copy_handle_count: u32 = 0;
+    let mut move_handle_count: u32 = 0;
+    if message.header.get_has_special_header() {
+        let special_header: CommandSpecialHeader = reader.read()?;
+        if special_header.get_send_process_id() {
+            message.process_id = Some(reader.read()?);
+        }
+        copy_handle_count = special_header.get_copy_handle_count();
+        move_handle_count = special_header.get_move_handle_count();
+        message.special_header = Some(special_header);
+    }
+
+    message.copy_handles = reader.read_array(copy_handle_count)?;
+    message.move_handles = reader.read_array(move_handle_count)?;
+    message.send_statics = reader.read_array(message.header.get_send_static_count())?;
+    message.send_buffers = reader.read_array(message.header.get_send_buffer_count())?;
+    message.receive_buffers = reader.read_array(message.header.get_receive_buffer_count())?;
+    message.exchange_buffers = reader.read_array(message.header.get_exchange_buffer_count())?;
+
+    let data_words_offset = reader.offset;
+    message.data_words = reader.read_array(message.header.get_data_word_count())?;
+
+    // A receive static count of 0xFF means a single one, sharing the server's
+    // pointer buffer
+    let receive_static_count = match message.header.get_receive_static_count() {
+        0xFF => 1,
+        count => count,
+    };
+    message.receive_statics = reader.read_array(receive_static_count)?;
+
+    let align = DATA_PADDING as usize - 1;
+    let data_offset = (data_words_offset + align) & !align;
+    message.data_offset = data_offset - data_words_offset;
+    let data_end_offset = data_words_offset + message.data_words.len() * 4;
+    if data_offset < data_end_offset {
+        decode_cmif_data(&mut message, &buffer[data_offset..data_end_offset]);
+    }
+
+    Ok(message)
+}
+
+fn get_command_type_name(command_type: u32) -> &'static str {
+    match cmif::convert_command_type(command_type) {
+        cmif::CommandType::Invalid => match command_type {
+            0 => "Invalid",
+            // TIPC messages encode the request ID (+ 16) as the command type
+            _ => "Tipc",
+        },
+        cmif::CommandType::LegacyRequest => "LegacyRequest",
+        cmif::CommandType::Close => "Close",
+        cmif::CommandType::LegacyControl => "LegacyControl",
+        cmif::CommandType::Request => "Request",
+        cmif::CommandType::Control => "Control",
+        cmif::CommandType::RequestWithContext => "RequestWithContext",
+        cmif::CommandType::ControlWithContext => "ControlWithContext",
+    }
+}
+
+fn write_buffer_descriptors(
+    f: &mut fmt::Formatter<'_>,
+    kind: &str,
+    descriptors: &[BufferDescriptor],
+) -> fmt::Result {
+    for (i, descriptor) in descriptors.iter().enumerate() {
+        writeln!(
+            f,
+            "  {}[{}]: address {:p}, size {:#X}, flags {}",
+            kind,
+            i,
+            descriptor.get_address(),
+            descriptor.get_size(),
+            read_bits!(0, 1, descriptor.bits)
+        )?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for DecodedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let command_type = self.header.get_command_type();
+        writeln!(
+            f,
+            "HIPC message: type {} ({}), {} data words",
+            command_type,
+            get_command_type_name(command_type),
+            self.header.get_data_word_count()
+        )?;
+
+        if let Some(special_header) = self.special_header {
+            writeln!(
+                f,
+                "  Special header: send process ID {}, {} copy handles, {} move handles",
+                special_header.get_send_process_id(),
+                special_header.get_copy_handle_count(),
+                special_header.get_move_handle_count()
+            )?;
+        }
+        if let Some(process_id) = self.process_id {
+            writeln!(f, "  Process ID: {:#X}", process_id)?;
+        }
+        for (i, handle) in self.copy_handles.iter().enumerate() {
+            writeln!(f, "  Copy handle[{}]: {:#X}", i, handle)?;
+        }
+        for (i, handle) in self.move_handles.iter().enumerate() {
+            writeln!(f, "  Move handle[{}]: {:#X}", i, handle)?;
+        }
+
+        for (i, descriptor) in self.send_statics.iter().enumerate() {
+            writeln!(
+                f,
+                "  Send static[{}]: index {}, address {:p}, size {:#X}",
+                i,
+                descriptor.get_index(),
+                descriptor.get_address(),
+                descriptor.get_size()
+            )?;
+        }
+        write_buffer_descriptors(f, "Send buffer", &self.send_buffers)?;
+        write_buffer_descriptors(f, "Receive buffer", &self.receive_buffers)?;
+        write_buffer_descriptors(f, "Exchange buffer", &self.exchange_buffers)?;
+        for (i, descriptor) in self.receive_statics.iter().enumerate() {
+            writeln!(
+                f,
+                "  Receive static[{}]: address {:p}, size {:#X}",
+                i,
+                descriptor.get_address(),
+                descriptor.get_size()
+            )?;
+        }
+
+        match self.domain_header {
+            Some(DomainHeader::In(domain_header)) => writeln!(
+                f,
+                "  Domain in header: type {:?}, object ID {}, data size {:#X}, {} objects {:?}",
+                domain_header.command_type,
+                domain_header.domain_object_id,
+                domain_header.data_size,
+                domain_header.object_count,
+                self.domain_object_ids
+            )?,
+            // Responses don't say whether they come from a domain object, this is just where the
+            // data header was found
+            Some(DomainHeader::Out(domain_header)) => writeln!(
+                f,
+                "  Domain out header (guessed): {} objects",
+                domain_header.out_object_count
+            )?,
+            None => {}
+        }
+        if let Some(data_header) = self.data_header {
+            let magic = data_header.magic.to_le_bytes();
+            write!(
+                f,
+                "  Data header: magic {}{}{}{}, version {}, ",
+                magic[0] as char,
+                magic[1] as char,
+                magic[2] as char,
+                magic[3] as char,
+                data_header.version
+            )?;
+            if data_header.magic == cmif::OUT_DATA_HEADER_MAGIC {
+                writeln!(f, "result {0} ({0:?})", ResultCode::new(data_header.value))?;
+            } else {
+                writeln!(f, "request ID {}", data_header.value)?;
+            }
+        }
+
+        write!(
+            f,
+            "  Data words (aligned data at word {}):",
+            self.data_offset / 4
+        )?;
+        for (i, word) in self.data_words.iter().enumerate() {
+            if (i % 4) == 0 {
+                write!(f, "\n    {:#05X}:", i * 4)?;
+            }
+            write!(f, " {:08X}", word)?;
+        }
+        writeln!(f)
+    }
+}
+
+// Logs the given message, or why it couldn't be decoded
+pub fn log_message<L: Logger>(buffer: &[u8]) {
+    match decode(buffer) {
+        Ok(message) => {
+            diag_log!(L { log::LogSeverity::Trace, true } => "{}", message);
+        }
+        Err(rc) => {
+            diag_log!(L { log::LogSeverity::Error, true } => "Unable to decode HIPC message: {0} ({0:?})\n", rc);
+        }
+    }
+}
+
+// Logs whatever is currently in this thread's TLS message buffer
+pub fn log_ipc_buffer<L: Logger>() {
+    let buffer =
+        unsafe { slice::from_raw_parts(get_ipc_buffer() as *const u8, MESSAGE_BUFFER_SIZE) };
+    log_message::<L>(buffer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::cmif::{client, server};
+
+    const REQUEST_ID: u32 = 5;
+    const RAW_DATA: u64 = 0x1122334455667788;
+
+    fn write_request(buffer: &mut [u8; MESSAGE_BUFFER_SIZE], object_info: cmif::ObjectInfo) {
+        let mut ctx = cmif::CommandContext::new_server(
+            object_info,
+            core::ptr::null_mut(),
+            buffer.as_mut_ptr(),
+        );
+        ctx.in_params.send_process_id = true;
+        ctx.in_params.add_copy_handle(0x1234).unwrap();
+        if object_info.is_domain() {
+            ctx.in_params.add_domain_object(9).unwrap();
+        }
+        ctx.in_params.data_size = core::mem::size_of::<u64>() as u32;
+        client::write_request_command_on_ipc_buffer(
+            &mut ctx,
+            Some(REQUEST_ID),
+            cmif::DomainCommandType::SendMessage,
+        );
+        unsafe {
+            ptr::write_unaligned(ctx.in_params.data_offset as *mut u64, RAW_DATA);
+        }
+    }
+
+    // Command header, special header, process ID and the copy handle
+    fn get_data_words_offset() -> usize {
+        core::mem::size_of::<CommandHeader>()
+            + core::mem::size_of::<CommandSpecialHeader>()
+            + core::mem::size_of::<u64>()
+            + core::mem::size_of::<svc::Handle>()
+    }
+
+    fn read_raw_data(buffer: &[u8], message: &DecodedMessage, headers_size: usize) -> u64 {
+        read_at(
+            buffer,
+            get_data_words_offset() + message.data_offset + headers_size,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn decode_request() {
+        let mut buffer = [0u8; MESSAGE_BUFFER_SIZE];
+        write_request(&mut buffer, cmif::ObjectInfo::from_handle(0xCAFE));
+
+        let message = decode(&buffer).unwrap();
+        assert_eq!(
+            message.header.get_command_type(),
+            cmif::CommandType::Request as u32
+        );
+        assert!(message.special_header.unwrap().get_send_process_id());
+        assert_eq!(message.process_id, Some(0));
+        assert_eq!(message.copy_handles, [0x1234]);
+        assert!(message.move_handles.is_empty());
+        assert_eq!(message.domain_header, None);
+
+        let data_header = message.data_header.unwrap();
+        assert_eq!(data_header.magic, cmif::IN_DATA_HEADER_MAGIC);
+        assert_eq!(data_header.value, REQUEST_ID);
+        assert_eq!(
+            read_raw_data(&buffer, &message, core::mem::size_of::<cmif::DataHeader>()),
+            RAW_DATA
+        );
+    }
+
+    #[test]
+    fn decode_domain_request() {
+        let mut buffer = [0u8; MESSAGE_BUFFER_SIZE];
+        write_request(
+            &mut buffer,
+            cmif::ObjectInfo::from_domain_object_id(0xCAFE, 7),
+        );
+
+        let message = decode(&buffer).unwrap();
+        match message.domain_header {
+            Some(DomainHeader::In(domain_header)) => {
+                assert_eq!(
+                    domain_header.command_type,
+                    cmif::DomainCommandType::SendMessage
+                );
+                assert_eq!(domain_header.domain_object_id, 7);
+                assert_eq!(domain_header.object_count, 1);
+            }
+            domain_header => panic!("unexpected domain header: {:?}", domain_header),
+        }
+        assert_eq!(message.domain_object_ids, [9]);
+
+        let data_header = message.data_header.unwrap();
+        assert_eq!(data_header.magic, cmif::IN_DATA_HEADER_MAGIC);
+        assert_eq!(data_header.value, REQUEST_ID);
+        assert_eq!(
+            read_raw_data(
+                &buffer,
+                &message,
+                core::mem::size_of::<cmif::DomainInDataHeader>()
+                    + core::mem::size_of::<cmif::DataHeader>()
+            ),
+            RAW_DATA
+        );
+    }
+
+    #[test]
+    fn decode_domain_response() {
+        let mut buffer = [0u8; MESSAGE_BUFFER_SIZE];
+        let mut ctx = cmif::CommandContext::new_server(
+            cmif::ObjectInfo::from_domain_object_id(0xCAFE, 7),
+            core::ptr::null_mut(),
+            buffer.as_mut_ptr(),
+        );
+        ctx.out_params.push_domain_object(3).unwrap();
+        server::write_request_command_response_on_ipc_buffer(
+            &mut ctx,
+            results::hipc::ResultSessionClosed::make(),
+            cmif::CommandType::Request,
+        );
+
+        let message = decode(&buffer).unwrap();
+        match message.domain_header {
+            Some(DomainHeader::Out(domain_header)) => {
+                assert_eq!(domain_header.out_object_count, 1)
+            }
+            domain_header => panic!("unexpected domain header: {:?}", domain_header),
+        }
+
+        let data_header = message.data_header.unwrap();
+        assert_eq!(data_header.magic, cmif::OUT_DATA_HEADER_MAGIC);
+        assert_eq!(
+            data_header.value,
+            results::hipc::ResultSessionClosed::make().get_value()
+        );
+    }
+
+    #[test]
+    fn decode_truncated_message() {
+        let mut buffer = [0u8; MESSAGE_BUFFER_SIZE];
+        write_request(&mut buffer, cmif::ObjectInfo::from_handle(0xCAFE));
+
+        let message = decode(&buffer).unwrap();
+        let message_size =
+            get_data_words_offset() + message.data_words.len() * core::mem::size_of::<u32>();
+        assert!(decode(&buffer[..message_size]).is_ok());
+
+        // Cut in the middle of every part of the message
+        for &size in &[0, 4, 12, 20, message_size - 1] {
+            assert!(results::hipc::ResultInvalidRequestSize::matches(
+                decode(&buffer[..size]).unwrap_err()
+            ));
+        }
+    }
+}
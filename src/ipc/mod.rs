@@ -112,6 +112,10 @@ impl SendStaticDescriptor {
     pub const fn get_size(&self) -> usize {
         read_bits!(16, 31, self.bits) as usize
     }
+
+    pub const fn get_index(&self) -> u32 {
+        read_bits!(0, 5, self.bits)
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
@@ -297,6 +301,9 @@ impl CommandSpecialHeader {
 
 pub const DATA_PADDING: u32 = 16;
 
+// Size of the TLS message buffer (and the maximum size of any message)
+pub const MESSAGE_BUFFER_SIZE: usize = 0x100;
+
 bit_enum! {
     BufferAttribute (u8) {
         In = bit!(0),
@@ -340,6 +347,14 @@ impl DataWalker {
         self.cur_offset += core::mem::size_of::<T>() as isize;
     }
 
+    // Offset right after the next T, without advancing
+    pub fn get_end_offset<T>(&self) -> isize {
+        let align_of_type = core::mem::align_of::<T>() as isize;
+        let mut offset = self.cur_offset + align_of_type - 1;
+        offset -= offset % align_of_type;
+        offset + core::mem::size_of::<T>() as isize
+    }
+
     pub fn advance_get<T>(&mut self) -> T {
         unsafe {
             let align_of_type = core::mem::align_of::<T>() as isize;
@@ -423,3 +438,7 @@ pub fn get_aligned_data_offset(data_words_offset: *mut u8, base_offset: *mut u8)
 pub mod cmif;
 
 pub mod tipc;
+
+pub mod transport;
+
+pub mod debug;
@@ -6,7 +6,7 @@ use core::mem as cmem;
 pub fn write_command_on_ipc_buffer(ctx: &mut CommandContext, command_type: u32, data_size: u32) {
     unsafe {
         // TODO: in move handles are allowed?
-        let mut ipc_buf = get_ipc_buffer();
+        let mut ipc_buf = ctx.message_buffer;
 
         let has_special_header = ctx.in_params.send_process_id
             || !ctx.in_params.copy_handles.is_empty()
@@ -68,7 +68,7 @@ pub fn write_command_on_ipc_buffer(ctx: &mut CommandContext, command_type: u32,
 #[inline(always)]
 pub fn read_command_response_from_ipc_buffer(ctx: &mut CommandContext) {
     unsafe {
-        let mut ipc_buf = get_ipc_buffer();
+        let mut ipc_buf = ctx.message_buffer;
 
         let command_header = ipc_buf as *mut CommandHeader;
         ipc_buf = command_header.offset(1) as *mut u8;
@@ -13,6 +13,7 @@ pub enum CommandType {
 pub struct ObjectInfo {
     pub handle: svc::Handle,
     pub owns_handle: bool,
+    pub transport_id: transport::TransportId,
 }
 
 impl ObjectInfo {
@@ -20,6 +21,7 @@ impl ObjectInfo {
         Self {
             handle: 0,
             owns_handle: false,
+            transport_id: transport::KERNEL_TRANSPORT_ID,
         }
     }
 
@@ -27,6 +29,20 @@ impl ObjectInfo {
         Self {
             handle,
             owns_handle: true,
+            transport_id: transport::KERNEL_TRANSPORT_ID,
+        }
+    }
+
+    // Sessions going through an in-process transport, which never own their
+    // (fake) handles
+    pub const fn from_transport_handle(
+        handle: svc::Handle,
+        transport_id: transport::TransportId,
+    ) -> Self {
+        Self {
+            handle,
+            owns_handle: false,
+            transport_id,
         }
     }
 
@@ -39,6 +55,7 @@ impl ObjectInfo {
             handle: self.handle,
             domain_object_id: 0,
             owns_handle: self.owns_handle,
+            transport_id: self.transport_id,
         }
     }
 }
@@ -188,6 +205,7 @@ pub struct CommandContext {
     pub object_info: ObjectInfo,
     pub in_params: CommandIn,
     pub out_params: CommandOut,
+    message_buffer: *mut u8,
     send_buffers: ArrayVec<[BufferDescriptor; MAX_COUNT]>,
     receive_buffers: ArrayVec<[BufferDescriptor; MAX_COUNT]>,
     exchange_buffers: ArrayVec<[BufferDescriptor; MAX_COUNT]>,
@@ -199,6 +217,7 @@ impl CommandContext {
             object_info: ObjectInfo::new(),
             in_params: CommandIn::empty(),
             out_params: CommandOut::empty(),
+            message_buffer: ptr::null_mut(),
             send_buffers: ArrayVec::new(),
             receive_buffers: ArrayVec::new(),
             exchange_buffers: ArrayVec::new(),
@@ -208,9 +227,21 @@ impl CommandContext {
     pub fn new_client(object_info: ObjectInfo) -> Self {
         let mut ctx = Self::empty();
         ctx.object_info = object_info;
+        ctx.message_buffer = transport::get_message_buffer(object_info.transport_id);
         ctx
     }
 
+    pub fn new_server(object_info: ObjectInfo, message_buffer: *mut u8) -> Self {
+        let mut ctx = Self::empty();
+        ctx.object_info = object_info;
+        ctx.message_buffer = message_buffer;
+        ctx
+    }
+
+    pub fn get_message_buffer(&self) -> *mut u8 {
+        self.message_buffer
+    }
+
     pub fn add_send_buffer(&mut self, send_buffer: BufferDescriptor) -> Result<()> {
         match self.send_buffers.try_push(send_buffer) {
             Ok(()) => Ok(()),
@@ -33,7 +33,7 @@ impl<'a> ServerContext<'a> {
 #[inline(always)]
 pub fn read_command_from_ipc_buffer(ctx: &mut CommandContext) -> u32 {
     unsafe {
-        let mut ipc_buf = get_ipc_buffer();
+        let mut ipc_buf = ctx.message_buffer;
 
         let command_header = ipc_buf as *mut CommandHeader;
         ipc_buf = command_header.offset(1) as *mut u8;
@@ -80,7 +80,7 @@ pub fn write_command_response_on_ipc_buffer(
     data_size: u32,
 ) {
     unsafe {
-        let mut ipc_buf = get_ipc_buffer();
+        let mut ipc_buf = ctx.message_buffer;
 
         let command_header = ipc_buf as *mut CommandHeader;
         ipc_buf = command_header.offset(1) as *mut u8;
@@ -142,10 +142,9 @@ pub fn write_command_response_on_ipc_buffer(
 
 #[inline(always)]
 pub fn read_request_command_from_ipc_buffer(ctx: &mut CommandContext) -> Result<()> {
-    let ipc_buf = get_ipc_buffer();
-    let data_offset = get_aligned_data_offset(ctx.in_params.data_words_offset, ipc_buf);
-
-    ctx.in_params.data_offset = data_offset;
+    // Unlike CMIF, TIPC data starts right after the descriptors (no padding or
+    // data header)
+    ctx.in_params.data_offset = ctx.in_params.data_words_offset;
     Ok(())
 }
 
@@ -156,14 +155,11 @@ pub fn write_request_command_response_on_ipc_buffer(
     request_type: u32,
 ) {
     unsafe {
-        let ipc_buf = get_ipc_buffer();
-        let mut data_size =
-            DATA_PADDING + cmem::size_of::<DataHeader>() as u32 + ctx.out_params.data_size;
-        data_size = (data_size + 1) & !1;
+        // The result is the first data word, followed by the output data
+        let data_size = cmem::size_of::<ResultCode>() as u32 + ctx.out_params.data_size;
 
         write_command_response_on_ipc_buffer(ctx, request_type, data_size);
-        let data_offset = get_aligned_data_offset(ctx.out_params.data_words_offset, ipc_buf);
-        let rc_ref = data_offset as *mut ResultCode;
+        let rc_ref = ctx.out_params.data_words_offset as *mut ResultCode;
         *rc_ref = result;
 
         ctx.out_params.data_offset = rc_ref.offset(1) as *mut u8;
@@ -231,9 +227,7 @@ impl<const M: HandleMode> CommandParameter<sf::Handle<M>> for sf::Handle<M> {
 impl CommandParameter<sf::ProcessId> for sf::ProcessId {
     fn after_request_read(ctx: &mut ServerContext) -> Result<Self> {
         if ctx.ctx.in_params.send_process_id {
-            // TODO: is this really how process ID works? (is the in raw u64 just
-            // placeholder data?)
-            let _ = ctx.raw_data_walker.advance_get::<u64>();
+            // In TIPC, process IDs do not have placeholder space in raw data
             Ok(sf::ProcessId::from(ctx.ctx.in_params.process_id))
         } else {
             Err(results::hipc::ResultUnsupportedOperation::make())
@@ -474,3 +468,126 @@ pub trait INamedPort: IServerObject {
 }
 
 // TODO: implement mitms, implement ServerManager, merge it with cmif one...?
+
+// First fake handle given to loopback sessions, meant to never collide with
+// actual kernel handles
+const LOOPBACK_HANDLE_BASE: svc::Handle = 0x7F000000;
+
+// In-process transport for TIPC sessions, analogous to the CMIF one: requests
+// are handled right away on the transport's message buffer by the object
+// behind each fake handle. Objects returned by commands aren't supported (they
+// would need actual kernel sessions)
+pub struct LoopbackTransport {
+    message_buffer: [u8; MESSAGE_BUFFER_SIZE],
+    server_holders: Vec<ServerHolder>,
+    next_handle: svc::Handle,
+    transport_id: transport::TransportId,
+}
+
+impl LoopbackTransport {
+    // Transports are registered by address, hence the shared (boxed) object
+    pub fn new() -> mem::Shared<Self> {
+        let mut loopback = mem::Shared::new(Self {
+            message_buffer: [0; MESSAGE_BUFFER_SIZE],
+            server_holders: Vec::new(),
+            next_handle: LOOPBACK_HANDLE_BASE,
+            transport_id: transport::KERNEL_TRANSPORT_ID,
+        });
+
+        let transport_ptr: *mut dyn transport::Transport = loopback.get();
+        loopback.get().transport_id = transport::register_transport(transport_ptr);
+        loopback
+    }
+
+    pub fn get_transport_id(&self) -> transport::TransportId {
+        self.transport_id
+    }
+
+    // The returned sessions don't own their fake handles, so nothing is ever
+    // closed through the kernel for them
+    pub fn open_session<S: IServerObject + 'static>(&mut self) -> sf::Session {
+        self.open_object_session(mem::Shared::new(S::new()))
+    }
+
+    pub fn open_object_session(&mut self, object: mem::Shared<dyn sf::IObject>) -> sf::Session {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+
+        let mut server_holder = ServerHolder::new_session(handle, object);
+        server_holder.info.owns_handle = false;
+        self.server_holders.push(server_holder);
+        sf::Session::from(ObjectInfo::from_transport_handle(handle, self.transport_id))
+    }
+
+    fn handle_request_command(
+        ctx: &mut CommandContext,
+        server: mem::Shared<dyn sf::IObject>,
+        rq_id: u32,
+    ) -> Result<()> {
+        read_request_command_from_ipc_buffer(ctx)?;
+
+        let command = match server
+            .get()
+            .get_command_table()
+            .into_iter()
+            .find(|command| command.matches(rq_id))
+        {
+            Some(command) => command,
+            None => return Err(results::cmif::ResultInvalidCommandRequestId::make()),
+        };
+
+        let mut new_sessions: Vec<ServerHolder> = Vec::new();
+        let mut server_ctx = ServerContext::new(ctx, DataWalker::empty(), &mut new_sessions);
+        server
+            .get()
+            .call_self_command(command.command_fn, &mut server_ctx)?;
+        result_return_unless!(
+            new_sessions.is_empty(),
+            results::hipc::ResultUnsupportedOperation
+        );
+        Ok(())
+    }
+}
+
+impl Drop for LoopbackTransport {
+    fn drop(&mut self) {
+        transport::unregister_transport(self.transport_id);
+    }
+}
+
+impl transport::Transport for LoopbackTransport {
+    fn get_message_buffer(&mut self) -> *mut u8 {
+        self.message_buffer.as_mut_ptr()
+    }
+
+    fn send_sync_request(&mut self, handle: svc::Handle) -> Result<()> {
+        let index = match self
+            .server_holders
+            .iter()
+            .position(|server_holder| server_holder.info.handle == handle)
+        {
+            Some(index) => index,
+            None => return Err(results::os::ResultInvalidHandle::make()),
+        };
+
+        let server_info = self.server_holders[index].info;
+        let server = self.server_holders[index].server.clone();
+        let mut ctx = CommandContext::new_server(server_info, self.message_buffer.as_mut_ptr());
+        let command_type = read_command_from_ipc_buffer(&mut ctx);
+        if command_type == CommandType::CloseSession as u32 {
+            write_close_command_response_on_ipc_buffer(&mut ctx);
+            self.server_holders.remove(index);
+            return Ok(());
+        }
+
+        // TIPC request IDs are sent as the command type itself
+        let rq_id = command_type.wrapping_sub(16);
+        if let Err(rc) = Self::handle_request_command(&mut ctx, server, rq_id) {
+            // Written on a fresh context, since the request one might be partially filled
+            let mut error_ctx =
+                CommandContext::new_server(server_info, self.message_buffer.as_mut_ptr());
+            write_request_command_response_on_ipc_buffer(&mut error_ctx, rc, command_type);
+        }
+        Ok(())
+    }
+}
@@ -190,7 +190,10 @@ impl Session {
             if self.object_info.owns_handle {
                 let mut ctx = CommandContext::new_client(self.object_info);
                 client::write_close_command_on_ipc_buffer(&mut ctx);
-                let _ = svc::send_sync_request(self.object_info.handle);
+                let _ = transport::send_sync_request(
+                    self.object_info.transport_id,
+                    self.object_info.handle,
+                );
                 let _ = svc::close_handle(self.object_info.handle);
             }
             self.object_info = ObjectInfo::new();
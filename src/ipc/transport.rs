@@ -0,0 +1,82 @@
+use crate::{ipc, result::*, results, svc, sync};
+use alloc::vec::Vec;
+
+// A transport owns the message buffer requests and responses are encoded on,
+// and the step which actually delivers a request to the other side
+// Every CMIF session carries the ID of the transport it goes through, so that
+// in-process transports only affect their own sessions (everything else,
+// servers included, keeps using the kernel and the current thread's TLS buffer)
+
+pub trait Transport {
+    fn get_message_buffer(&mut self) -> *mut u8;
+    fn send_sync_request(&mut self, handle: svc::Handle) -> Result<()>;
+}
+
+pub type TransportId = u32;
+
+pub const KERNEL_TRANSPORT_ID: TransportId = 0;
+
+// Default transport: the current thread's TLS message buffer and the kernel
+pub struct KernelTransport;
+
+impl Transport for KernelTransport {
+    fn get_message_buffer(&mut self) -> *mut u8 {
+        ipc::get_ipc_buffer()
+    }
+
+    fn send_sync_request(&mut self, handle: svc::Handle) -> Result<()> {
+        svc::send_sync_request(handle)
+    }
+}
+
+// Registered transports aren't owned here: they must unregister themselves
+// before being dropped (sessions left using them will just fail to send)
+static mut G_TRANSPORTS: sync::Locked<Vec<(TransportId, *mut dyn Transport)>> =
+    sync::Locked::new(false, Vec::new());
+static mut G_NEXT_TRANSPORT_ID: sync::Locked<TransportId> =
+    sync::Locked::new(false, KERNEL_TRANSPORT_ID + 1);
+
+pub fn register_transport(transport: *mut dyn Transport) -> TransportId {
+    unsafe {
+        let transport_id = *G_NEXT_TRANSPORT_ID.get();
+        G_NEXT_TRANSPORT_ID.set(transport_id + 1);
+        G_TRANSPORTS.get().push((transport_id, transport));
+        transport_id
+    }
+}
+
+pub fn unregister_transport(transport_id: TransportId) {
+    unsafe {
+        G_TRANSPORTS.get().retain(|&(id, _)| id != transport_id);
+    }
+}
+
+fn find_transport(transport_id: TransportId) -> Option<*mut dyn Transport> {
+    unsafe {
+        G_TRANSPORTS
+            .get()
+            .iter()
+            .find(|&&(id, _)| id == transport_id)
+            .map(|&(_, transport)| transport)
+    }
+}
+
+#[inline(always)]
+pub fn get_message_buffer(transport_id: TransportId) -> *mut u8 {
+    match find_transport(transport_id) {
+        Some(transport) => unsafe { (*transport).get_message_buffer() },
+        None => KernelTransport.get_message_buffer(),
+    }
+}
+
+#[inline(always)]
+pub fn send_sync_request(transport_id: TransportId, handle: svc::Handle) -> Result<()> {
+    if transport_id == KERNEL_TRANSPORT_ID {
+        return KernelTransport.send_sync_request(handle);
+    }
+
+    match find_transport(transport_id) {
+        Some(transport) => unsafe { (*transport).send_sync_request(handle) },
+        None => Err(results::hipc::ResultSessionClosed::make()),
+    }
+}
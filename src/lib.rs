@@ -77,8 +77,20 @@ pub mod arm;
 
 pub mod wait;
 
+pub mod io;
+
 pub mod fs;
 
+pub mod nro;
+
+pub mod nacp;
+
+pub mod nso;
+
+pub mod lz4;
+
+pub mod crypto;
+
 pub mod version;
 
 pub use paste;
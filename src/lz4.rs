@@ -0,0 +1,251 @@
+use crate::{result::*, results};
+use alloc::vec::Vec;
+use core::cmp;
+
+// LZ4 block format (no frame headers), as used by NSO segments
+
+const MIN_MATCH_LEN: usize = 4;
+const LAST_LITERALS_LEN: usize = 5;
+const MATCH_FIND_LIMIT: usize = 12;
+const MAX_OFFSET: usize = 0xFFFF;
+const LEN_MASK: usize = 0xF;
+const HASH_LOG: u32 = 12;
+
+fn read_len_extension(src: &[u8], src_offset: &mut usize, base_len: usize) -> Result<usize> {
+    let mut len = base_len;
+    if base_len == LEN_MASK {
+        loop {
+            result_return_unless!(
+                *src_offset < src.len(),
+                results::lib::lz4::ResultInvalidBlock
+            );
+            let byte = src[*src_offset];
+            *src_offset += 1;
+            len += byte as usize;
+            if byte != 0xFF {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+// Returns the decompressed size
+pub fn decompress_block(src: &[u8], dst: &mut [u8]) -> Result<usize> {
+    let mut src_offset = 0;
+    let mut dst_offset = 0;
+    while src_offset < src.len() {
+        let token = src[src_offset] as usize;
+        src_offset += 1;
+
+        let literals_len = read_len_extension(src, &mut src_offset, token >> 4)?;
+        result_return_unless!(
+            literals_len <= (src.len() - src_offset),
+            results::lib::lz4::ResultInvalidBlock
+        );
+        result_return_unless!(
+            literals_len <= (dst.len() - dst_offset),
+            results::lib::lz4::ResultOutputTooSmall
+        );
+        dst[dst_offset..dst_offset + literals_len]
+            .copy_from_slice(&src[src_offset..src_offset + literals_len]);
+        src_offset += literals_len;
+        dst_offset += literals_len;
+
+        // The last sequence only has literals
+        if src_offset == src.len() {
+            break;
+        }
+
+        result_return_unless!(
+            (src.len() - src_offset) >= 2,
+            results::lib::lz4::ResultInvalidBlock
+        );
+        let match_offset = u16::from_le_bytes([src[src_offset], src[src_offset + 1]]) as usize;
+        src_offset += 2;
+        result_return_unless!(
+            (match_offset != 0) && (match_offset <= dst_offset),
+            results::lib::lz4::ResultInvalidBlock
+        );
+
+        let match_len = read_len_extension(src, &mut src_offset, token & LEN_MASK)? + MIN_MATCH_LEN;
+        result_return_unless!(
+            match_len <= (dst.len() - dst_offset),
+            results::lib::lz4::ResultOutputTooSmall
+        );
+
+        // Matches may overlap with the output being written, so copy bytewise
+        let match_start = dst_offset - match_offset;
+        for i in 0..match_len {
+            dst[dst_offset + i] = dst[match_start + i];
+        }
+        dst_offset += match_len;
+    }
+    Ok(dst_offset)
+}
+
+fn write_len_extension(dst: &mut Vec<u8>, len: usize) {
+    if len >= LEN_MASK {
+        let mut remaining_len = len - LEN_MASK;
+        while remaining_len >= 0xFF {
+            dst.push(0xFF);
+            remaining_len -= 0xFF;
+        }
+        dst.push(remaining_len as u8);
+    }
+}
+
+fn write_sequence(dst: &mut Vec<u8>, literals: &[u8], match_info: Option<(usize, usize)>) {
+    let literals_token = cmp::min(literals.len(), LEN_MASK);
+    let match_token = match match_info {
+        Some((_, match_len)) => cmp::min(match_len - MIN_MATCH_LEN, LEN_MASK),
+        None => 0,
+    };
+    dst.push(((literals_token << 4) | match_token) as u8);
+    write_len_extension(dst, literals.len());
+    dst.extend_from_slice(literals);
+
+    if let Some((match_offset, match_len)) = match_info {
+        dst.extend_from_slice(&(match_offset as u16).to_le_bytes());
+        write_len_extension(dst, match_len - MIN_MATCH_LEN);
+    }
+}
+
+fn read_u32(src: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        src[offset],
+        src[offset + 1],
+        src[offset + 2],
+        src[offset + 3],
+    ])
+}
+
+fn hash_sequence(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+// Simple greedy compressor, meant for tooling rather than compression ratio
+pub fn compress_block(src: &[u8]) -> Vec<u8> {
+    let mut dst: Vec<u8> = Vec::new();
+    let mut hash_table = vec![usize::MAX; 1 << HASH_LOG];
+    let match_end_limit = src.len().saturating_sub(LAST_LITERALS_LEN);
+    let match_start_limit = src.len().saturating_sub(MATCH_FIND_LIMIT);
+
+    let mut anchor = 0;
+    let mut offset = 0;
+    while offset < match_start_limit {
+        let sequence = read_u32(src, offset);
+        let hash = hash_sequence(sequence);
+        let candidate = hash_table[hash];
+        hash_table[hash] = offset;
+
+        if (candidate != usize::MAX)
+            && ((offset - candidate) <= MAX_OFFSET)
+            && (read_u32(src, candidate) == sequence)
+        {
+            let mut match_len = MIN_MATCH_LEN;
+            while ((offset + match_len) < match_end_limit)
+                && (src[candidate + match_len] == src[offset + match_len])
+            {
+                match_len += 1;
+            }
+
+            write_sequence(
+                &mut dst,
+                &src[anchor..offset],
+                Some((offset - candidate, match_len)),
+            );
+            offset += match_len;
+            anchor = offset;
+        } else {
+            offset += 1;
+        }
+    }
+
+    write_sequence(&mut dst, &src[anchor..], None);
+    dst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) {
+        let compressed = compress_block(data);
+        let mut decompressed = vec![0u8; data.len()];
+        assert_eq!(
+            decompress_block(&compressed, &mut decompressed).unwrap(),
+            data.len()
+        );
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn round_trip_small() {
+        round_trip(b"");
+        round_trip(b"a");
+        round_trip(b"abcdefghijkl");
+        round_trip(b"abcdabcdabcdabcdabcd");
+    }
+
+    #[test]
+    fn round_trip_long_runs() {
+        // Runs this long need several length extension bytes
+        round_trip(&vec![0u8; 0x1000]);
+
+        let mut data: Vec<u8> = Vec::new();
+        for i in 0..0x2000usize {
+            data.push((i / 0x300) as u8);
+        }
+        round_trip(&data);
+    }
+
+    #[test]
+    fn round_trip_mixed() {
+        let mut data: Vec<u8> = Vec::new();
+        let mut state: u32 = 0x12345678;
+        for i in 0..0x4000usize {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            // Alternate between incompressible and repeated chunks
+            if ((i / 0x100) % 2) == 0 {
+                data.push((state >> 16) as u8);
+            } else {
+                data.push(data[i - 0x100]);
+            }
+        }
+        round_trip(&data);
+    }
+
+    #[test]
+    fn compresses_repeated_data() {
+        let data = vec![0xAAu8; 0x1000];
+        assert!(compress_block(&data).len() < 0x40);
+    }
+
+    #[test]
+    fn decompress_overlapping_match() {
+        // "ab" literal, then a match of 6 at offset 2 (overlapping its own output)
+        let block = [0x22, b'a', b'b', 0x02, 0x00, 0x00];
+        let mut decompressed = [0u8; 8];
+        assert_eq!(decompress_block(&block, &mut decompressed).unwrap(), 8);
+        assert_eq!(&decompressed, b"abababab");
+    }
+
+    #[test]
+    fn decompress_invalid_blocks() {
+        let mut decompressed = [0u8; 0x10];
+
+        // Literals past the end of the block
+        assert!(results::lib::lz4::ResultInvalidBlock::matches(
+            decompress_block(&[0x50, b'a'], &mut decompressed).unwrap_err()
+        ));
+        // Match offset pointing before the output start
+        assert!(results::lib::lz4::ResultInvalidBlock::matches(
+            decompress_block(&[0x10, b'a', 0x02, 0x00], &mut decompressed).unwrap_err()
+        ));
+        // Output too small for the match
+        assert!(results::lib::lz4::ResultOutputTooSmall::matches(
+            decompress_block(&[0x1F, b'a', 0x01, 0x00, 0x20], &mut decompressed).unwrap_err()
+        ));
+    }
+}
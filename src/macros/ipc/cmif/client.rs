@@ -25,7 +25,7 @@ macro_rules! ipc_cmif_client_send_request_command {
                 }
             )*
 
-            $crate::svc::send_sync_request($session.handle)?;
+            $crate::ipc::transport::send_sync_request($session.transport_id, $session.handle)?;
 
             $crate::ipc::cmif::client::read_request_command_response_from_ipc_buffer(&mut ctx)?;
 
@@ -63,7 +63,7 @@ macro_rules! ipc_cmif_client_send_control_command {
                 }
             )*
 
-            $crate::svc::send_sync_request($session.handle)?;
+            $crate::ipc::transport::send_sync_request($session.transport_id, $session.handle)?;
 
             $crate::ipc::cmif::client::read_control_command_response_from_ipc_buffer(&mut ctx)?;
 
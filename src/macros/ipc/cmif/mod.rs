@@ -85,3 +85,119 @@ macro_rules! ipc_cmif_interface_make_command_meta {
         }
     };
 }
+
+#[macro_export]
+macro_rules! ipc_cmif_interface_make_request_id {
+    ($id:expr) => {
+        ($id, None, None)
+    };
+    ($id:expr, ($major:expr, $minor:expr, $micro:expr) =>) => {
+        (
+            $id,
+            Some($crate::version::Version::new($major, $minor, $micro)),
+            None,
+        )
+    };
+    ($id:expr, => ($major:expr, $minor:expr, $micro:expr)) => {
+        (
+            $id,
+            None,
+            Some($crate::version::Version::new($major, $minor, $micro)),
+        )
+    };
+    ($id:expr, ($major_a:expr, $minor_a:expr, $micro_a:expr) => ($major_b:expr, $minor_b:expr, $micro_b:expr)) => {
+        (
+            $id,
+            Some($crate::version::Version::new($major_a, $minor_a, $micro_a)),
+            Some($crate::version::Version::new($major_b, $minor_b, $micro_b)),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! ipc_cmif_interface_client_type {
+    ($out_param_type:ty) => {
+        $out_param_type
+    };
+    ($out_param_type:ty, $out_client_type:ty) => {
+        $out_client_type
+    };
+}
+
+#[macro_export]
+macro_rules! ipc_cmif_interface_send_request_command {
+    ([$session:expr; $rq_id:expr] ( $( $in_param_name:ident ),* ) send ( $( $send_param_name:ident ),* ) => ( $( $out_param:tt )* )) => {
+        $crate::ipc_cmif_client_send_request_command!([$session; $rq_id] ( $( $send_param_name ),* ) => ( $( $out_param )* ))
+    };
+    ([$session:expr; $rq_id:expr] ( $( $in_param_name:ident ),* ) => ( $( $out_param:tt )* )) => {
+        $crate::ipc_cmif_client_send_request_command!([$session; $rq_id] ( $( $in_param_name ),* ) => ( $( $out_param )* ))
+    };
+}
+
+// Single-source interface definition: generates the trait (with the server-side command handlers and
+// a command table for server implementations) and the client type, all from the same IDs/versions
+// Commands look like "name [id, <version range like in ipc_cmif_interface_make_command_meta>]: (in) => (out);"
+// Commands whose ID changed across firmware versions can list several "[id, range]" entries
+// Returned objects can specify the client type they're received as ("obj: mem::Shared<dyn sf::IObject> as mem::Shared<Client>"),
+// and a trailing "send (...)" sets the order in params are sent in, when it differs from the declared one
+// Several client types can be listed (for services exposing the same interface under different names)
+// Clients fail with ResultUnsupportedFirmware when no entry is valid for the current version, without sending anything
+#[macro_export]
+macro_rules! ipc_cmif_interface {
+    ($trait_name:ident, $( $client_name:ident ),+ $commands:tt) => {
+        $crate::ipc_cmif_interface_define_trait!($trait_name $commands);
+        $( $crate::ipc_cmif_interface_define_client!($trait_name, $client_name $commands); )+
+    };
+}
+
+#[macro_export]
+macro_rules! ipc_cmif_interface_define_trait {
+    ($trait_name:ident { $( $name:ident $( [$rq_id:expr $(, $( $ver:tt )+ )?] )+: ( $( $in_param_name:ident: $in_param_type:ty ),* ) => ( $( $out_param_name:ident: $out_param_type:ty $( as $out_client_type:ty )? ),* ) $( send ( $( $send_param_name:ident ),* ) )?; )* }) => {
+        pub trait $trait_name {
+            $(
+                $crate::ipc_cmif_interface_define_command!($name: ( $( $in_param_name: $in_param_type ),* ) => ( $( $out_param_name: $out_param_type ),* ));
+            )*
+
+            fn get_interface_command_table(&self) -> $crate::ipc::cmif::sf::CommandMetadataTable where Self: Sized {
+                vec![
+                    $( $( $crate::ipc_cmif_interface_make_command_meta!($name: $rq_id $(, [ $( $ver )+ ] )?) ),+ ),*
+                ]
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! ipc_cmif_interface_define_client {
+    ($trait_name:ident, $client_name:ident { $( $name:ident $( [$rq_id:expr $(, $( $ver:tt )+ )?] )+: ( $( $in_param_name:ident: $in_param_type:ty ),* ) => ( $( $out_param_name:ident: $out_param_type:ty $( as $out_client_type:ty )? ),* ) $( send ( $( $send_param_name:ident ),* ) )?; )* }) => {
+        pub struct $client_name {
+            session: $crate::ipc::cmif::sf::Session,
+        }
+
+        impl $crate::ipc::cmif::sf::IObject for $client_name {
+            fn get_session(&mut self) -> &mut $crate::ipc::cmif::sf::Session {
+                &mut self.session
+            }
+
+            fn get_command_table(&self) -> $crate::ipc::cmif::sf::CommandMetadataTable {
+                <Self as $trait_name>::get_interface_command_table(self)
+            }
+        }
+
+        impl $crate::service::cmif::IClientObject for $client_name {
+            fn new(session: $crate::ipc::cmif::sf::Session) -> Self {
+                Self { session }
+            }
+        }
+
+        impl $trait_name for $client_name {
+            $(
+                #[allow(unused_parens)]
+                fn $name(&mut self, $( $in_param_name: $in_param_type ),* ) -> $crate::result::Result<( $( $out_param_type ),* )> {
+                    let rq_id = $crate::ipc::cmif::sf::select_request_id(&[ $( $crate::ipc_cmif_interface_make_request_id!($rq_id $(, $( $ver )+ )?) ),+ ])?;
+                    $crate::ipc_cmif_interface_send_request_command!([self.session.object_info; rq_id] ( $( $in_param_name ),* ) $( send ( $( $send_param_name ),* ) )? => ( $( $out_param_name: $crate::ipc_cmif_interface_client_type!($out_param_type $(, $out_client_type )?) ),* ))
+                }
+            )*
+        }
+    };
+}
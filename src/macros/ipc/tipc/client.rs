@@ -25,7 +25,7 @@ macro_rules! ipc_tipc_client_send_request_command {
                 }
             )*
 
-            $crate::svc::send_sync_request($session.handle)?;
+            $crate::ipc::transport::send_sync_request($session.transport_id, $session.handle)?;
 
             $crate::ipc::tipc::client::read_request_command_response_from_ipc_buffer(&mut ctx)?;
 
@@ -99,6 +99,10 @@ impl<T: ?Sized> Shared<T> {
         self.refcount.use_count()
     }
 
+    pub fn is_same(&self, other: &Self) -> bool {
+        self.object as *mut u8 == other.object as *mut u8
+    }
+
     pub fn to<U>(&self) -> Shared<U> {
         let mut new_shared = Shared::<U> {
             object: self.object as *mut U,
@@ -0,0 +1,386 @@
+use crate::{
+    nro,
+    result::*,
+    results, service,
+    service::cmif::{set, set::ISettingsServer},
+    util,
+};
+use alloc::vec::Vec;
+use core::{mem as cmem, ptr, slice};
+
+// NACP (application control property), found in NRO assets and at the start of
+// the control data ns returns for installed applications
+
+pub const LANGUAGE_COUNT: usize = 16;
+
+// Title entry order inside the NACP
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Language {
+    AmericanEnglish = 0,
+    BritishEnglish = 1,
+    Japanese = 2,
+    French = 3,
+    German = 4,
+    LatinAmericanSpanish = 5,
+    Spanish = 6,
+    Italian = 7,
+    Dutch = 8,
+    CanadianFrench = 9,
+    Portuguese = 10,
+    Russian = 11,
+    Korean = 12,
+    TraditionalChinese = 13,
+    SimplifiedChinese = 14,
+    BrazilianPortuguese = 15,
+}
+
+impl Language {
+    pub fn from_language_code(language_code: &str) -> Option<Self> {
+        match language_code {
+            "en-US" => Some(Self::AmericanEnglish),
+            "en-GB" => Some(Self::BritishEnglish),
+            "ja" => Some(Self::Japanese),
+            "fr" => Some(Self::French),
+            "de" => Some(Self::German),
+            "es-419" => Some(Self::LatinAmericanSpanish),
+            "es" => Some(Self::Spanish),
+            "it" => Some(Self::Italian),
+            "nl" => Some(Self::Dutch),
+            "fr-CA" => Some(Self::CanadianFrench),
+            "pt" => Some(Self::Portuguese),
+            "ru" => Some(Self::Russian),
+            "ko" => Some(Self::Korean),
+            "zh-TW" | "zh-Hant" => Some(Self::TraditionalChinese),
+            "zh-CN" | "zh-Hans" => Some(Self::SimplifiedChinese),
+            "pt-BR" => Some(Self::BrazilianPortuguese),
+            _ => None,
+        }
+    }
+}
+
+// Console language as reported by set, falling back to American English for
+// codes without a NACP title entry
+pub fn get_system_language() -> Result<Language> {
+    let set_srv = service::cmif::new_service_object::<set::SettingsServer>()?;
+    let language_code = set_srv.get().get_language_code()?;
+    Ok(Language::from_language_code(language_code.get_str()?).unwrap_or(Language::AmericanEnglish))
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum StartupUserAccount {
+    None = 0,
+    Required = 1,
+    RequiredWithNetworkServiceAccountAvailable = 2,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Screenshot {
+    Allow = 0,
+    Deny = 1,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum VideoCapture {
+    Disable = 0,
+    Manual = 1,
+    Enable = 2,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct ApplicationTitle {
+    pub name: util::CString<0x200>,
+    pub publisher: util::CString<0x100>,
+}
+
+impl ApplicationTitle {
+    pub fn is_empty(&self) -> bool {
+        self.name.c_str[0] == 0
+    }
+}
+
+// Enum-like fields are kept as raw values, since the structure is read as-is
+// from untrusted data (use the typed accessors below)
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct ApplicationControlProperty {
+    pub titles: [ApplicationTitle; LANGUAGE_COUNT],
+    pub isbn: [u8; 0x25],
+    pub startup_user_account: u8,
+    pub user_account_switch_lock: u8,
+    pub add_on_content_registration_type: u8,
+    pub attribute_flag: u32,
+    pub supported_language_flag: u32,
+    pub parental_control_flag: u32,
+    pub screenshot: u8,
+    pub video_capture: u8,
+    pub data_loss_confirmation: u8,
+    pub play_log_policy: u8,
+    pub presence_group_id: u64,
+    pub rating_age: [i8; 0x20],
+    pub display_version: util::CString<0x10>,
+    pub add_on_content_base_id: u64,
+    pub save_data_owner_id: u64,
+    pub user_account_save_data_size: i64,
+    pub user_account_save_data_journal_size: i64,
+    pub device_save_data_size: i64,
+    pub device_save_data_journal_size: i64,
+    pub bcat_delivery_cache_storage_size: i64,
+    pub application_error_code_category: [u8; 0x8],
+    pub local_communication_ids: [u64; 0x8],
+    pub logo_type: u8,
+    pub logo_handling: u8,
+    pub runtime_add_on_content_install: u8,
+    pub runtime_parameter_delivery: u8,
+    pub reserved_1: [u8; 0x2],
+    pub crash_report: u8,
+    pub hdcp: u8,
+    pub seed_for_pseudo_device_id: u64,
+    pub bcat_passphrase: [u8; 0x41],
+    pub startup_user_account_option: u8,
+    pub reserved_2: [u8; 0x6],
+    pub user_account_save_data_size_max: i64,
+    pub user_account_save_data_journal_size_max: i64,
+    pub device_save_data_size_max: i64,
+    pub device_save_data_journal_size_max: i64,
+    pub temporary_storage_size: i64,
+    pub cache_storage_size: i64,
+    pub cache_storage_journal_size: i64,
+    pub cache_storage_data_and_journal_size_max: i64,
+    pub cache_storage_index_max: u16,
+    pub reserved_3: [u8; 0x6],
+    pub play_log_queryable_application_ids: [u64; 0x10],
+    pub play_log_query_capability: u8,
+    pub repair_flag: u8,
+    pub program_index: u8,
+    pub required_network_service_license_on_launch: u8,
+    pub reserved_4: [u8; 0xDEC],
+}
+
+// Fails to build if the layout above ever stops matching the 0x4000-byte NACP
+const _: [(); 0x4000] = [(); cmem::size_of::<ApplicationControlProperty>()];
+
+impl Default for ApplicationControlProperty {
+    fn default() -> Self {
+        // Every field is plain data, all-zeroes is a valid (empty) NACP
+        unsafe { cmem::zeroed() }
+    }
+}
+
+impl ApplicationControlProperty {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        result_return_unless!(
+            data.len() >= cmem::size_of::<Self>(),
+            results::lib::nacp::ResultInvalidSize
+        );
+        unsafe { Ok(ptr::read_unaligned(data.as_ptr() as *const Self)) }
+    }
+
+    pub fn from_nro(nro: &nro::Nro) -> Result<Self> {
+        Self::from_bytes(&nro.read_nacp_data()?)
+    }
+
+    // ns control data is the NACP followed by the icon
+    pub fn from_control_data(control_data: &[u8]) -> Result<Self> {
+        Self::from_bytes(control_data)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self as *const Self as *const u8, cmem::size_of::<Self>()) }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        Vec::from(self.as_bytes())
+    }
+
+    pub fn get_title(&self, language: Language) -> &ApplicationTitle {
+        &self.titles[language as usize]
+    }
+
+    pub fn get_title_mut(&mut self, language: Language) -> &mut ApplicationTitle {
+        &mut self.titles[language as usize]
+    }
+
+    pub fn set_title(&mut self, language: Language, name: &str, publisher: &str) -> Result<()> {
+        let title = self.get_title_mut(language);
+        title.name.set_str(name)?;
+        title.publisher.set_str(publisher)?;
+        self.supported_language_flag |= bit!(language as u32);
+        Ok(())
+    }
+
+    // Title in the given language, or the first non-empty one if that entry
+    // is empty (most homebrew only fill a single entry)
+    pub fn get_language_title(&self, language: Language) -> &ApplicationTitle {
+        let title = self.get_title(language);
+        if title.is_empty() {
+            if let Some(fallback_title) = self.titles.iter().find(|title| !title.is_empty()) {
+                return fallback_title;
+            }
+        }
+        title
+    }
+
+    pub fn get_desired_title(&self) -> Result<&ApplicationTitle> {
+        let language = get_system_language()?;
+        Ok(self.get_language_title(language))
+    }
+
+    pub fn get_startup_user_account(&self) -> Option<StartupUserAccount> {
+        match self.startup_user_account {
+            0 => Some(StartupUserAccount::None),
+            1 => Some(StartupUserAccount::Required),
+            2 => Some(StartupUserAccount::RequiredWithNetworkServiceAccountAvailable),
+            _ => None,
+        }
+    }
+
+    pub fn set_startup_user_account(&mut self, startup_user_account: StartupUserAccount) {
+        self.startup_user_account = startup_user_account as u8;
+    }
+
+    pub fn get_screenshot(&self) -> Option<Screenshot> {
+        match self.screenshot {
+            0 => Some(Screenshot::Allow),
+            1 => Some(Screenshot::Deny),
+            _ => None,
+        }
+    }
+
+    pub fn set_screenshot(&mut self, screenshot: Screenshot) {
+        self.screenshot = screenshot as u8;
+    }
+
+    pub fn get_video_capture(&self) -> Option<VideoCapture> {
+        match self.video_capture {
+            0 => Some(VideoCapture::Disable),
+            1 => Some(VideoCapture::Manual),
+            2 => Some(VideoCapture::Enable),
+            _ => None,
+        }
+    }
+
+    pub fn set_video_capture(&mut self, video_capture: VideoCapture) {
+        self.video_capture = video_capture as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nacp_size() {
+        assert_eq!(cmem::size_of::<ApplicationTitle>(), 0x300);
+        assert_eq!(cmem::size_of::<ApplicationControlProperty>(), 0x4000);
+    }
+
+    #[test]
+    fn round_trip_titles() {
+        let mut nacp = ApplicationControlProperty::new();
+        nacp.set_title(Language::Japanese, "Title", "Publisher")
+            .unwrap();
+        nacp.set_title(Language::German, "Titel", "Herausgeber")
+            .unwrap();
+        nacp.display_version.set_str("1.0.0").unwrap();
+        nacp.set_screenshot(Screenshot::Deny);
+
+        let nacp_data = nacp.to_bytes();
+        assert_eq!(nacp_data.len(), 0x4000);
+        let nacp = ApplicationControlProperty::from_bytes(&nacp_data).unwrap();
+        let title = nacp.get_title(Language::Japanese);
+        assert_eq!(title.name.get_str().unwrap(), "Title");
+        assert_eq!(title.publisher.get_str().unwrap(), "Publisher");
+        assert_eq!(
+            nacp.get_title(Language::German).name.get_str().unwrap(),
+            "Titel"
+        );
+        assert!(nacp.get_title(Language::AmericanEnglish).is_empty());
+        assert_eq!(
+            nacp.supported_language_flag,
+            bit!(Language::Japanese as u32) | bit!(Language::German as u32)
+        );
+        assert_eq!(nacp.display_version.get_str().unwrap(), "1.0.0");
+        assert_eq!(nacp.get_screenshot(), Some(Screenshot::Deny));
+        assert_eq!(
+            nacp.get_startup_user_account(),
+            Some(StartupUserAccount::None)
+        );
+    }
+
+    #[test]
+    fn language_title_fallback() {
+        let mut nacp = ApplicationControlProperty::new();
+        assert!(nacp.get_language_title(Language::French).is_empty());
+
+        nacp.set_title(Language::Korean, "Korean", "").unwrap();
+        nacp.set_title(Language::Italian, "Italian", "").unwrap();
+        // The first non-empty entry is used, in NACP order
+        for &language in &[Language::AmericanEnglish, Language::BrazilianPortuguese] {
+            assert_eq!(
+                nacp.get_language_title(language).name.get_str().unwrap(),
+                "Italian"
+            );
+        }
+        assert_eq!(
+            nacp.get_language_title(Language::Korean)
+                .name
+                .get_str()
+                .unwrap(),
+            "Korean"
+        );
+    }
+
+    #[test]
+    fn language_codes() {
+        for &(language_code, language) in &[
+            ("en-US", Language::AmericanEnglish),
+            ("en-GB", Language::BritishEnglish),
+            ("ja", Language::Japanese),
+            ("fr", Language::French),
+            ("de", Language::German),
+            ("es-419", Language::LatinAmericanSpanish),
+            ("es", Language::Spanish),
+            ("it", Language::Italian),
+            ("nl", Language::Dutch),
+            ("fr-CA", Language::CanadianFrench),
+            ("pt", Language::Portuguese),
+            ("ru", Language::Russian),
+            ("ko", Language::Korean),
+            ("zh-TW", Language::TraditionalChinese),
+            ("zh-Hant", Language::TraditionalChinese),
+            ("zh-CN", Language::SimplifiedChinese),
+            ("zh-Hans", Language::SimplifiedChinese),
+            ("pt-BR", Language::BrazilianPortuguese),
+        ] {
+            assert_eq!(Language::from_language_code(language_code), Some(language));
+        }
+        for &language_code in &["", "en", "EN-US", "zh", "pl"] {
+            assert_eq!(Language::from_language_code(language_code), None);
+        }
+    }
+
+    #[test]
+    fn reject_short_data() {
+        let nacp_data = ApplicationControlProperty::new().to_bytes();
+        for &size in &[0, 0x300, 0x3FFF] {
+            assert!(results::lib::nacp::ResultInvalidSize::matches(
+                ApplicationControlProperty::from_bytes(&nacp_data[..size])
+                    .err()
+                    .unwrap()
+            ));
+        }
+        // Control data has the icon after the NACP
+        let mut control_data = nacp_data.clone();
+        control_data.extend_from_slice(&[0xFF; 0x10]);
+        assert!(ApplicationControlProperty::from_control_data(&control_data).is_ok());
+    }
+}
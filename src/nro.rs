@@ -0,0 +1,496 @@
+use crate::{
+    crt0, fs,
+    fs::{romfs, romfs::Source},
+    hbl, mem,
+    result::*,
+    results,
+};
+use alloc::{string::String, vec::Vec};
+use core::{mem as cmem, ptr, slice};
+
+// NRO executables: a start section and header, followed by the text, ro and
+// data segments, and optionally by an asset section (icon, NACP and RomFS)
+// appended after the NRO itself
+
+pub const MAGIC: u32 = u32::from_le_bytes(*b"NRO0");
+pub const ASSET_MAGIC: u32 = u32::from_le_bytes(*b"ASET");
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct Start {
+    pub unused: u32,
+    pub mod_offset: u32,
+    pub padding: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct SegmentHeader {
+    pub file_offset: u32,
+    pub size: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct Header {
+    pub magic: u32,
+    pub version: u32,
+    pub size: u32,
+    pub flags: u32,
+    pub text: SegmentHeader,
+    pub ro: SegmentHeader,
+    pub data: SegmentHeader,
+    pub bss_size: u32,
+    pub reserved: u32,
+    pub module_id: [u8; 0x20],
+    pub dso_handle_offset: u32,
+    pub reserved_2: u32,
+    pub api_info: SegmentHeader,
+    pub dynstr: SegmentHeader,
+    pub dynsym: SegmentHeader,
+}
+
+pub const HEADER_OFFSET: usize = cmem::size_of::<Start>();
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct AssetSection {
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl AssetSection {
+    pub fn is_present(&self) -> bool {
+        self.size != 0
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct AssetHeader {
+    pub magic: u32,
+    pub version: u32,
+    pub icon: AssetSection,
+    pub nacp: AssetSection,
+    pub romfs: AssetSection,
+}
+
+fn read_val<T: Copy + Default>(source: &mut dyn Source, offset: usize) -> Result<T> {
+    let mut t: T = Default::default();
+    let t_buf =
+        unsafe { slice::from_raw_parts_mut(&mut t as *mut T as *mut u8, cmem::size_of::<T>()) };
+    source.read(offset, t_buf)?;
+    Ok(t)
+}
+
+pub struct Nro {
+    file: mem::Shared<dyn fs::FileHandle>,
+    source: mem::Shared<dyn Source>,
+    header: Header,
+    asset_header: Option<AssetHeader>,
+}
+
+impl Nro {
+    pub fn new(file: mem::Shared<dyn fs::FileHandle>) -> Result<Self> {
+        let source: mem::Shared<dyn Source> =
+            mem::Shared::new(romfs::FileSource::new(file.clone(), 0));
+        let header: Header = read_val(source.get(), HEADER_OFFSET)?;
+        result_return_unless!(
+            header.magic == MAGIC,
+            results::lib::nro::ResultInvalidHeader
+        );
+
+        // NROs without assets simply end after their segments (or have some
+        // unrelated data appended), any other read failure is an actual error
+        let asset_header = match read_val::<AssetHeader>(source.get(), header.size as usize) {
+            Ok(asset_header) if asset_header.magic == ASSET_MAGIC => Some(asset_header),
+            Ok(_) => None,
+            Err(rc) if results::lib::fs::ResultSourceOutOfBounds::matches(rc) => None,
+            Err(rc) => return Err(rc),
+        };
+
+        Ok(Self {
+            file,
+            source,
+            header,
+            asset_header,
+        })
+    }
+
+    pub fn get_header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn get_asset_header(&self) -> Option<&AssetHeader> {
+        self.asset_header.as_ref()
+    }
+
+    pub fn has_assets(&self) -> bool {
+        self.asset_header.is_some()
+    }
+
+    // Sizes and offsets come straight from the file, so they're checked before
+    // allocating anything
+    fn read_data(&self, offset: u64, size: u64) -> Result<Vec<u8>> {
+        let source_size = self.source.get().get_size()? as u64;
+        match offset.checked_add(size) {
+            Some(end_offset) if end_offset <= source_size => {}
+            _ => return Err(results::lib::nro::ResultInvalidHeader::make()),
+        };
+
+        let mut data = vec![0u8; size as usize];
+        self.source.get().read(offset as usize, &mut data)?;
+        Ok(data)
+    }
+
+    pub fn read_segment(&self, segment: &SegmentHeader) -> Result<Vec<u8>> {
+        self.read_data(segment.file_offset as u64, segment.size as u64)
+    }
+
+    fn get_asset_section(&self, get_fn: fn(&AssetHeader) -> AssetSection) -> Result<AssetSection> {
+        match self.asset_header {
+            Some(ref asset_header) => {
+                let section = get_fn(asset_header);
+                result_return_unless!(section.is_present(), results::lib::nro::ResultAssetNotFound);
+                Ok(section)
+            }
+            None => Err(results::lib::nro::ResultAssetsNotFound::make()),
+        }
+    }
+
+    // Asset offsets are relative to the asset header
+    fn get_asset_offset(&self, section: &AssetSection) -> Result<u64> {
+        match (self.header.size as u64).checked_add(section.offset) {
+            Some(asset_offset) => Ok(asset_offset),
+            None => Err(results::lib::nro::ResultInvalidHeader::make()),
+        }
+    }
+
+    fn read_asset(&self, section: AssetSection) -> Result<Vec<u8>> {
+        self.read_data(self.get_asset_offset(&section)?, section.size)
+    }
+
+    // JPEG image
+    pub fn read_icon(&self) -> Result<Vec<u8>> {
+        let section = self.get_asset_section(|asset_header| asset_header.icon)?;
+        self.read_asset(section)
+    }
+
+    pub fn read_nacp_data(&self) -> Result<Vec<u8>> {
+        let section = self.get_asset_section(|asset_header| asset_header.nacp)?;
+        self.read_asset(section)
+    }
+
+    pub fn get_romfs_offset(&self) -> Result<usize> {
+        let section = self.get_asset_section(|asset_header| asset_header.romfs)?;
+        let romfs_offset = self.get_asset_offset(&section)?;
+        result_return_unless!(
+            romfs_offset <= self.source.get().get_size()? as u64,
+            results::lib::nro::ResultInvalidHeader
+        );
+        Ok(romfs_offset as usize)
+    }
+
+    pub fn open_romfs(&self) -> Result<romfs::FileSystem> {
+        let romfs_offset = self.get_romfs_offset()?;
+        romfs::FileSystem::new(mem::Shared::new(romfs::FileSource::new(
+            self.file.clone(),
+            romfs_offset,
+        )))
+    }
+}
+
+pub fn open_file(path: String) -> Result<Nro> {
+    let nro_file = fs::open_file(path, fs::FileOpenOption::Read())?;
+    Nro::new(nro_file.get_handle())
+}
+
+pub fn get_self_header() -> Result<Header> {
+    let base_address = crt0::get_base_address();
+    result_return_if!(
+        base_address.is_null(),
+        results::lib::nro::ResultInvalidHeader
+    );
+
+    // The start section and header are mapped as part of the text segment
+    let header = unsafe { ptr::read_unaligned(base_address.add(HEADER_OFFSET) as *const Header) };
+    result_return_unless!(
+        header.magic == MAGIC,
+        results::lib::nro::ResultInvalidHeader
+    );
+    Ok(header)
+}
+
+pub fn get_self_path() -> Result<String> {
+    let argv = match hbl::get_argv() {
+        Some(argv) => argv,
+        None => return Err(results::lib::fs::ResultNroPathNotAvailable::make()),
+    };
+
+    // The first argument is the path of the NRO itself, which may be quoted
+    let nro_path = match argv.strip_prefix('"') {
+        Some(quoted_argv) => quoted_argv.split('"').next(),
+        None => argv.split(' ').next(),
+    };
+    match nro_path {
+        Some(nro_path) if !nro_path.is_empty() => Ok(String::from(nro_path)),
+        _ => Err(results::lib::fs::ResultNroPathNotAvailable::make()),
+    }
+}
+
+fn open_self_file() -> Result<mem::Shared<dyn fs::FileHandle>> {
+    let nro_path = get_self_path()?;
+
+    // Homebrew NROs are always launched from the SD card, so open it directly
+    // instead of depending on the user's mounts
+    let nro_sd_path = match nro_path.strip_prefix("sdmc:") {
+        Some(nro_sd_path) => nro_sd_path,
+        None => return Err(results::lib::fs::ResultNroPathNotAvailable::make()),
+    };
+    let sd_fs = fs::open_sd_card_filesystem()?;
+    sd_fs.get().open_file(nro_sd_path, fs::FileOpenMode::Read())
+}
+
+// Assets aren't mapped in memory, so the running NRO is read back from its file
+pub fn open_self() -> Result<Nro> {
+    Nro::new(open_self_file()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::FileSystem as _;
+
+    const SEGMENTS: &[&[u8]] = &[b"text segment", b"ro", b"data segment"];
+    const ICON: &[u8] = b"\xFF\xD8icon";
+    const NACP: &[u8] = b"nacp";
+
+    fn push_val<T: Copy>(image: &mut Vec<u8>, t: T) {
+        let t_buf =
+            unsafe { slice::from_raw_parts(&t as *const T as *const u8, cmem::size_of::<T>()) };
+        image.extend_from_slice(t_buf);
+    }
+
+    fn build_nro() -> Vec<u8> {
+        let mut segment_offset = HEADER_OFFSET + cmem::size_of::<Header>();
+        let mut segment_headers: Vec<SegmentHeader> = Vec::new();
+        for segment in SEGMENTS {
+            segment_headers.push(SegmentHeader {
+                file_offset: segment_offset as u32,
+                size: segment.len() as u32,
+            });
+            segment_offset += segment.len();
+        }
+
+        let mut image: Vec<u8> = Vec::new();
+        push_val(&mut image, Start::default());
+        push_val(
+            &mut image,
+            Header {
+                magic: MAGIC,
+                size: segment_offset as u32,
+                text: segment_headers[0],
+                ro: segment_headers[1],
+                data: segment_headers[2],
+                ..Default::default()
+            },
+        );
+        for segment in SEGMENTS {
+            image.extend_from_slice(segment);
+        }
+        image
+    }
+
+    fn build_nro_with_assets(icon: &[u8]) -> Vec<u8> {
+        let romfs = romfs::tests::build_romfs(3);
+        let icon_offset = cmem::size_of::<AssetHeader>();
+        let nacp_offset = icon_offset + icon.len();
+        let romfs_offset = nacp_offset + NACP.len();
+
+        let mut image = build_nro();
+        push_val(
+            &mut image,
+            AssetHeader {
+                magic: ASSET_MAGIC,
+                version: 0,
+                icon: AssetSection {
+                    offset: icon_offset as u64,
+                    size: icon.len() as u64,
+                },
+                nacp: AssetSection {
+                    offset: nacp_offset as u64,
+                    size: NACP.len() as u64,
+                },
+                romfs: AssetSection {
+                    offset: romfs_offset as u64,
+                    size: romfs.len() as u64,
+                },
+            },
+        );
+        image.extend_from_slice(icon);
+        image.extend_from_slice(NACP);
+        image.extend_from_slice(&romfs);
+        image
+    }
+
+    fn open_nro(image: &[u8]) -> Result<Nro> {
+        let mut ram_fs = fs::ram::FileSystem::new();
+        ram_fs.create_file("/file.nro", fs::FileAttribute::None(), 0)?;
+        let file = ram_fs.open_file(
+            "/file.nro",
+            fs::FileOpenMode::Read() | fs::FileOpenMode::Write() | fs::FileOpenMode::Append(),
+        )?;
+        file.get().write(0, image, fs::FileWriteOption::None())?;
+        Nro::new(file)
+    }
+
+    // Fails every read past the given size, like a file on a removed SD card
+    struct FailingFile {
+        data: Vec<u8>,
+    }
+
+    impl fs::FileHandle for FailingFile {
+        fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+            result_return_unless!(
+                offset < self.data.len(),
+                results::lib::fs::ResultDeviceRemoved
+            );
+            let read_size = core::cmp::min(buf.len(), self.data.len() - offset);
+            buf[..read_size].copy_from_slice(&self.data[offset..offset + read_size]);
+            Ok(read_size)
+        }
+
+        fn write(
+            &mut self,
+            _offset: usize,
+            _buf: &[u8],
+            _option: fs::FileWriteOption,
+        ) -> Result<()> {
+            Err(results::fs::ResultUnsupportedOperation::make())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_size(&mut self, _size: usize) -> Result<()> {
+            Err(results::fs::ResultUnsupportedOperation::make())
+        }
+
+        fn get_size(&mut self) -> Result<usize> {
+            Ok(self.data.len())
+        }
+    }
+
+    #[test]
+    fn read_segments_and_assets() {
+        let nro = open_nro(&build_nro_with_assets(ICON)).unwrap();
+        assert!(nro.has_assets());
+        assert_eq!(nro.get_header().size as usize, build_nro().len());
+
+        let header = nro.get_header();
+        let segment_headers = [header.text, header.ro, header.data];
+        for (segment, segment_header) in SEGMENTS.iter().zip(&segment_headers) {
+            assert_eq!(nro.read_segment(segment_header).unwrap(), *segment);
+        }
+        assert_eq!(nro.read_icon().unwrap(), ICON);
+        assert_eq!(nro.read_nacp_data().unwrap(), NACP);
+
+        let mut romfs = nro.open_romfs().unwrap();
+        let file = romfs
+            .open_file("/root.txt", fs::FileOpenMode::Read())
+            .unwrap();
+        let mut data = [0u8; 9];
+        assert_eq!(file.get().read(0, &mut data).unwrap(), data.len());
+        assert_eq!(&data, b"root file");
+    }
+
+    #[test]
+    fn missing_assets() {
+        let nro = open_nro(&build_nro_with_assets(b"")).unwrap();
+        assert!(results::lib::nro::ResultAssetNotFound::matches(
+            nro.read_icon().unwrap_err()
+        ));
+        assert_eq!(nro.read_nacp_data().unwrap(), NACP);
+
+        // Nothing appended, less than an asset header and unrelated data
+        let mut image = build_nro();
+        for &appended_size in &[0, 8, 0x100] {
+            image.resize(build_nro().len() + appended_size, 0xAA);
+            let nro = open_nro(&image).unwrap();
+            assert!(!nro.has_assets());
+            assert!(results::lib::nro::ResultAssetsNotFound::matches(
+                nro.read_nacp_data().unwrap_err()
+            ));
+            assert!(results::lib::nro::ResultAssetsNotFound::matches(
+                nro.open_romfs().err().unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn propagate_read_errors() {
+        let file = FailingFile { data: build_nro() };
+        assert!(results::lib::fs::ResultDeviceRemoved::matches(
+            Nro::new(mem::Shared::new(file)).err().unwrap()
+        ));
+    }
+
+    #[test]
+    fn reject_invalid_header() {
+        let mut image = build_nro();
+        image[HEADER_OFFSET] = b'X';
+        assert!(results::lib::nro::ResultInvalidHeader::matches(
+            open_nro(&image).err().unwrap()
+        ));
+    }
+
+    #[test]
+    fn reject_out_of_bounds_sections() {
+        let nro = open_nro(&build_nro_with_assets(ICON)).unwrap();
+        let image_size = build_nro_with_assets(ICON).len() as u32;
+        for &(file_offset, size) in &[
+            (0, u32::MAX),
+            (u32::MAX, 1),
+            (image_size - 1, 2),
+            (image_size + 1, 0),
+        ] {
+            assert!(results::lib::nro::ResultInvalidHeader::matches(
+                nro.read_segment(&SegmentHeader { file_offset, size })
+                    .unwrap_err()
+            ));
+        }
+        let segment = SegmentHeader {
+            file_offset: image_size - 1,
+            size: 1,
+        };
+        assert_eq!(nro.read_segment(&segment).unwrap().len(), 1);
+
+        // Huge asset sizes and offsets past the end (or overflowing) of the file
+        for &(offset, size) in &[(0, u64::MAX), (u64::MAX, 1), (u64::MAX - 1, 1)] {
+            let mut image = build_nro_with_assets(ICON);
+            let asset_header_offset = build_nro().len();
+            let section = AssetSection { offset, size };
+            let mut section_buf = Vec::new();
+            push_val(&mut section_buf, section);
+            for section_offset in (8..cmem::size_of::<AssetHeader>()).step_by(0x10) {
+                let section_start = asset_header_offset + section_offset;
+                image[section_start..section_start + section_buf.len()]
+                    .copy_from_slice(&section_buf);
+            }
+
+            let nro = open_nro(&image).unwrap();
+            assert!(results::lib::nro::ResultInvalidHeader::matches(
+                nro.read_icon().unwrap_err()
+            ));
+            assert!(results::lib::nro::ResultInvalidHeader::matches(
+                nro.read_nacp_data().unwrap_err()
+            ));
+            if offset != 0 {
+                assert!(results::lib::nro::ResultInvalidHeader::matches(
+                    nro.get_romfs_offset().unwrap_err()
+                ));
+            }
+        }
+    }
+}
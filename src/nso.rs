@@ -0,0 +1,319 @@
+use crate::{
+    crypto::sha256, dynamic, dynamic::mod0, fs, fs::romfs::Source, lz4, mem, result::*, results,
+};
+use alloc::{string::String, vec::Vec};
+use core::{mem as cmem, ptr, slice};
+
+// NSO executables: a header followed by the (optionally LZ4-compressed) text,
+// ro and data segments
+
+pub const MAGIC: u32 = u32::from_le_bytes(*b"NSO0");
+
+bit_enum! {
+    Flags (u32) {
+        None = 0,
+        TextCompressed = bit!(0),
+        RoCompressed = bit!(1),
+        DataCompressed = bit!(2),
+        TextHash = bit!(3),
+        RoHash = bit!(4),
+        DataHash = bit!(5)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct SegmentHeader {
+    pub file_offset: u32,
+    pub memory_offset: u32,
+    pub size: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct RelativeSegmentHeader {
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct Header {
+    pub magic: u32,
+    pub version: u32,
+    pub reserved: u32,
+    pub flags: Flags,
+    pub text: SegmentHeader,
+    pub module_name_offset: u32,
+    pub ro: SegmentHeader,
+    pub module_name_size: u32,
+    pub data: SegmentHeader,
+    pub bss_size: u32,
+    pub module_id: [u8; 0x20],
+    pub text_file_size: u32,
+    pub ro_file_size: u32,
+    pub data_file_size: u32,
+    pub reserved_2: [u8; 0x1C],
+    pub api_info: RelativeSegmentHeader,
+    pub dynstr: RelativeSegmentHeader,
+    pub dynsym: RelativeSegmentHeader,
+    pub text_hash: [u8; 0x20],
+    pub ro_hash: [u8; 0x20],
+    pub data_hash: [u8; 0x20],
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Segment {
+    Text,
+    Ro,
+    Data,
+}
+
+// Everything needed to load one segment: where it is, how it's stored and
+// the SHA-256 of its decompressed contents (checked when reading the segment if
+// the hash flag is set)
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SegmentInfo {
+    pub header: SegmentHeader,
+    pub file_size: u32,
+    pub is_compressed: bool,
+    pub check_hash: bool,
+    pub hash: [u8; 0x20],
+}
+
+impl Header {
+    pub fn get_segment_info(&self, segment: Segment) -> SegmentInfo {
+        match segment {
+            Segment::Text => SegmentInfo {
+                header: self.text,
+                file_size: self.text_file_size,
+                is_compressed: self.flags.contains(Flags::TextCompressed()),
+                check_hash: self.flags.contains(Flags::TextHash()),
+                hash: self.text_hash,
+            },
+            Segment::Ro => SegmentInfo {
+                header: self.ro,
+                file_size: self.ro_file_size,
+                is_compressed: self.flags.contains(Flags::RoCompressed()),
+                check_hash: self.flags.contains(Flags::RoHash()),
+                hash: self.ro_hash,
+            },
+            Segment::Data => SegmentInfo {
+                header: self.data,
+                file_size: self.data_file_size,
+                is_compressed: self.flags.contains(Flags::DataCompressed()),
+                check_hash: self.flags.contains(Flags::DataHash()),
+                hash: self.data_hash,
+            },
+        }
+    }
+
+    // Size of the whole module once loaded, .bss included
+    pub fn get_image_size(&self) -> usize {
+        self.data.memory_offset as usize + self.data.size as usize + self.bss_size as usize
+    }
+}
+
+pub struct Nso {
+    source: mem::Shared<dyn Source>,
+    header: Header,
+}
+
+impl Nso {
+    pub fn new(source: mem::Shared<dyn Source>) -> Result<Self> {
+        let mut header: Header = Default::default();
+        let header_buf = unsafe {
+            slice::from_raw_parts_mut(&mut header as *mut _ as *mut u8, cmem::size_of::<Header>())
+        };
+        source.get().read(0, header_buf)?;
+        result_return_unless!(
+            header.magic == MAGIC,
+            results::lib::nso::ResultInvalidHeader
+        );
+
+        // Segments must be laid out in order in memory
+        result_return_unless!(
+            ((header.text.memory_offset as usize + header.text.size as usize)
+                <= header.ro.memory_offset as usize)
+                && ((header.ro.memory_offset as usize + header.ro.size as usize)
+                    <= header.data.memory_offset as usize),
+            results::lib::nso::ResultInvalidHeader
+        );
+
+        Ok(Self { source, header })
+    }
+
+    pub fn get_header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn get_module_id(&self) -> &[u8; 0x20] {
+        &self.header.module_id
+    }
+
+    pub fn read_module_name(&self) -> Result<String> {
+        let mut module_name = vec![0u8; self.header.module_name_size as usize];
+        self.source
+            .get()
+            .read(self.header.module_name_offset as usize, &mut module_name)?;
+        match String::from_utf8(module_name) {
+            Ok(module_name) => Ok(String::from(module_name.trim_matches('\0'))),
+            Err(_) => Err(results::lib::nso::ResultInvalidHeader::make()),
+        }
+    }
+
+    pub fn read_segment(&self, segment: Segment) -> Result<Vec<u8>> {
+        let segment_info = self.header.get_segment_info(segment);
+        let mut file_data = vec![0u8; segment_info.file_size as usize];
+        self.source
+            .get()
+            .read(segment_info.header.file_offset as usize, &mut file_data)?;
+
+        let segment_data = if segment_info.is_compressed {
+            let mut segment_data = vec![0u8; segment_info.header.size as usize];
+            let decompressed_size = lz4::decompress_block(&file_data, &mut segment_data)?;
+            result_return_unless!(
+                decompressed_size == segment_data.len(),
+                results::lib::nso::ResultInvalidSegment
+            );
+            segment_data
+        } else {
+            result_return_unless!(
+                file_data.len() == segment_info.header.size as usize,
+                results::lib::nso::ResultInvalidSegment
+            );
+            file_data
+        };
+
+        if segment_info.check_hash {
+            result_return_unless!(
+                sha256::hash(&segment_data) == segment_info.hash,
+                results::lib::nso::ResultSegmentHashMismatch
+            );
+        }
+        Ok(segment_data)
+    }
+
+    // Module as it would be mapped in memory (.bss zero-filled)
+    pub fn load_image(&self) -> Result<Vec<u8>> {
+        let mut image = vec![0u8; self.header.get_image_size()];
+        for segment in &[Segment::Text, Segment::Ro, Segment::Data] {
+            let segment_data = self.read_segment(*segment)?;
+            let memory_offset =
+                self.header.get_segment_info(*segment).header.memory_offset as usize;
+            image[memory_offset..memory_offset + segment_data.len()].copy_from_slice(&segment_data);
+        }
+        Ok(image)
+    }
+}
+
+fn read_image_val<T: Copy>(image: &[u8], offset: usize) -> Result<T> {
+    match offset.checked_add(cmem::size_of::<T>()) {
+        Some(end_offset) if end_offset <= image.len() => unsafe {
+            Ok(ptr::read_unaligned(image.as_ptr().add(offset) as *const T))
+        },
+        _ => Err(results::lib::nso::ResultInvalidModule::make()),
+    }
+}
+
+// Locates the MOD0 header through the module start at the beginning of .text,
+// returning its offset in the loaded image
+pub fn find_mod0(image: &[u8]) -> Result<(usize, mod0::Header)> {
+    let module_start: dynamic::ModuleStart = read_image_val(image, 0)?;
+    let mod0_offset = module_start.magic_offset as usize;
+    let mod0_header: mod0::Header = read_image_val(image, mod0_offset)?;
+    result_return_unless!(
+        mod0_header.magic == mod0::MAGIC,
+        results::lib::dynamic::ResultInvalidModuleMagic
+    );
+    Ok((mod0_offset, mod0_header))
+}
+
+// MOD0 offsets are relative to the MOD0 header itself
+pub fn get_dynamic_offset(image: &[u8]) -> Result<usize> {
+    let (mod0_offset, mod0_header) = find_mod0(image)?;
+    let dynamic_offset = (mod0_offset as isize + mod0_header.dynamic as i32 as isize) as usize;
+    result_return_unless!(
+        dynamic_offset < image.len(),
+        results::lib::nso::ResultInvalidModule
+    );
+    Ok(dynamic_offset)
+}
+
+pub fn open_file(path: String) -> Result<Nso> {
+    let nso_file = fs::open_file(path, fs::FileOpenOption::Read())?;
+    Nso::new(mem::Shared::new(fs::romfs::FileSource::new(
+        nso_file.get_handle(),
+        0,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"text segment text segment text segment text segment";
+
+    fn make_nso(flags: Flags, text_hash: [u8; 0x20]) -> Nso {
+        let header_size = cmem::size_of::<Header>();
+        let text_file_data = match flags.contains(Flags::TextCompressed()) {
+            true => lz4::compress_block(TEXT),
+            false => TEXT.to_vec(),
+        };
+        let segments_end = (header_size + text_file_data.len()) as u32;
+
+        let header = Header {
+            magic: MAGIC,
+            flags,
+            text: SegmentHeader {
+                file_offset: header_size as u32,
+                memory_offset: 0,
+                size: TEXT.len() as u32,
+            },
+            ro: SegmentHeader {
+                file_offset: segments_end,
+                memory_offset: TEXT.len() as u32,
+                size: 0,
+            },
+            data: SegmentHeader {
+                file_offset: segments_end,
+                memory_offset: TEXT.len() as u32,
+                size: 0,
+            },
+            text_file_size: text_file_data.len() as u32,
+            text_hash,
+            ..Default::default()
+        };
+        let mut nso_data =
+            unsafe { slice::from_raw_parts(&header as *const _ as *const u8, header_size) }
+                .to_vec();
+        nso_data.extend_from_slice(&text_file_data);
+        Nso::new(mem::Shared::new(nso_data)).unwrap()
+    }
+
+    #[test]
+    fn read_hashed_segments() {
+        let text_hash = sha256::hash(TEXT);
+        let nso = make_nso(Flags::TextHash(), text_hash);
+        assert_eq!(nso.read_segment(Segment::Text).unwrap(), TEXT);
+
+        let nso = make_nso(Flags::TextCompressed() | Flags::TextHash(), text_hash);
+        assert_eq!(nso.read_segment(Segment::Text).unwrap(), TEXT);
+        assert_eq!(nso.load_image().unwrap(), TEXT);
+    }
+
+    #[test]
+    fn reject_hash_mismatch() {
+        let mut text_hash = sha256::hash(TEXT);
+        text_hash[0] ^= 0xFF;
+
+        let nso = make_nso(Flags::TextCompressed() | Flags::TextHash(), text_hash);
+        assert!(results::lib::nso::ResultSegmentHashMismatch::matches(
+            nso.read_segment(Segment::Text).unwrap_err()
+        ));
+
+        // Hashes are only checked when the flag is set
+        let nso = make_nso(Flags::TextCompressed(), text_hash);
+        assert_eq!(nso.read_segment(Segment::Text).unwrap(), TEXT);
+    }
+}
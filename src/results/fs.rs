@@ -2,5 +2,10 @@ pub const RESULT_MODULE: u32 = 2;
 
 result_define_group!(RESULT_MODULE => {
     PathNotFound: 1,
-    PathAlreadyExists: 2
+    PathAlreadyExists: 2,
+    DirectoryNotEmpty: 8,
+    FileExtensionWithoutOpenModeAllowAppend: 6201,
+    ReadNotPermitted: 6202,
+    WriteNotPermitted: 6203,
+    UnsupportedOperation: 6300
 });
@@ -2,5 +2,7 @@ pub const RESULT_MODULE: u32 = 11;
 
 result_define_group!(RESULT_MODULE => {
     UnsupportedOperation: 1,
-    SessionClosed: 301
+    SessionClosed: 301,
+    InvalidRequestSize: 402,
+    UnknownCommandType: 403
 });
@@ -2,5 +2,10 @@ pub const RESULT_SUBMODULE: u32 = 100;
 
 result_define_subgroup!(super::RESULT_MODULE, RESULT_SUBMODULE => {
     RelaSizeMismatch: 1,
-    InvalidModuleMagic: 2
+    InvalidModuleMagic: 2,
+    SymbolNotFound: 3,
+    UnknownRelocationType: 4,
+    UnresolvedSymbol: 5,
+    UnsupportedRelocationFormat: 6,
+    OutOfMemory: 7
 });
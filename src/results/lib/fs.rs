@@ -0,0 +1,22 @@
+pub const RESULT_SUBMODULE: u32 = 600;
+
+result_define_subgroup!(super::RESULT_MODULE, RESULT_SUBMODULE => {
+    DifferentDevices: 1,
+    InvalidRomFsHeader: 2,
+    InvalidRomFsEntry: 3,
+    SourceOutOfBounds: 4,
+    NroPathNotAvailable: 5,
+    NroRomFsNotFound: 6,
+    NotInitialized: 7,
+    DeviceNotFound: 8,
+    DeviceAlreadyMounted: 9,
+    InvalidPath: 10,
+    PathTooLong: 11,
+    PathOutsideOfRoot: 12,
+    NoCurrentDirectory: 13,
+    DeviceRemoved: 14,
+    InvalidPartitionHeader: 15,
+    InvalidPartitionEntry: 16,
+    PartitionHashMismatch: 17,
+    ContentPathNotResolvable: 18
+});
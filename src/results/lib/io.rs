@@ -0,0 +1,8 @@
+pub const RESULT_SUBMODULE: u32 = 700;
+
+result_define_subgroup!(super::RESULT_MODULE, RESULT_SUBMODULE => {
+    UnexpectedEof: 1,
+    WriteZero: 2,
+    InvalidSeek: 3,
+    InvalidUtf8: 4
+});
@@ -0,0 +1,5 @@
+pub const RESULT_SUBMODULE: u32 = 1200;
+
+result_define_subgroup!(super::RESULT_MODULE, RESULT_SUBMODULE => {
+    UnsupportedFirmware: 1
+});
@@ -0,0 +1,6 @@
+pub const RESULT_SUBMODULE: u32 = 1000;
+
+result_define_subgroup!(super::RESULT_MODULE, RESULT_SUBMODULE => {
+    InvalidBlock: 1,
+    OutputTooSmall: 2
+});
@@ -9,3 +9,17 @@ pub mod gpu;
 pub mod elf;
 
 pub mod util;
+
+pub mod fs;
+
+pub mod io;
+
+pub mod nro;
+
+pub mod nacp;
+
+pub mod lz4;
+
+pub mod nso;
+
+pub mod ipc;
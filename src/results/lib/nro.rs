@@ -0,0 +1,7 @@
+pub const RESULT_SUBMODULE: u32 = 800;
+
+result_define_subgroup!(super::RESULT_MODULE, RESULT_SUBMODULE => {
+    InvalidHeader: 1,
+    AssetsNotFound: 2,
+    AssetNotFound: 3
+});
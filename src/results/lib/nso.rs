@@ -0,0 +1,8 @@
+pub const RESULT_SUBMODULE: u32 = 1100;
+
+result_define_subgroup!(super::RESULT_MODULE, RESULT_SUBMODULE => {
+    InvalidHeader: 1,
+    InvalidSegment: 2,
+    InvalidModule: 3,
+    SegmentHashMismatch: 4
+});
@@ -0,0 +1,17 @@
+use crate::{result::*, service};
+
+pub use crate::ipc::cmif::sf::lr::*;
+
+impl service::cmif::IService for LocationResolverManager {
+    fn get_name() -> &'static str {
+        nul!("lr")
+    }
+
+    fn as_domain() -> bool {
+        false
+    }
+
+    fn post_initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -74,3 +74,7 @@ pub mod set;
 pub mod mii;
 
 pub mod spl;
+
+pub mod ro;
+
+pub mod lr;
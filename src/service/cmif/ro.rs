@@ -0,0 +1,38 @@
+use crate::{ipc::cmif::sf, result::*, service, svc};
+
+pub use crate::ipc::cmif::sf::ro::*;
+
+impl service::cmif::IService for RoInterface {
+    fn get_name() -> &'static str {
+        nul!("ldr:ro")
+    }
+
+    fn as_domain() -> bool {
+        false
+    }
+
+    fn post_initialize(&mut self) -> Result<()> {
+        self.initialize(
+            sf::ProcessId::new(),
+            sf::Handle::from(svc::CURRENT_PROCESS_PSEUDO_HANDLE),
+        )
+    }
+}
+
+// Same interface as ldr:ro, meant for JIT-like usage (7.0.0+)
+impl service::cmif::IService for JitRoInterface {
+    fn get_name() -> &'static str {
+        nul!("ro:1")
+    }
+
+    fn as_domain() -> bool {
+        false
+    }
+
+    fn post_initialize(&mut self) -> Result<()> {
+        self.initialize(
+            sf::ProcessId::new(),
+            sf::Handle::from(svc::CURRENT_PROCESS_PSEUDO_HANDLE),
+        )
+    }
+}
@@ -2,6 +2,46 @@ use crate::{ipc::cmif::sf, result::*, service};
 
 pub use crate::ipc::cmif::sf::set::*;
 
+pub struct SettingsServer {
+    session: sf::Session,
+}
+
+impl sf::IObject for SettingsServer {
+    fn get_session(&mut self) -> &mut sf::Session {
+        &mut self.session
+    }
+
+    fn get_command_table(&self) -> sf::CommandMetadataTable {
+        vec![ipc_cmif_interface_make_command_meta!(get_language_code: 0)]
+    }
+}
+
+impl service::cmif::IClientObject for SettingsServer {
+    fn new(session: sf::Session) -> Self {
+        Self { session }
+    }
+}
+
+impl ISettingsServer for SettingsServer {
+    fn get_language_code(&mut self) -> Result<LanguageCode> {
+        ipc_cmif_client_send_request_command!([self.session.object_info; 0] () => (language_code: LanguageCode))
+    }
+}
+
+impl service::cmif::IService for SettingsServer {
+    fn get_name() -> &'static str {
+        nul!("set")
+    }
+
+    fn as_domain() -> bool {
+        false
+    }
+
+    fn post_initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
 pub struct SystemSettingsServer {
     session: sf::Session,
 }